@@ -0,0 +1,203 @@
+//! Node category styles
+//!
+//! A [`NodeCategory`] is only a name; the [`CategoryRegistry`] maps names to
+//! the colors and icon a node of that category is drawn with. Host apps
+//! register their own categories in code or load them from a theme file:
+//!
+//! ```json
+//! {
+//!   "categories": [
+//!     { "name": "llm", "label": "LLM", "color": "#3366b3", "header_color": "#4073bf", "icon": "✦" },
+//!     { "name": "tts", "label": "TTS", "color": "#33994d", "header_color": "#40a659" }
+//!   ]
+//! }
+//! ```
+//!
+//! Colors are `#rgb`, `#rrggbb` or `#rrggbbaa`. Nodes whose category is not
+//! registered use the registry's default style.
+
+use makepad_widgets::*;
+use serde::Deserialize;
+use std::fmt;
+use crate::graph::NodeCategory;
+
+/// How nodes of one category are drawn
+#[derive(Clone, Debug, PartialEq)]
+pub struct CategoryStyle {
+    /// Short name shown in lists and filters
+    pub label: String,
+    /// Body color; zoomed out blocks and the minimap use it for the whole node
+    pub color: Vec4,
+    pub header_color: Vec4,
+    /// Border when the node is not selected; `None` keeps the shape's default
+    pub border_color: Option<Vec4>,
+    /// Text drawn before the node title
+    pub icon: Option<String>,
+}
+
+impl Default for CategoryStyle {
+    fn default() -> Self {
+        Self {
+            label: String::new(),
+            color: vec4(0.24, 0.24, 0.36, 1.0),
+            header_color: vec4(0.30, 0.30, 0.45, 1.0),
+            border_color: None,
+            icon: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CategoryError {
+    /// The theme file is not valid JSON or does not match the schema
+    Json(serde_json::Error),
+    /// A color is not a `#rgb`, `#rrggbb` or `#rrggbbaa` hex string
+    InvalidColor { category: String, value: String },
+}
+
+impl fmt::Display for CategoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CategoryError::Json(e) => write!(f, "invalid category theme: {}", e),
+            CategoryError::InvalidColor { category, value } => write!(
+                f, "category {}: invalid color {:?}", category, value
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CategoryError {}
+
+impl From<serde_json::Error> for CategoryError {
+    fn from(e: serde_json::Error) -> Self {
+        CategoryError::Json(e)
+    }
+}
+
+// Theme file schema
+#[derive(Deserialize)]
+struct ThemeFile {
+    #[serde(default)]
+    categories: Vec<ThemeCategory>,
+}
+
+#[derive(Deserialize)]
+struct ThemeCategory {
+    name: String,
+    #[serde(default)]
+    label: Option<String>,
+    color: String,
+    #[serde(default)]
+    header_color: Option<String>,
+    #[serde(default)]
+    border_color: Option<String>,
+    #[serde(default)]
+    icon: Option<String>,
+}
+
+/// Styles by category name, in registration order
+#[derive(Clone, Debug, Default)]
+pub struct CategoryRegistry {
+    styles: Vec<(NodeCategory, CategoryStyle)>,
+    default_style: CategoryStyle,
+}
+
+impl CategoryRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry with the categories of a JSON theme file
+    pub fn from_json(json: &str) -> Result<Self, CategoryError> {
+        let mut registry = Self::new();
+        registry.load_json(json)?;
+        Ok(registry)
+    }
+
+    /// Add or replace the categories of a JSON theme file.
+    /// Nothing is changed if the file has an error.
+    pub fn load_json(&mut self, json: &str) -> Result<(), CategoryError> {
+        let theme: ThemeFile = serde_json::from_str(json)?;
+        let mut styles = Vec::with_capacity(theme.categories.len());
+        for entry in theme.categories {
+            let color_of = |value: &str| parse_hex_color(value).ok_or_else(|| CategoryError::InvalidColor {
+                category: entry.name.clone(),
+                value: value.to_string(),
+            });
+            let color = color_of(&entry.color)?;
+            let header_color = entry.header_color.as_deref().map(color_of).transpose()?.unwrap_or(color);
+            let border_color = entry.border_color.as_deref().map(color_of).transpose()?;
+            styles.push((entry.name.clone(), CategoryStyle {
+                label: entry.label.clone().unwrap_or_else(|| entry.name.clone()),
+                color,
+                header_color,
+                border_color,
+                icon: entry.icon.clone(),
+            }));
+        }
+        for (name, style) in styles {
+            self.register(&name, style);
+        }
+        Ok(())
+    }
+
+    /// Add a category, replacing any style already registered under `name`
+    pub fn register(&mut self, name: &str, style: CategoryStyle) {
+        match self.styles.iter_mut().find(|(c, _)| c.name() == name) {
+            Some((_, existing)) => *existing = style,
+            None => self.styles.push((NodeCategory::new(name), style)),
+        }
+    }
+
+    pub fn unregister(&mut self, name: &str) {
+        self.styles.retain(|(c, _)| c.name() != name);
+    }
+
+    /// Registered style of a category, if any
+    pub fn get(&self, category: &NodeCategory) -> Option<&CategoryStyle> {
+        self.styles.iter().find(|(c, _)| c == category).map(|(_, s)| s)
+    }
+
+    /// Style a category is drawn with, falling back to the default style
+    pub fn style(&self, category: &NodeCategory) -> &CategoryStyle {
+        self.get(category).unwrap_or(&self.default_style)
+    }
+
+    /// Style of categories that are not registered
+    pub fn default_style(&self) -> &CategoryStyle {
+        &self.default_style
+    }
+
+    pub fn set_default_style(&mut self, style: CategoryStyle) {
+        self.default_style = style;
+    }
+
+    /// Registered categories, in registration order
+    pub fn categories(&self) -> impl Iterator<Item = (&NodeCategory, &CategoryStyle)> {
+        self.styles.iter().map(|(c, s)| (c, s))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.styles.is_empty()
+    }
+}
+
+/// Parse `#rgb`, `#rrggbb` or `#rrggbbaa` into a color
+pub fn parse_hex_color(value: &str) -> Option<Vec4> {
+    let hex = value.strip_prefix('#')?;
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digits: Vec<u8> = match hex.len() {
+        3 => hex.bytes().flat_map(|b| [b, b]).collect(),
+        6 | 8 => hex.bytes().collect(),
+        _ => return None,
+    };
+    let channel = |i: usize| {
+        std::str::from_utf8(&digits[i * 2..i * 2 + 2]).ok()
+            .and_then(|s| u8::from_str_radix(s, 16).ok())
+            .map(|v| v as f32 / 255.0)
+    };
+    let alpha = if digits.len() == 8 { channel(3)? } else { 1.0 };
+    Some(vec4(channel(0)?, channel(1)?, channel(2)?, alpha))
+}
@@ -0,0 +1,129 @@
+//! Connection validation
+//!
+//! Before an interactively drawn edge is added, the canvas asks its
+//! [`ConnectionValidator`] whether the link is allowed. Without a custom
+//! validator, [`DefaultValidator`] applies the built-in rules: no self-loops,
+//! no duplicates, matching port data types and per-port connection limits.
+
+use crate::graph::*;
+use std::fmt;
+
+/// Port data type that connects to any other type
+pub const ANY_DATA_TYPE: &str = "any";
+
+/// Why a connection was refused
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConnectionRejection {
+    /// Source and target are the same node
+    SelfLoop,
+    /// The node or port does not exist
+    MissingEndpoint(String),
+    /// An identical edge already exists
+    Duplicate,
+    /// Port data types do not match
+    TypeMismatch { from_type: String, to_type: String },
+    /// The port already has its maximum number of connections
+    PortFull { node: String, port: String, max: usize },
+    /// Rejected by an application-specific rule
+    Custom(String),
+}
+
+impl fmt::Display for ConnectionRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionRejection::SelfLoop => write!(f, "a node cannot connect to itself"),
+            ConnectionRejection::MissingEndpoint(what) => write!(f, "{} does not exist", what),
+            ConnectionRejection::Duplicate => write!(f, "these ports are already connected"),
+            ConnectionRejection::TypeMismatch { from_type, to_type } => {
+                write!(f, "cannot connect {} output to {} input", from_type, to_type)
+            }
+            ConnectionRejection::PortFull { node, port, max } => {
+                write!(f, "{}/{} accepts at most {} connection(s)", node, port, max)
+            }
+            ConnectionRejection::Custom(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// Decides whether an edge may be added to a graph.
+/// Install one on a canvas with `FlowCanvasRef::set_connection_validator`.
+pub trait ConnectionValidator {
+    fn validate(&self, graph: &FlowGraph, edge: &EdgeConnection) -> Result<(), ConnectionRejection>;
+}
+
+/// The built-in rules. Custom validators can call this first and then add
+/// their own checks.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultValidator;
+
+impl ConnectionValidator for DefaultValidator {
+    fn validate(&self, graph: &FlowGraph, edge: &EdgeConnection) -> Result<(), ConnectionRejection> {
+        if edge.from_node == edge.to_node {
+            return Err(ConnectionRejection::SelfLoop);
+        }
+        let from_node = graph.node(&edge.from_node)
+            .ok_or_else(|| ConnectionRejection::MissingEndpoint(format!("node {}", edge.from_node)))?;
+        let to_node = graph.node(&edge.to_node)
+            .ok_or_else(|| ConnectionRejection::MissingEndpoint(format!("node {}", edge.to_node)))?;
+        let from_port = resolve_port(&from_node.output_ports, &edge.from_port)
+            .ok_or_else(|| ConnectionRejection::MissingEndpoint(format!("output {}/{}", edge.from_node, edge.from_port)))?;
+        let to_port = resolve_port(&to_node.input_ports, &edge.to_port)
+            .ok_or_else(|| ConnectionRejection::MissingEndpoint(format!("input {}/{}", edge.to_node, edge.to_port)))?;
+
+        let duplicate = graph.edges().iter().any(|e|
+            e.from_node == edge.from_node && e.to_node == edge.to_node &&
+            resolve_port(&from_node.output_ports, &e.from_port).is_some_and(|p| p.id == from_port.id) &&
+            resolve_port(&to_node.input_ports, &e.to_port).is_some_and(|p| p.id == to_port.id)
+        );
+        if duplicate {
+            return Err(ConnectionRejection::Duplicate);
+        }
+
+        if let (Some(from_type), Some(to_type)) = (&from_port.data_type, &to_port.data_type) {
+            if !types_compatible(from_type, to_type) {
+                return Err(ConnectionRejection::TypeMismatch {
+                    from_type: from_type.clone(),
+                    to_type: to_type.clone(),
+                });
+            }
+        }
+
+        check_capacity(graph, &edge.from_node, from_port, &from_node.output_ports, true)?;
+        check_capacity(graph, &edge.to_node, to_port, &to_node.input_ports, false)?;
+        Ok(())
+    }
+}
+
+/// Whether an output of `from_type` may feed an input of `to_type`
+pub fn types_compatible(from_type: &str, to_type: &str) -> bool {
+    from_type == to_type || from_type == ANY_DATA_TYPE || to_type == ANY_DATA_TYPE
+}
+
+/// Find a port by id; an empty id means the node's first port (legacy edges)
+fn resolve_port<'a>(ports: &'a [Port], port_id: &str) -> Option<&'a Port> {
+    if port_id.is_empty() {
+        ports.first()
+    } else {
+        ports.iter().find(|p| p.id == port_id)
+    }
+}
+
+fn check_capacity(graph: &FlowGraph, node_id: &str, port: &Port, ports: &[Port], is_output: bool) -> Result<(), ConnectionRejection> {
+    let Some(max) = port.max_connections else {
+        return Ok(());
+    };
+    let used = graph.edges().iter()
+        .filter(|e| {
+            let (edge_node, edge_port) = if is_output { (&e.from_node, &e.from_port) } else { (&e.to_node, &e.to_port) };
+            edge_node == node_id && resolve_port(ports, edge_port).is_some_and(|p| p.id == port.id)
+        })
+        .count();
+    if used >= max {
+        return Err(ConnectionRejection::PortFull {
+            node: node_id.to_string(),
+            port: port.id.clone(),
+            max,
+        });
+    }
+    Ok(())
+}
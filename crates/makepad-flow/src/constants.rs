@@ -9,6 +9,10 @@ pub mod node {
     pub const WIDTH: f64 = 180.0;
     /// Minimum node height in pixels
     pub const MIN_HEIGHT: f64 = 80.0;
+    /// Smallest width a node can be resized to
+    pub const MIN_WIDTH: f64 = 100.0;
+    /// Resize handle size in screen pixels
+    pub const RESIZE_HANDLE_SIZE: f64 = 8.0;
     /// Camera node height in pixels
     pub const CAMERA_HEIGHT: f64 = 80.0;
     /// Default node height in pixels
@@ -25,6 +29,10 @@ pub mod node {
     pub const BORDER_WIDTH: f32 = 2.0;
     /// Legacy Y offset for single-port nodes
     pub const LEGACY_PORT_Y_OFFSET: f64 = 54.0;
+    /// Title font size of nodes drawn as blocks when zoomed out; does not scale with zoom
+    pub const BLOCK_TITLE_FONT_SIZE: f32 = 9.0;
+    /// Approximate character width of block titles, for truncation
+    pub const BLOCK_TITLE_CHAR_WIDTH: f64 = 6.0;
 }
 
 /// Port rendering constants
@@ -51,8 +59,21 @@ pub mod edge {
     pub const WIDTH_F32: f32 = 2.0;
     /// Number of segments for bezier curve rendering
     pub const BEZIER_SEGMENTS: usize = 100;
+    /// Minimum horizontal run out of / into a port for step paths
+    pub const STEP_OFFSET: f64 = 20.0;
+    /// Corner radius for smooth-step paths
+    pub const SMOOTH_STEP_RADIUS: f64 = 10.0;
+    /// Number of segments per rounded corner
+    pub const CORNER_SEGMENTS: usize = 8;
+    /// Chords the edge shader measures a curved piece with; at most 32, its loop bound
+    pub const CURVE_SEGMENTS: usize = 24;
     /// Distance threshold for edge hit detection
     pub const HIT_DISTANCE: f64 = 8.0;
+    /// On-screen distance from an edge end within which dragging reconnects that end;
+    /// never more than a port's hit area in canvas units
+    pub const RECONNECT_RADIUS: f64 = 24.0;
+    /// On-screen distance a grabbed edge end must be dragged before it is detached
+    pub const RECONNECT_DRAG_THRESHOLD: f64 = 4.0;
     /// Dash length for dashed lines
     pub const DASH_LENGTH: f64 = 12.0;
     /// Gap between dashes
@@ -63,6 +84,10 @@ pub mod edge {
     pub const ARROW_SIZE_MULTIPLIER: f64 = 4.0;
     /// Particle size multiplier for animated edges
     pub const PARTICLE_SIZE_MULTIPLIER: f64 = 2.0;
+    /// Distance between particles on animated edges
+    pub const PARTICLE_SPACING: f64 = 30.0;
+    /// Dot size multiplier for dotted lines (relative to line thickness)
+    pub const DOT_SIZE_MULTIPLIER: f64 = 1.5;
 }
 
 /// Canvas interaction constants
@@ -75,6 +100,14 @@ pub mod canvas {
     pub const FIT_VIEW_PADDING: f64 = 50.0;
     /// Maximum undo history size
     pub const UNDO_STACK_SIZE: usize = 50;
+    /// Grid cells smaller than this on screen are merged (spacing doubled)
+    pub const GRID_MIN_SCREEN_SPACING: f64 = 8.0;
+    /// Radius of grid dots in pixels
+    pub const GRID_DOT_RADIUS: f32 = 1.0;
+    /// Width of grid lines in pixels
+    pub const GRID_LINE_WIDTH: f32 = 1.0;
+    /// Distance a duplicated selection is moved from the original
+    pub const DUPLICATE_OFFSET: f64 = 20.0;
 }
 
 /// Context menu constants
@@ -105,16 +138,49 @@ pub mod label {
     pub const TEXT_Y_OFFSET: f64 = 6.0;
 }
 
-/// Shape rendering constants (for Round and Diamond shapes)
-pub mod shape {
-    /// Number of segments for circular shapes
-    pub const CIRCLE_SEGMENTS: usize = 72;
-    /// Pixel step size for shape filling
-    pub const FILL_STEP: f64 = 4.0;
-    /// Small pixel size for shape rendering
-    pub const PIXEL_SIZE: f64 = 4.0;
-    /// Line step for diamond border
-    pub const LINE_STEP: f64 = 2.0;
-    /// Steps divisor for diamond border
-    pub const BORDER_STEPS_DIVISOR: f64 = 3.0;
+/// Orthogonal edge routing constants
+pub mod routing {
+    /// Clearance kept between routed edges and node borders
+    pub const MARGIN: f64 = 16.0;
+    /// Extra path cost per bend, in pixels of length
+    pub const BEND_PENALTY: f64 = 40.0;
+    /// Distance between parallel segments sharing a corridor
+    pub const NUDGE_SPACING: f64 = 6.0;
+    /// How far beyond the endpoints' bounding box a route may wander
+    pub const SEARCH_PADDING: f64 = 240.0;
+}
+
+/// Live force-directed layout constants
+pub mod simulation {
+    /// Seconds between simulation steps
+    pub const INTERVAL: f64 = 1.0 / 60.0;
+    /// Temperature the simulation is raised to while nodes are dragged
+    pub const DRAG_REHEAT: f64 = 0.3;
+}
+
+/// Group (frame) node constants
+pub mod group {
+    /// Space between a group frame and its children
+    pub const PADDING: f64 = 20.0;
+    /// Separator between a child id and its port id in a collapsed group's ports
+    pub const PORT_SEPARATOR: char = '/';
+}
+
+/// Spatial index constants
+pub mod spatial {
+    /// Side of a grid cell in canvas units
+    pub const CELL_SIZE: f64 = 256.0;
+    /// Added around node bounds so port hit areas, which stick out of the node, are found
+    pub const NODE_MARGIN: f64 = 18.0;
+    /// Items spanning more cells than this are checked on every query instead of bucketed
+    pub const MAX_ITEM_CELLS: u64 = 64;
+    /// Added around the visible area when culling, in screen pixels, so markers
+    /// and labels of edges just off screen still draw
+    pub const CULL_MARGIN: f64 = 64.0;
+}
+
+/// Node palette constants
+pub mod palette {
+    /// Pointer travel in pixels before pressing an entry starts a drag
+    pub const DRAG_THRESHOLD: f64 = 4.0;
 }
@@ -0,0 +1,170 @@
+//! JSON persistence for complete flows
+//!
+//! A [`FlowDocument`] bundles the graph (nodes, ports, edges with all per-edge
+//! styling) with the viewport so an editor session can be restored exactly.
+//! Documents carry a `version` field; older versions are upgraded on load by
+//! the step functions in [`MIGRATIONS`].
+
+use crate::graph::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// Schema version written by this crate
+pub const FLOW_DOCUMENT_VERSION: u32 = 1;
+
+/// Upgrade steps between schema versions.
+/// Entry `i` rewrites a version `i + 1` document into version `i + 2` in place,
+/// so supporting a new version means bumping [`FLOW_DOCUMENT_VERSION`] and
+/// appending one function here.
+pub const MIGRATIONS: &[fn(&mut Value)] = &[];
+
+/// Pan offset and zoom of a canvas
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FlowViewport {
+    pub pan_x: f64,
+    pub pan_y: f64,
+    pub zoom: f64,
+}
+
+impl Default for FlowViewport {
+    fn default() -> Self {
+        Self { pan_x: 0.0, pan_y: 0.0, zoom: 1.0 }
+    }
+}
+
+/// Serialized form of a flow
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FlowDocument {
+    pub version: u32,
+    #[serde(default)]
+    pub viewport: FlowViewport,
+    #[serde(default)]
+    pub nodes: Vec<FlowNode>,
+    #[serde(default)]
+    pub edges: Vec<EdgeConnection>,
+}
+
+#[derive(Debug)]
+pub enum DocumentError {
+    /// The text is not valid JSON or does not match the schema
+    Json(serde_json::Error),
+    /// The document was written by a newer version of this crate
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentError::Json(e) => write!(f, "invalid flow document: {}", e),
+            DocumentError::UnsupportedVersion(v) => write!(
+                f, "flow document version {} is newer than supported version {}",
+                v, FLOW_DOCUMENT_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DocumentError {}
+
+impl From<serde_json::Error> for DocumentError {
+    fn from(e: serde_json::Error) -> Self {
+        DocumentError::Json(e)
+    }
+}
+
+impl FlowDocument {
+    pub fn new(graph: &FlowGraph, viewport: FlowViewport) -> Self {
+        Self {
+            version: FLOW_DOCUMENT_VERSION,
+            viewport,
+            nodes: graph.nodes().to_vec(),
+            edges: graph.edges().to_vec(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, DocumentError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Parse a document, migrating it to the current version first.
+    /// Documents without a `version` field are treated as version 1.
+    pub fn from_json(json: &str) -> Result<Self, DocumentError> {
+        let mut value: Value = serde_json::from_str(json)?;
+        let version = value.get("version")
+            .and_then(Value::as_u64)
+            .unwrap_or(1) as u32;
+        migrate(&mut value, version)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn into_graph(self) -> FlowGraph {
+        FlowGraph::from_parts(self.nodes, self.edges)
+    }
+}
+
+/// Upgrade a raw document from `version` to [`FLOW_DOCUMENT_VERSION`]
+fn migrate(value: &mut Value, version: u32) -> Result<(), DocumentError> {
+    if version == 0 || version > FLOW_DOCUMENT_VERSION {
+        return Err(DocumentError::UnsupportedVersion(version));
+    }
+    for step in &MIGRATIONS[(version - 1) as usize..] {
+        step(value);
+    }
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("version".to_string(), Value::from(FLOW_DOCUMENT_VERSION));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> FlowGraph {
+        let mut graph = FlowGraph::new();
+        for (id, x) in [("a", 0.0), ("b", 200.0)] {
+            graph.add_node(FlowNode::new_dataflow(id, x, 40.0, id, NodeCategory::new("io"), vec![Port::new("in")], vec![Port::new("out")]));
+        }
+        let mut edge = EdgeConnection::new_with_ports("a", "out", "b", "in");
+        edge.label = "frames".to_string();
+        graph.connect(edge);
+        graph
+    }
+
+    #[test]
+    fn version_zero_is_rejected() {
+        let mut value = serde_json::json!({ "version": 0 });
+        assert!(matches!(migrate(&mut value, 0), Err(DocumentError::UnsupportedVersion(0))));
+        assert!(matches!(FlowDocument::from_json(r#"{ "version": 0 }"#), Err(DocumentError::UnsupportedVersion(0))));
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let newer = FLOW_DOCUMENT_VERSION + 1;
+        let json = format!(r#"{{ "version": {} }}"#, newer);
+        assert!(matches!(FlowDocument::from_json(&json), Err(DocumentError::UnsupportedVersion(v)) if v == newer));
+    }
+
+    #[test]
+    fn missing_version_loads_as_version_one() {
+        let document = FlowDocument::from_json(r#"{ "nodes": [], "edges": [] }"#).unwrap();
+        assert_eq!(document.version, FLOW_DOCUMENT_VERSION);
+        assert_eq!(document.viewport, FlowViewport::default());
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let graph = sample();
+        let viewport = FlowViewport { pan_x: 12.0, pan_y: -30.0, zoom: 1.5 };
+        let json = FlowDocument::new(&graph, viewport).to_json().unwrap();
+
+        let document = FlowDocument::from_json(&json).unwrap();
+        assert_eq!(document.version, FLOW_DOCUMENT_VERSION);
+        assert_eq!(document.viewport, viewport);
+        let loaded = document.into_graph();
+        assert_eq!(loaded.nodes(), graph.nodes());
+        assert_eq!(loaded.edges(), graph.edges());
+        assert_eq!(FlowDocument::new(&loaded, viewport).to_json().unwrap(), json);
+    }
+}
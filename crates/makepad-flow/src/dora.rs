@@ -0,0 +1,349 @@
+//! Dora dataflow YAML import/export (enabled with the `dora` cargo feature)
+//!
+//! Every dora node becomes a [`FlowNode`] whose ports are the node's `inputs`
+//! and `outputs`; every input wired to another node's output becomes an
+//! [`EdgeConnection`]. Fields the graph has no member for are kept as metadata
+//! so an edited graph can be written back:
+//!
+//! - node fields other than `id`/`inputs`/`outputs` (`build`, `path`, `env`, ...)
+//!   go to `FlowNode::metadata`
+//! - per-input settings other than `source` (`queue_size`, ...) go to
+//!   `EdgeConnection::metadata`
+//! - inputs fed by something that is not a node in the file (`dora/timer/...`)
+//!   keep their raw definition in the input `Port::metadata`
+//! - top-level keys other than `nodes` go to [`DoraDataflow::extra`]
+//!
+//! YAML comments and anchors are not preserved.
+
+use crate::graph::*;
+use serde_json::Value as JsonValue;
+use serde_yaml::{Mapping, Value as YamlValue};
+use std::fmt;
+
+/// Node fields in the order they are written on export.
+/// Fields not listed here follow in alphabetical order.
+const NODE_FIELD_ORDER: &[&str] = &[
+    "name", "description", "build", "git", "branch", "tag", "rev",
+    "path", "args", "operator", "operators", "custom", "env",
+];
+
+/// Input port metadata key holding a source that is not a node of the dataflow
+const EXTERNAL_SOURCE: &str = "source";
+
+#[derive(Debug)]
+pub enum DoraError {
+    Yaml(serde_yaml::Error),
+    /// Structurally valid YAML that is not a dora dataflow
+    Invalid(String),
+}
+
+impl fmt::Display for DoraError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DoraError::Yaml(e) => write!(f, "YAML error: {}", e),
+            DoraError::Invalid(msg) => write!(f, "invalid dataflow: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for DoraError {}
+
+impl From<serde_yaml::Error> for DoraError {
+    fn from(e: serde_yaml::Error) -> Self {
+        DoraError::Yaml(e)
+    }
+}
+
+/// A dora dataflow loaded into a graph
+#[derive(Clone, Debug, Default)]
+pub struct DoraDataflow {
+    pub graph: FlowGraph,
+    /// Top-level keys other than `nodes` (e.g. `communication`), written back unchanged
+    pub extra: Metadata,
+}
+
+impl DoraDataflow {
+    pub fn from_yaml(yaml: &str) -> Result<Self, DoraError> {
+        import_dataflow(yaml)
+    }
+
+    pub fn to_yaml(&self) -> Result<String, DoraError> {
+        export_dataflow(&self.graph, &self.extra)
+    }
+}
+
+/// Parse a dora dataflow into a graph.
+/// Nodes are created at the origin; run a layout afterwards to place them.
+pub fn import_dataflow(yaml: &str) -> Result<DoraDataflow, DoraError> {
+    let root: YamlValue = serde_yaml::from_str(yaml)?;
+    let YamlValue::Mapping(root) = root else {
+        return Err(DoraError::Invalid("top level is not a mapping".to_string()));
+    };
+
+    let mut extra = Metadata::new();
+    let mut node_defs = Vec::new();
+    for (key, value) in root {
+        let key = yaml_key(&key)?;
+        if key == "nodes" {
+            let YamlValue::Sequence(nodes) = value else {
+                return Err(DoraError::Invalid("`nodes` is not a list".to_string()));
+            };
+            node_defs = nodes;
+        } else {
+            extra.insert(key, to_json(value)?);
+        }
+    }
+
+    // First pass: nodes and ports. Input sources are resolved once all ids are known.
+    let mut graph = FlowGraph::new();
+    let mut pending_inputs = Vec::new();
+    for def in node_defs {
+        let YamlValue::Mapping(def) = def else {
+            return Err(DoraError::Invalid("node entry is not a mapping".to_string()));
+        };
+        let mut id = None;
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        let mut metadata = Metadata::new();
+        for (key, value) in def {
+            match yaml_key(&key)?.as_str() {
+                "id" => id = value.as_str().map(str::to_string),
+                "inputs" => {
+                    if let YamlValue::Mapping(map) = value {
+                        for (port, input) in map {
+                            inputs.push((yaml_key(&port)?, input));
+                        }
+                    }
+                }
+                "outputs" => {
+                    if let YamlValue::Sequence(list) = value {
+                        outputs = list.iter()
+                            .filter_map(|v| v.as_str())
+                            .map(Port::new)
+                            .collect();
+                    }
+                }
+                other => {
+                    metadata.insert(other.to_string(), to_json(value)?);
+                }
+            }
+        }
+        let id = id.ok_or_else(|| DoraError::Invalid("node without an `id`".to_string()))?;
+        if graph.contains_node(&id) {
+            return Err(DoraError::Invalid(format!("duplicate node id `{}`", id)));
+        }
+
+        // Dora inputs have exactly one source
+        let input_ports = inputs.iter().map(|(port, _)| Port::new(port).with_max_connections(1)).collect();
+        let mut node = FlowNode::new_dataflow(&id, 0.0, 0.0, &id, NodeCategory::default(), input_ports, outputs);
+        node.metadata = metadata;
+        graph.add_node(node);
+        pending_inputs.push((id, inputs));
+    }
+
+    // Second pass: edges for inputs fed by another node, port metadata for the rest
+    for (to_node, inputs) in pending_inputs {
+        for (to_port, input) in inputs {
+            let (source, mut settings) = split_input(input)?;
+            let edge_source = source.as_deref()
+                .and_then(|s| s.split_once('/'))
+                .filter(|(from_node, _)| graph.contains_node(from_node));
+            match edge_source {
+                Some((from_node, from_port)) => {
+                    let mut edge = EdgeConnection::new_with_ports(from_node, from_port, &to_node, &to_port);
+                    edge.metadata = settings;
+                    graph.connect(edge);
+                }
+                None => {
+                    if let Some(source) = source {
+                        settings.insert(EXTERNAL_SOURCE.to_string(), JsonValue::String(source));
+                    }
+                    graph.update_node(&to_node, |n| {
+                        if let Some(port) = n.input_ports.iter_mut().find(|p| p.id == to_port) {
+                            port.metadata = settings;
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(DoraDataflow { graph, extra })
+}
+
+/// Write a graph back out as a dora dataflow.
+/// Input ports with neither an incoming edge nor an external source are
+/// omitted, since dora requires every input to have a source.
+pub fn export_dataflow(graph: &FlowGraph, extra: &Metadata) -> Result<String, DoraError> {
+    // Groups are a canvas-only concept: export the expanded members, not the frames
+    let mut expanded = graph.clone();
+    expanded.expand_all();
+    let graph = &expanded;
+
+    let mut root = Mapping::new();
+    let mut nodes = Vec::with_capacity(graph.node_count());
+    for node in graph.nodes().iter().filter(|n| !n.is_group()) {
+        nodes.push(YamlValue::Mapping(export_node(graph, node)?));
+    }
+    root.insert(YamlValue::from("nodes"), YamlValue::Sequence(nodes));
+    for (key, value) in extra {
+        root.insert(YamlValue::from(key.as_str()), to_yaml(value)?);
+    }
+    Ok(serde_yaml::to_string(&YamlValue::Mapping(root))?)
+}
+
+fn export_node(graph: &FlowGraph, node: &FlowNode) -> Result<Mapping, DoraError> {
+    let mut out = Mapping::new();
+    out.insert(YamlValue::from("id"), YamlValue::from(node.id.as_str()));
+
+    let mut keys: Vec<&String> = node.metadata.keys().collect();
+    keys.sort_by_key(|k| NODE_FIELD_ORDER.iter().position(|f| f == k).unwrap_or(NODE_FIELD_ORDER.len()));
+    let (known, unknown): (Vec<&String>, Vec<&String>) = keys.into_iter()
+        .partition(|k| NODE_FIELD_ORDER.contains(&k.as_str()));
+
+    for key in &known {
+        out.insert(YamlValue::from(key.as_str()), to_yaml(&node.metadata[*key])?);
+    }
+
+    let mut inputs = Mapping::new();
+    for port in &node.input_ports {
+        let edge = graph.edges().iter()
+            .find(|e| e.to_node == node.id && e.to_port == port.id && graph.contains_node(&e.from_node));
+        let input = match edge {
+            Some(edge) => join_input(format!("{}/{}", edge.from_node, edge.from_port), &edge.metadata)?,
+            None => match port.metadata.get(EXTERNAL_SOURCE).and_then(JsonValue::as_str) {
+                Some(source) => {
+                    let mut settings = port.metadata.clone();
+                    settings.remove(EXTERNAL_SOURCE);
+                    join_input(source.to_string(), &settings)?
+                }
+                None => continue,
+            },
+        };
+        inputs.insert(YamlValue::from(port.id.as_str()), input);
+    }
+    if !inputs.is_empty() {
+        out.insert(YamlValue::from("inputs"), YamlValue::Mapping(inputs));
+    }
+
+    if !node.output_ports.is_empty() {
+        let outputs = node.output_ports.iter()
+            .map(|p| YamlValue::from(p.id.as_str()))
+            .collect();
+        out.insert(YamlValue::from("outputs"), YamlValue::Sequence(outputs));
+    }
+
+    for key in unknown {
+        out.insert(YamlValue::from(key.as_str()), to_yaml(&node.metadata[key])?);
+    }
+    Ok(out)
+}
+
+/// Split an input definition into its source and remaining settings.
+/// Accepts both `port: node/output` and `port: { source: node/output, queue_size: 10 }`.
+fn split_input(input: YamlValue) -> Result<(Option<String>, Metadata), DoraError> {
+    match input {
+        YamlValue::String(source) => Ok((Some(source), Metadata::new())),
+        YamlValue::Mapping(map) => {
+            let mut source = None;
+            let mut settings = Metadata::new();
+            for (key, value) in map {
+                let key = yaml_key(&key)?;
+                if key == "source" {
+                    source = value.as_str().map(str::to_string);
+                } else {
+                    settings.insert(key, to_json(value)?);
+                }
+            }
+            Ok((source, settings))
+        }
+        other => {
+            // Unknown shape: keep it verbatim on the port
+            let mut settings = Metadata::new();
+            settings.insert("input".to_string(), to_json(other)?);
+            Ok((None, settings))
+        }
+    }
+}
+
+/// Inverse of [`split_input`]: the short string form when there are no settings
+fn join_input(source: String, settings: &Metadata) -> Result<YamlValue, DoraError> {
+    if settings.is_empty() {
+        return Ok(YamlValue::String(source));
+    }
+    let mut map = Mapping::new();
+    map.insert(YamlValue::from("source"), YamlValue::String(source));
+    for (key, value) in settings {
+        map.insert(YamlValue::from(key.as_str()), to_yaml(value)?);
+    }
+    Ok(YamlValue::Mapping(map))
+}
+
+fn yaml_key(key: &YamlValue) -> Result<String, DoraError> {
+    match key {
+        YamlValue::String(s) => Ok(s.clone()),
+        YamlValue::Number(n) => Ok(n.to_string()),
+        YamlValue::Bool(b) => Ok(b.to_string()),
+        _ => Err(DoraError::Invalid(format!("unsupported mapping key {:?}", key))),
+    }
+}
+
+fn to_json(value: YamlValue) -> Result<JsonValue, DoraError> {
+    serde_json::to_value(value).map_err(|e| DoraError::Invalid(e.to_string()))
+}
+
+fn to_yaml(value: &JsonValue) -> Result<YamlValue, DoraError> {
+    Ok(serde_yaml::to_value(value)?)
+}
+
+#[cfg(all(test, feature = "dora"))]
+mod tests {
+    use super::*;
+
+    const DATAFLOW: &str = r#"
+nodes:
+  - id: camera
+    build: pip install opencv-video-capture
+    path: opencv-video-capture
+    inputs:
+      tick: dora/timer/millis/20
+    outputs:
+      - image
+    env:
+      CAPTURE_PATH: 0
+      IMAGE_WIDTH: 640
+  - id: plot
+    path: dora-rerun
+    inputs:
+      image:
+        source: camera/image
+        queue_size: 1
+communication:
+  _unstable_local:
+    UdpSocket
+"#;
+
+    #[test]
+    fn export_then_import_round_trips() {
+        let imported = import_dataflow(DATAFLOW).unwrap();
+        let graph = &imported.graph;
+        assert_eq!(graph.node_count(), 2);
+        assert_eq!(graph.edges(), &[{
+            let mut edge = EdgeConnection::new_with_ports("camera", "image", "plot", "image");
+            edge.metadata.insert("queue_size".to_string(), JsonValue::from(1));
+            edge
+        }]);
+        let camera = graph.node("camera").unwrap();
+        assert!(camera.metadata.contains_key("build"));
+        assert!(camera.metadata.contains_key("env"));
+        assert_eq!(camera.input_ports[0].metadata.get(EXTERNAL_SOURCE), Some(&JsonValue::from("dora/timer/millis/20")));
+        assert!(imported.extra.contains_key("communication"));
+
+        let yaml = imported.to_yaml().unwrap();
+        let reimported = DoraDataflow::from_yaml(&yaml).unwrap();
+        assert_eq!(reimported.graph.nodes(), imported.graph.nodes());
+        assert_eq!(reimported.graph.edges(), imported.graph.edges());
+        assert_eq!(reimported.extra, imported.extra);
+        assert_eq!(reimported.to_yaml().unwrap(), yaml);
+    }
+}
@@ -0,0 +1,285 @@
+//! Edge path geometry
+//!
+//! Turns an edge's endpoints and [`EdgePathType`] into a polyline. Hit testing,
+//! arrow markers and label placement all work from this polyline, so they agree
+//! with each other for every path type. Drawing uses the same path as a few
+//! [`CubicSegment`]s instead, which the edge shader renders one quad each.
+
+use makepad_widgets::*;
+use crate::constants::edge;
+use crate::graph::EdgePathType;
+
+/// Cubic bezier piece of a path: start, two control points, end
+pub type CubicSegment = [DVec2; 4];
+
+// Bezier curve helper
+pub struct BezierCurve;
+
+impl BezierCurve {
+    /// Get point at parameter t on cubic bezier curve
+    pub fn point_at(t: f64, p0: DVec2, c0: DVec2, c1: DVec2, p1: DVec2) -> DVec2 {
+        let t2 = t * t;
+        let t3 = t2 * t;
+        let mt = 1.0 - t;
+        let mt2 = mt * mt;
+        let mt3 = mt2 * mt;
+        DVec2 {
+            x: mt3 * p0.x + 3.0 * mt2 * t * c0.x + 3.0 * mt * t2 * c1.x + t3 * p1.x,
+            y: mt3 * p0.y + 3.0 * mt2 * t * c0.y + 3.0 * mt * t2 * c1.y + t3 * p1.y,
+        }
+    }
+
+    /// Control points that leave `from` and enter `to` horizontally
+    pub fn horizontal_controls(from: DVec2, to: DVec2) -> [DVec2; 2] {
+        let dx = (to.x - from.x).abs() * 0.5;
+        [DVec2 { x: from.x + dx, y: from.y }, DVec2 { x: to.x - dx, y: to.y }]
+    }
+
+    /// Generate points along bezier curve with horizontal tangent control points
+    pub fn points_with_horizontal_tangents(from: DVec2, to: DVec2, segments: usize) -> Vec<DVec2> {
+        let [c0, c1] = Self::horizontal_controls(from, to);
+
+        let mut points = Vec::with_capacity(segments + 1);
+        for i in 0..=segments {
+            let t = i as f64 / segments as f64;
+            points.push(Self::point_at(t, from, c0, c1, to));
+        }
+        points
+    }
+}
+
+impl EdgePathType {
+    /// Polyline from an output port at `from` to an input port at `to`
+    pub fn points(&self, from: DVec2, to: DVec2) -> Vec<DVec2> {
+        match self {
+            EdgePathType::Bezier => BezierCurve::points_with_horizontal_tangents(from, to, edge::BEZIER_SEGMENTS),
+            EdgePathType::Straight => vec![from, to],
+            EdgePathType::Step => step_points(from, to),
+            EdgePathType::SmoothStep => round_corners(&step_points(from, to), edge::SMOOTH_STEP_RADIUS),
+        }
+    }
+
+    /// Box containing the whole path from `from` to `to`, without building the polyline
+    pub fn bounds(&self, from: DVec2, to: DVec2) -> Rect {
+        match self {
+            // A cubic bezier stays inside the hull of its control points
+            EdgePathType::Bezier => {
+                let [c0, c1] = BezierCurve::horizontal_controls(from, to);
+                bounds(&[from, to, c0, c1])
+            }
+            EdgePathType::Straight => bounds(&[from, to]),
+            // Rounding corners only cuts them, so the step path's box holds
+            EdgePathType::Step | EdgePathType::SmoothStep => bounds(&step_points(from, to)),
+        }
+        .unwrap_or_default()
+    }
+
+    /// The same path as [`points`](Self::points), as cubic pieces. Straight runs
+    /// keep their control points on the line and smooth-step corners are their
+    /// quadratic arcs raised to cubics, so no shape is approximated.
+    pub fn cubics(&self, from: DVec2, to: DVec2) -> Vec<CubicSegment> {
+        match self {
+            EdgePathType::Bezier => {
+                let [c0, c1] = BezierCurve::horizontal_controls(from, to);
+                vec![[from, c0, c1, to]]
+            }
+            EdgePathType::Straight => vec![line_cubic(from, to)],
+            EdgePathType::Step => polyline_cubics(&step_points(from, to)),
+            EdgePathType::SmoothStep => rounded_cubics(&step_points(from, to), edge::SMOOTH_STEP_RADIUS),
+        }
+    }
+}
+
+/// Straight cubic pieces along a polyline, e.g. a routed edge
+pub fn polyline_cubics(points: &[DVec2]) -> Vec<CubicSegment> {
+    points.windows(2).map(|w| line_cubic(w[0], w[1])).collect()
+}
+
+/// Whether the control points of a piece lie on the segment between its ends,
+/// so the piece traces just that segment
+pub fn is_straight(cubic: &CubicSegment) -> bool {
+    let [a, c0, c1, b] = *cubic;
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let len_sq = dx * dx + dy * dy;
+    if len_sq < 1e-12 {
+        return distance(a, c0) < 1e-6 && distance(a, c1) < 1e-6;
+    }
+    let on_segment = |p: DVec2| {
+        let off_line = ((p.x - a.x) * dy - (p.y - a.y) * dx).abs() / len_sq.sqrt();
+        let t = ((p.x - a.x) * dx + (p.y - a.y) * dy) / len_sq;
+        off_line < 1e-6 && (-1e-9..=1.0 + 1e-9).contains(&t)
+    };
+    on_segment(c0) && on_segment(c1)
+}
+
+/// Length of a piece, measured the way the edge shader measures it: straight
+/// pieces exactly, curves as `edge::CURVE_SEGMENTS` chords
+pub fn cubic_length(cubic: &CubicSegment) -> f64 {
+    let [a, c0, c1, b] = *cubic;
+    if is_straight(cubic) {
+        return distance(a, b);
+    }
+    let points: Vec<DVec2> = (0..=edge::CURVE_SEGMENTS)
+        .map(|i| BezierCurve::point_at(i as f64 / edge::CURVE_SEGMENTS as f64, a, c0, c1, b))
+        .collect();
+    path_length(&points)
+}
+
+fn line_cubic(a: DVec2, b: DVec2) -> CubicSegment {
+    [a, lerp(a, b, 1.0 / 3.0), lerp(a, b, 2.0 / 3.0), b]
+}
+
+/// Straight runs and corner arcs of [`round_corners`] as cubic pieces
+fn rounded_cubics(points: &[DVec2], radius: f64) -> Vec<CubicSegment> {
+    let mut out = Vec::new();
+    let Some(&first) = points.first() else { return out };
+    // Arcs of adjacent corners may meet, leaving no straight run between them
+    let line_to = |out: &mut Vec<CubicSegment>, from: DVec2, to: DVec2| {
+        if distance(from, to) > 0.001 {
+            out.push(line_cubic(from, to));
+        }
+    };
+    let mut pen = first;
+    for w in points.windows(3) {
+        let (prev, corner, next) = (w[0], w[1], w[2]);
+        let Some((start, end)) = corner_cut(prev, corner, next, radius) else {
+            line_to(&mut out, pen, corner);
+            pen = corner;
+            continue;
+        };
+        line_to(&mut out, pen, start);
+        // Quadratic through `corner` as a cubic
+        out.push([start, lerp(start, corner, 2.0 / 3.0), lerp(end, corner, 2.0 / 3.0), end]);
+        pen = end;
+    }
+    if points.len() > 1 {
+        line_to(&mut out, pen, points[points.len() - 1]);
+    }
+    out
+}
+
+/// Orthogonal route that leaves and enters ports horizontally.
+/// When the target is behind the source the route doubles back through the
+/// vertical midpoint instead of cutting through the nodes.
+fn step_points(from: DVec2, to: DVec2) -> Vec<DVec2> {
+    let offset = edge::STEP_OFFSET;
+    if to.x - from.x >= offset * 2.0 {
+        let mid_x = (from.x + to.x) / 2.0;
+        vec![from, DVec2 { x: mid_x, y: from.y }, DVec2 { x: mid_x, y: to.y }, to]
+    } else {
+        let mid_y = (from.y + to.y) / 2.0;
+        let out_x = from.x + offset;
+        let in_x = to.x - offset;
+        vec![
+            from,
+            DVec2 { x: out_x, y: from.y },
+            DVec2 { x: out_x, y: mid_y },
+            DVec2 { x: in_x, y: mid_y },
+            DVec2 { x: in_x, y: to.y },
+            to,
+        ]
+    }
+}
+
+/// Replace every interior corner with a quadratic arc of at most `radius`
+fn round_corners(points: &[DVec2], radius: f64) -> Vec<DVec2> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let mut out = vec![points[0]];
+    for w in points.windows(3) {
+        let (prev, corner, next) = (w[0], w[1], w[2]);
+        let Some((start, end)) = corner_cut(prev, corner, next, radius) else {
+            out.push(corner);
+            continue;
+        };
+        for i in 0..=edge::CORNER_SEGMENTS {
+            let t = i as f64 / edge::CORNER_SEGMENTS as f64;
+            let a = lerp(start, corner, t);
+            let b = lerp(corner, end, t);
+            out.push(lerp(a, b, t));
+        }
+    }
+    out.push(points[points.len() - 1]);
+    out
+}
+
+/// Where the arc rounding `corner` starts and ends, or `None` if a segment is
+/// too short to round
+fn corner_cut(prev: DVec2, corner: DVec2, next: DVec2, radius: f64) -> Option<(DVec2, DVec2)> {
+    let in_len = distance(prev, corner);
+    let out_len = distance(corner, next);
+    let r = radius.min(in_len / 2.0).min(out_len / 2.0);
+    (r >= 0.001).then(|| (lerp(corner, prev, r / in_len), lerp(corner, next, r / out_len)))
+}
+
+/// Total length of a polyline
+pub fn path_length(points: &[DVec2]) -> f64 {
+    points.windows(2).map(|w| distance(w[0], w[1])).sum()
+}
+
+/// Point at `fraction` (0.0..=1.0) of the way along a polyline
+pub fn point_along(points: &[DVec2], fraction: f64) -> DVec2 {
+    let Some(&first) = points.first() else {
+        return DVec2::default();
+    };
+    let mut remaining = path_length(points) * fraction.clamp(0.0, 1.0);
+    for w in points.windows(2) {
+        let seg_len = distance(w[0], w[1]);
+        if remaining <= seg_len && seg_len > 0.001 {
+            return lerp(w[0], w[1], remaining / seg_len);
+        }
+        remaining -= seg_len;
+    }
+    points.last().copied().unwrap_or(first)
+}
+
+/// Smallest box containing every point of a polyline
+pub fn bounds(points: &[DVec2]) -> Option<Rect> {
+    let (&first, rest) = points.split_first()?;
+    let (mut min, mut max) = (first, first);
+    for p in rest {
+        min = DVec2 { x: min.x.min(p.x), y: min.y.min(p.y) };
+        max = DVec2 { x: max.x.max(p.x), y: max.y.max(p.y) };
+    }
+    Some(Rect { pos: min, size: DVec2 { x: max.x - min.x, y: max.y - min.y } })
+}
+
+/// Unit direction of the last non-degenerate segment, i.e. the direction the
+/// path arrives at its end point
+pub fn end_direction(points: &[DVec2]) -> Option<DVec2> {
+    points.windows(2).rev().find_map(|w| {
+        let len = distance(w[0], w[1]);
+        (len > 0.001).then(|| DVec2 { x: (w[1].x - w[0].x) / len, y: (w[1].y - w[0].y) / len })
+    })
+}
+
+/// Shortest distance from `point` to a polyline
+pub fn distance_to_path(points: &[DVec2], point: DVec2) -> f64 {
+    match points {
+        [] => f64::INFINITY,
+        [only] => distance(*only, point),
+        _ => points.windows(2)
+            .map(|w| distance_to_segment(point, w[0], w[1]))
+            .fold(f64::INFINITY, f64::min),
+    }
+}
+
+fn distance_to_segment(p: DVec2, a: DVec2, b: DVec2) -> f64 {
+    let abx = b.x - a.x;
+    let aby = b.y - a.y;
+    let len_sq = abx * abx + aby * aby;
+    if len_sq < 1e-9 {
+        return distance(p, a);
+    }
+    let t = (((p.x - a.x) * abx + (p.y - a.y) * aby) / len_sq).clamp(0.0, 1.0);
+    distance(p, DVec2 { x: a.x + abx * t, y: a.y + aby * t })
+}
+
+fn distance(a: DVec2, b: DVec2) -> f64 {
+    (b.x - a.x).hypot(b.y - a.y)
+}
+
+fn lerp(a: DVec2, b: DVec2, t: f64) -> DVec2 {
+    DVec2 { x: a.x + (b.x - a.x) * t, y: a.y + (b.y - a.y) * t }
+}
@@ -1,12 +1,18 @@
 use makepad_widgets::*;
 use std::collections::HashSet;
-use crate::constants::{node, port, edge, canvas};
-
-/// Canvas constants for zoom bounds and other magic numbers
-mod canvas {
-    pub const MIN_ZOOM: f64 = 0.25;
-    pub const MAX_ZOOM: f64 = 4.0;
-}
+use crate::constants::{node, port, edge, canvas, simulation, spatial};
+use crate::graph::*;
+use crate::edge_path::{self, CubicSegment};
+use crate::router::EdgeRouter;
+use crate::spatial::SpatialIndex;
+use crate::layout::{ForceLayout, ForceSimulation, LayeredLayout};
+use crate::category::{CategoryError, CategoryRegistry};
+use crate::template::{NodeTemplate, NodeTemplateRegistry};
+use crate::theme::FlowTheme;
+use crate::connection::{ConnectionRejection, ConnectionValidator, DefaultValidator};
+use crate::document::{DocumentError, FlowDocument, FlowViewport};
+use crate::history::{FlowCommand, FlowHistory};
+use crate::palette::FlowPaletteAction;
 
 live_design! {
     use link::theme::*;
@@ -88,7 +94,126 @@ live_design! {
         }
     }
 
-    // Node templates (light theme)
+    // Circle or convex polygon, optionally with a header band and a border
+    DrawFlowShape = {{DrawFlowShape}} {
+        fn vertex_at(self, p: vec2) -> vec2 {
+            return p * self.rect_size;
+        }
+
+        fn pixel(self) -> vec4 {
+            let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+            if self.kind < 0.5 {
+                let r = min(self.rect_size.x, self.rect_size.y) * 0.5 - self.border_width * 0.5;
+                sdf.circle(self.rect_size.x * 0.5, self.rect_size.y * 0.5, r);
+            } else {
+                let p0 = self.vertex_at(self.points0.xy);
+                let p1 = self.vertex_at(self.points0.zw);
+                let p2 = self.vertex_at(self.points1.xy);
+                sdf.move_to(p0.x, p0.y);
+                sdf.line_to(p1.x, p1.y);
+                sdf.line_to(p2.x, p2.y);
+                if self.point_count > 3.5 {
+                    let p3 = self.vertex_at(self.points1.zw);
+                    sdf.line_to(p3.x, p3.y);
+                }
+                if self.point_count > 4.5 {
+                    let p4 = self.vertex_at(self.points2.xy);
+                    sdf.line_to(p4.x, p4.y);
+                }
+                if self.point_count > 5.5 {
+                    let p5 = self.vertex_at(self.points2.zw);
+                    sdf.line_to(p5.x, p5.y);
+                }
+                if self.point_count > 6.5 {
+                    let p6 = self.vertex_at(self.points3.xy);
+                    sdf.line_to(p6.x, p6.y);
+                }
+                if self.point_count > 7.5 {
+                    let p7 = self.vertex_at(self.points3.zw);
+                    sdf.line_to(p7.x, p7.y);
+                }
+                sdf.close_path();
+            }
+            // Pixels above `header_bottom` take the header color
+            let y = self.rect_pos.y + self.pos.y * self.rect_size.y;
+            sdf.fill_keep(mix(self.color, self.header_color, step(y, self.header_bottom)));
+            if self.border_width > 0.0 {
+                sdf.stroke(self.border_color, self.border_width);
+            }
+            return sdf.result;
+        }
+    }
+
+    // One cubic piece of an edge. The distance to the curve, and the path length
+    // at the closest point for dashes, dots and flow particles, are found per
+    // pixel from `segments` chords, so each piece is a single quad.
+    DrawFlowEdge = {{DrawFlowEdge}} {
+        fn curve_at(self, t: float) -> vec2 {
+            let mt = 1.0 - t;
+            return mt * mt * mt * self.p0 + 3.0 * mt * mt * t * self.c0 + 3.0 * mt * t * t * self.c1 + t * t * t * self.p1;
+        }
+
+        fn pixel(self) -> vec4 {
+            let p = self.pos * self.rect_size;
+            let dist = 1000000.0;
+            let along = 0.0;
+            let travelled = 0.0;
+            let prev = self.p0;
+            for i in 0..32 {
+                if float(i) >= self.segments {
+                    break;
+                }
+                let next = self.curve_at((float(i) + 1.0) / self.segments);
+                let chord = next - prev;
+                let len = length(chord);
+                let h = clamp(dot(p - prev, chord) / max(dot(chord, chord), 0.0001), 0.0, 1.0);
+                let d = length(p - prev - chord * h);
+                if d < dist {
+                    dist = d;
+                    along = travelled + len * h;
+                }
+                travelled = travelled + len;
+                prev = next;
+            }
+            along = along + self.length_offset;
+
+            let color = self.color;
+            let coverage = clamp(self.thickness * 0.5 + 0.5 - dist, 0.0, 1.0);
+            let phase = max(self.phase, 0.0);
+            if self.style > 1.5 {
+                // Dotted: round dots, drifting along the path when animated
+                let from_dot = abs(mod(along - phase * self.dot_spacing + self.dot_spacing * 0.5, self.dot_spacing) - self.dot_spacing * 0.5);
+                coverage = clamp(self.dot_radius + 0.5 - length(vec2(from_dot, dist)), 0.0, 1.0);
+            } else if self.style > 0.5 {
+                // Dashed, marching along the path when animated
+                let cycle = self.dash_length + self.dash_gap;
+                let in_cycle = mod(along + phase * cycle, cycle);
+                coverage = coverage * clamp(self.dash_length + 0.5 - in_cycle, 0.0, 1.0);
+            } else if self.phase >= 0.0 {
+                // Animated solid: particles in `flow_color` moving over the line
+                let from_particle = abs(mod(along - phase * self.particle_spacing + self.particle_spacing * 0.5, self.particle_spacing) - self.particle_spacing * 0.5);
+                let particle = clamp(self.particle_radius + 0.5 - length(vec2(from_particle, dist)), 0.0, 1.0);
+                color = mix(self.color, self.flow_color, particle);
+                coverage = max(coverage, particle);
+            }
+            return Pal::premul(vec4(color.rgb, color.a * coverage));
+        }
+    }
+
+    // Background grid: dots at, or lines through, every multiple of `spacing`
+    DrawGrid = {{DrawGrid}} {
+        fn pixel(self) -> vec4 {
+            let p = self.pos * self.rect_size - self.offset;
+            let cell = mod(p, vec2(self.spacing, self.spacing));
+            let d = min(cell, vec2(self.spacing, self.spacing) - cell);
+            let dot = clamp(self.thickness + 0.5 - length(d), 0.0, 1.0);
+            let line = clamp(self.thickness * 0.5 + 0.5 - min(d.x, d.y), 0.0, 1.0);
+            let coverage = mix(dot, line, step(1.5, self.style));
+            return Pal::premul(vec4(self.color.rgb, self.color.a * coverage));
+        }
+    }
+
+    // Node templates
     NodeCamera = <RoundedView> {
         width: 180, height: Fit, flow: Down
         draw_bg: { color: #ffffff, border_radius: 8.0, border_size: 1.0, border_color: #e0e0e0 }
@@ -135,7 +260,7 @@ live_design! {
         }
     }
 
-    // Flow canvas (light theme)
+    // Flow canvas
     pub FlowCanvas = {{FlowCanvas}} {
         width: Fill, height: Fill
         flow: Overlay
@@ -155,7 +280,16 @@ live_design! {
         selection_color: #4A90D9
         edge_color: #8CBFFF
         edge_selected_color: #FFD966
+        edge_valid_color: #4CAF50
+        edge_invalid_color: #E53935
         animate_edges: true
+        route_edges: false
+        grid_style: Dots
+        grid_size: 20.0
+        grid_color: #d0d0d0
+        snap_to_grid: false
+        detail_zoom: 0.6
+        block_zoom: 0.35
     }
 }
 
@@ -188,299 +322,182 @@ pub struct DrawRoundedBottomRect {
     #[live] pub radius: f32,
 }
 
-// Node shape types
-#[derive(Clone, Copy, PartialEq, Default, Debug)]
-pub enum NodeShape {
-    #[default]
-    RoundedRect,
-    DoubleRoundedRect,
-    Rectangle,
-    Round,
-    Diamond,
-}
-
-// Node category for coloring
-#[derive(Clone, Copy, PartialEq, Default, Debug)]
-pub enum NodeCategory {
-    #[default]
-    Default,
-    MaaS,       // LLM clients (blue)
-    TTS,        // Text-to-speech (green)
-    Bridge,     // Message routing (orange)
-    Controller, // Orchestration (purple)
-    MoFA,       // Dynamic UI widgets (cyan)
-    Segmenter,  // Text processing (yellow)
+// Circle or convex polygon shader (round and diamond nodes, edge markers)
+#[derive(Live, LiveHook, LiveRegister)]
+#[repr(C)]
+pub struct DrawFlowShape {
+    #[deref] pub draw_super: DrawQuad,
+    #[live] pub color: Vec4,
+    #[live] pub header_color: Vec4,
+    #[live] pub border_color: Vec4,
+    #[live] pub border_width: f32,
+    #[live] pub header_bottom: f32, // Screen y above which the fill is header_color
+    #[live] pub kind: f32,          // 0.0 = circle, 1.0 = polygon
+    #[live] pub point_count: f32,
+    #[live] pub points0: Vec4,      // Polygon points as fractions of the quad, two per vec4
+    #[live] pub points1: Vec4,
+    #[live] pub points2: Vec4,
+    #[live] pub points3: Vec4,
 }
 
-impl NodeCategory {
-    pub fn color(&self) -> Vec4 {
-        match self {
-            NodeCategory::Default => vec4(0.24, 0.24, 0.36, 1.0),
-            NodeCategory::MaaS => vec4(0.2, 0.4, 0.7, 1.0),
-            NodeCategory::TTS => vec4(0.2, 0.6, 0.3, 1.0),
-            NodeCategory::Bridge => vec4(0.7, 0.5, 0.2, 1.0),
-            NodeCategory::Controller => vec4(0.5, 0.3, 0.7, 1.0),
-            NodeCategory::MoFA => vec4(0.2, 0.6, 0.7, 1.0),
-            NodeCategory::Segmenter => vec4(0.7, 0.7, 0.2, 1.0),
-        }
-    }
-
-    pub fn header_color(&self) -> Vec4 {
-        match self {
-            NodeCategory::Default => vec4(0.30, 0.30, 0.45, 1.0),
-            NodeCategory::MaaS => vec4(0.25, 0.45, 0.75, 1.0),
-            NodeCategory::TTS => vec4(0.25, 0.65, 0.35, 1.0),
-            NodeCategory::Bridge => vec4(0.75, 0.55, 0.25, 1.0),
-            NodeCategory::Controller => vec4(0.55, 0.35, 0.75, 1.0),
-            NodeCategory::MoFA => vec4(0.25, 0.65, 0.75, 1.0),
-            NodeCategory::Segmenter => vec4(0.75, 0.75, 0.25, 1.0),
+impl DrawFlowShape {
+    /// Most points `draw_polygon` uses
+    pub const MAX_POINTS: usize = 8;
+
+    /// Circle filling the shorter side of `rect`, border inside it
+    pub fn draw_circle(&mut self, cx: &mut Cx2d, rect: Rect) {
+        self.kind = 0.0;
+        self.draw_abs(cx, rect);
+    }
+
+    /// Convex polygon through screen points, in either winding order
+    pub fn draw_polygon(&mut self, cx: &mut Cx2d, points: &[DVec2]) {
+        let points = &points[..points.len().min(Self::MAX_POINTS)];
+        let Some(bounds) = edge_path::bounds(points).filter(|_| points.len() >= 3) else { return };
+        // Room for the border, which is centred on the outline
+        let pad = self.border_width as f64 * 0.5 + 1.0;
+        let rect = Rect {
+            pos: DVec2 { x: bounds.pos.x - pad, y: bounds.pos.y - pad },
+            size: DVec2 { x: bounds.size.x + pad * 2.0, y: bounds.size.y + pad * 2.0 },
+        };
+        let mut fractions: Vec<Vec2> = points.iter()
+            .map(|p| vec2(((p.x - rect.pos.x) / rect.size.x) as f32, ((p.y - rect.pos.y) / rect.size.y) as f32))
+            .collect();
+        // The shader fills clockwise outlines (on screen, y down)
+        let area: f32 = (0..fractions.len())
+            .map(|i| {
+                let (a, b) = (fractions[i], fractions[(i + 1) % fractions.len()]);
+                a.x * b.y - b.x * a.y
+            })
+            .sum();
+        if area < 0.0 {
+            fractions.reverse();
+        }
+        let mut packed = [Vec4::default(); 4];
+        for (i, p) in fractions.iter().enumerate() {
+            let slot = &mut packed[i / 2];
+            if i % 2 == 0 {
+                (slot.x, slot.y) = (p.x, p.y);
+            } else {
+                (slot.z, slot.w) = (p.x, p.y);
+            }
         }
+        [self.points0, self.points1, self.points2, self.points3] = packed;
+        self.point_count = fractions.len() as f32;
+        self.kind = 1.0;
+        self.draw_abs(cx, rect);
     }
 }
 
-// Port definition
-#[derive(Clone, Debug)]
-pub struct Port {
-    pub id: String,
-    pub label: String,
+// Edge shader: one cubic piece of a path per quad
+#[derive(Live, LiveHook, LiveRegister)]
+#[repr(C)]
+pub struct DrawFlowEdge {
+    #[deref] pub draw_super: DrawQuad,
+    #[live] pub color: Vec4,
+    #[live] pub flow_color: Vec4,   // Particles of animated solid edges
+    #[live] pub p0: Vec2,           // Curve start, control points and end, relative to the quad
+    #[live] pub c0: Vec2,
+    #[live] pub c1: Vec2,
+    #[live] pub p1: Vec2,
+    #[live] pub segments: f32,      // Chords the curve is measured with, 1.0 for straight pieces
+    #[live] pub thickness: f32,
+    #[live] pub style: f32,         // 0.0 = solid, 1.0 = dashed, 2.0 = dotted
+    #[live] pub phase: f32,         // Animation phase 0..1, negative when not animated
+    #[live] pub length_offset: f32, // Path length before this piece, so patterns run on across pieces
+    #[live] pub dash_length: f32,
+    #[live] pub dash_gap: f32,
+    #[live] pub dot_spacing: f32,
+    #[live] pub dot_radius: f32,
+    #[live] pub particle_spacing: f32,
+    #[live] pub particle_radius: f32,
 }
 
-impl Port {
-    pub fn new(id: &str) -> Self {
-        Self {
-            id: id.to_string(),
-            label: id.to_string(),
+impl DrawFlowEdge {
+    /// Draw the pieces of one path with the current color, style and phase
+    pub fn draw_cubics(&mut self, cx: &mut Cx2d, cubics: &[CubicSegment]) {
+        // Wide enough for round caps, dots and particles
+        let pad = self.thickness.max(self.dot_radius).max(self.particle_radius) as f64 + 1.0;
+        let mut length_offset = 0.0;
+        for cubic in cubics {
+            let Some(bounds) = edge_path::bounds(cubic) else { continue };
+            let origin = DVec2 { x: bounds.pos.x - pad, y: bounds.pos.y - pad };
+            let local = |p: DVec2| vec2((p.x - origin.x) as f32, (p.y - origin.y) as f32);
+            self.p0 = local(cubic[0]);
+            self.c0 = local(cubic[1]);
+            self.c1 = local(cubic[2]);
+            self.p1 = local(cubic[3]);
+            self.segments = if edge_path::is_straight(cubic) { 1.0 } else { edge::CURVE_SEGMENTS as f32 };
+            self.length_offset = length_offset as f32;
+            self.draw_abs(cx, Rect {
+                pos: origin,
+                size: DVec2 { x: bounds.size.x + pad * 2.0, y: bounds.size.y + pad * 2.0 },
+            });
+            length_offset += edge_path::cubic_length(cubic);
         }
     }
 }
 
-// Node types
-#[derive(Clone, Copy, PartialEq, Debug)]
-pub enum NodeType {
-    Camera,
-    Detector,
-    Tracker,
-    Visualizer,
-    Custom,
-}
-
-impl NodeType {
-    pub fn title(&self) -> &'static str {
-        match self {
-            NodeType::Camera => "Camera",
-            NodeType::Detector => "Object Detector",
-            NodeType::Tracker => "Tracker",
-            NodeType::Visualizer => "Visualizer",
-            NodeType::Custom => "Processor",
-        }
-    }
-
-    pub fn has_input(&self) -> bool {
-        !matches!(self, NodeType::Camera)
-    }
-
-    pub fn has_output(&self) -> bool {
-        !matches!(self, NodeType::Visualizer)
-    }
+// Background grid shader
+#[derive(Live, LiveHook, LiveRegister)]
+#[repr(C)]
+pub struct DrawGrid {
+    #[deref] pub draw_super: DrawQuad,
+    #[live] pub color: Vec4,
+    #[live] pub offset: Vec2,   // Screen position of a grid point, relative to the quad
+    #[live] pub spacing: f32,   // Screen distance between grid points
+    #[live] pub thickness: f32, // Dot radius or line width
+    #[live] pub style: f32,     // 1.0 = dots, 2.0 = lines
 }
 
-// Node data
-#[derive(Clone, Debug)]
-pub struct FlowNode {
-    pub id: String,
-    pub x: f64,
-    pub y: f64,
-    pub width: f64,
-    pub height: f64,
-    pub node_type: NodeType,
-    pub title: String,
-    pub shape: NodeShape,
-    pub border_width: f32,
-    pub category: NodeCategory,
-    pub input_ports: Vec<Port>,
-    pub output_ports: Vec<Port>,
+/// Background pattern drawn behind the graph
+#[derive(Copy, Clone, Debug, PartialEq, Live, LiveHook)]
+#[live_ignore]
+pub enum GridStyle {
+    None,
+    #[pick] Dots,
+    Lines,
 }
 
-impl FlowNode {
-    pub fn new(x: f64, y: f64, node_type: NodeType) -> Self {
-        let height = if node_type == NodeType::Camera { node::CAMERA_HEIGHT } else { node::DEFAULT_HEIGHT };
-        let (input_ports, output_ports) = match node_type {
-            NodeType::Camera => (vec![], vec![Port::new("image")]),
-            NodeType::Visualizer => (vec![Port::new("input")], vec![]),
-            _ => (vec![Port::new("input")], vec![Port::new("output")]),
-        };
-        Self {
-            id: format!("node_{}", x as i32 + y as i32),
-            x, y,
-            width: node::WIDTH,
-            height,
-            node_type,
-            title: node_type.title().to_string(),
-            shape: NodeShape::RoundedRect,
-            border_width: node::BORDER_WIDTH,
-            category: NodeCategory::Default,
-            input_ports,
-            output_ports,
-        }
-    }
-
-    pub fn new_dataflow(id: &str, x: f64, y: f64, title: &str, category: NodeCategory, inputs: Vec<Port>, outputs: Vec<Port>) -> Self {
-        let port_count = inputs.len().max(outputs.len());
-        let height = node::HEADER_HEIGHT + (port_count as f64 * node::PORT_HEIGHT) + node::BODY_PADDING;
-
-        Self {
-            id: id.to_string(),
-            x, y,
-            width: node::WIDTH,
-            height: height.max(node::MIN_HEIGHT),
-            node_type: NodeType::Custom,
-            title: title.to_string(),
-            shape: NodeShape::DoubleRoundedRect,
-            border_width: node::BORDER_WIDTH,
-            category,
-            input_ports: inputs,
-            output_ports: outputs,
-        }
-    }
-
-    pub fn contains(&self, pos: DVec2) -> bool {
-        pos.x >= self.x && pos.x <= self.x + self.width &&
-        pos.y >= self.y && pos.y <= self.y + self.height
-    }
-
-    // Get position for a specific input port by index
-    pub fn input_port_pos(&self, index: usize) -> DVec2 {
-        let y = self.y + node::HEADER_HEIGHT + (index as f64 * node::PORT_HEIGHT) + node::PORT_HEIGHT / 2.0;
-        DVec2 { x: self.x, y }
-    }
-
-    // Get position for a specific output port by index
-    pub fn output_port_pos(&self, index: usize) -> DVec2 {
-        let y = self.y + node::HEADER_HEIGHT + (index as f64 * node::PORT_HEIGHT) + node::PORT_HEIGHT / 2.0;
-        DVec2 { x: self.x + self.width, y }
-    }
-
-    // Legacy: first output port position
-    pub fn output_pos(&self) -> DVec2 {
-        if self.output_ports.is_empty() {
-            DVec2 { x: self.x + self.width, y: self.y + node::LEGACY_PORT_Y_OFFSET }
-        } else {
-            self.output_port_pos(0)
-        }
-    }
-
-    // Legacy: first input port position
-    pub fn input_pos(&self) -> DVec2 {
-        if self.input_ports.is_empty() {
-            DVec2 { x: self.x, y: self.y + node::LEGACY_PORT_Y_OFFSET }
-        } else {
-            self.input_port_pos(0)
-        }
-    }
-
-    pub fn output_port_rect(&self) -> Rect {
-        let pos = self.output_pos();
-        Rect { pos: DVec2 { x: pos.x - port::HIT_OFFSET_X, y: pos.y - port::HIT_OFFSET_Y }, size: DVec2 { x: port::HIT_SIZE, y: port::HIT_SIZE } }
-    }
-
-    pub fn input_port_rect(&self) -> Rect {
-        let pos = self.input_pos();
-        Rect { pos: DVec2 { x: pos.x - port::HIT_OFFSET_Y, y: pos.y - port::HIT_OFFSET_Y }, size: DVec2 { x: port::HIT_SIZE, y: port::HIT_SIZE } }
-    }
-
-    // Find port index by id
-    pub fn input_port_index(&self, port_id: &str) -> Option<usize> {
-        self.input_ports.iter().position(|p| p.id == port_id)
-    }
-
-    pub fn output_port_index(&self, port_id: &str) -> Option<usize> {
-        self.output_ports.iter().position(|p| p.id == port_id)
-    }
+/// How much of each node and edge is drawn, picked from the zoom level
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum DetailLevel {
+    /// Plain rectangles in the node's category color
+    Blocks,
+    /// Colored blocks with their titles; no ports, edge labels or markers
+    Titles,
+    /// Everything
+    Full,
 }
 
-// Edge marker type
-#[derive(Clone, Copy, PartialEq, Default, Debug)]
-pub enum EdgeMarker {
-    #[default]
-    None,
-    Arrow,
-    ArrowFilled,
-    Circle,
+/// Corner of a node grabbed to resize it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResizeCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
 }
 
-// Edge connection with per-edge properties
-#[derive(Clone, Debug)]
-pub struct EdgeConnection {
-    pub from_node: usize,
-    pub to_node: usize,
-    pub from_port: String,  // output port id
-    pub to_port: String,    // input port id
-    pub style: f32,         // 0=solid, 1=dashed, 2=dotted
-    pub width: f32,         // line width
-    pub animated: bool,     // animation on/off
-    pub label: String,      // edge label text
-    pub marker_end: EdgeMarker, // marker at end
-}
+impl ResizeCorner {
+    pub const ALL: [ResizeCorner; 4] = [Self::TopLeft, Self::TopRight, Self::BottomLeft, Self::BottomRight];
 
-impl EdgeConnection {
-    pub fn new(from_node: usize, to_node: usize) -> Self {
-        Self {
-            from_node,
-            to_node,
-            from_port: String::new(),
-            to_port: String::new(),
-            style: 0.0,      // solid by default
-            width: edge::WIDTH_F32,
-            animated: true,  // animated by default
-            label: String::new(),
-            marker_end: EdgeMarker::Arrow, // arrow by default
-        }
-    }
-
-    pub fn new_with_ports(from_node: usize, from_port: &str, to_node: usize, to_port: &str) -> Self {
-        Self {
-            from_node,
-            to_node,
-            from_port: from_port.to_string(),
-            to_port: to_port.to_string(),
-            style: 0.0,
-            width: edge::WIDTH_F32,
-            animated: false,  // dataflow edges not animated by default
-            label: String::new(),
-            marker_end: EdgeMarker::Arrow,
-        }
-    }
-}
+    // Whether dragging this corner moves the left / top edge
+    fn moves_left(self) -> bool { matches!(self, Self::TopLeft | Self::BottomLeft) }
+    fn moves_top(self) -> bool { matches!(self, Self::TopLeft | Self::TopRight) }
 
-// Bezier curve helper - computes points along the curve
-pub struct BezierCurve;
-
-impl BezierCurve {
-    /// Get point at parameter t on cubic bezier curve
-    pub fn point_at(t: f64, p0: DVec2, c0: DVec2, c1: DVec2, p1: DVec2) -> DVec2 {
-        let t2 = t * t;
-        let t3 = t2 * t;
-        let mt = 1.0 - t;
-        let mt2 = mt * mt;
-        let mt3 = mt2 * mt;
+    pub fn point(self, rect: Rect) -> DVec2 {
         DVec2 {
-            x: mt3 * p0.x + 3.0 * mt2 * t * c0.x + 3.0 * mt * t2 * c1.x + t3 * p1.x,
-            y: mt3 * p0.y + 3.0 * mt2 * t * c0.y + 3.0 * mt * t2 * c1.y + t3 * p1.y,
+            x: if self.moves_left() { rect.pos.x } else { rect.pos.x + rect.size.x },
+            y: if self.moves_top() { rect.pos.y } else { rect.pos.y + rect.size.y },
         }
     }
 
-    /// Generate points along bezier curve with horizontal tangent control points
-    pub fn points_with_horizontal_tangents(from: DVec2, to: DVec2, segments: usize) -> Vec<DVec2> {
-        let dx = (to.x - from.x).abs() * 0.5;
-        let c0 = DVec2 { x: from.x + dx, y: from.y };
-        let c1 = DVec2 { x: to.x - dx, y: to.y };
-
-        let mut points = Vec::with_capacity(segments + 1);
-        for i in 0..=segments {
-            let t = i as f64 / segments as f64;
-            points.push(Self::point_at(t, from, c0, c1, to));
+    pub fn cursor(self) -> MouseCursor {
+        match self {
+            Self::TopLeft | Self::BottomRight => MouseCursor::NwseResize,
+            Self::TopRight | Self::BottomLeft => MouseCursor::NeswResize,
         }
-        points
     }
 }
 
@@ -488,25 +505,25 @@ impl BezierCurve {
 #[derive(Clone)]
 pub enum DragState {
     None,
-    DraggingNode { index: usize, offset: DVec2 },
-    DraggingNodes { offsets: Vec<(usize, DVec2)> }, // Multi-node drag
+    DraggingNode { node_id: String, offset: DVec2 },
+    DraggingNodes { offsets: Vec<(String, DVec2)> }, // Multi-node drag
     Panning { start: DVec2 },
-    CreatingEdge { from_node: usize, is_output: bool, cursor_pos: DVec2 },
+    CreatingEdge { from_node: String, from_port: String, is_output: bool, cursor_pos: DVec2 },
+    // Press near an edge end that becomes a reconnect once dragged; `start` is in screen space
+    PendingReconnect { index: usize, moving_target: bool, start: DVec2 },
+    // Edge taken out of the graph while one of its ends is dragged; `index` is where it goes back
+    ReconnectingEdge { index: usize, edge: EdgeConnection, moving_target: bool, cursor_pos: DVec2 },
     SelectionBox { start: DVec2, current: DVec2 }, // Drag selection box
-}
-
-// History entry for undo/redo
-#[derive(Clone)]
-pub struct HistoryEntry {
-    pub nodes: Vec<FlowNode>,
-    pub edges: Vec<EdgeConnection>,
+    // Node corner being dragged; `original` is the node rect before the drag
+    ResizingNode { node_id: String, corner: ResizeCorner, original: Rect },
 }
 
 // Flow canvas ref for external access
 #[derive(Clone, Debug, DefaultNone)]
 pub enum FlowCanvasCommand {
     None,
-    AddNode,
+    /// Add a node from the registered template with this id
+    AddNode { template: String },
     Delete,
     FitView,
     Clear,
@@ -519,12 +536,14 @@ pub enum FlowCanvasCommand {
 #[derive(Live, LiveHook, Widget)]
 pub struct FlowCanvas {
     #[deref] view: View,
-    #[live] draw_edge: DrawColor,
+    #[live] draw_edge: DrawFlowEdge,
+    #[live] draw_shape: DrawFlowShape,
     #[live] draw_node_bg: DrawColor,
     #[live] draw_rounded_rect: DrawRoundedRect,
     #[live] draw_rounded_top_rect: DrawRoundedTopRect,
     #[live] draw_rounded_bottom_rect: DrawRoundedBottomRect,
     #[live] draw_text: DrawText,
+    #[live] draw_grid: DrawGrid,
 
     // Configurable visual properties (can be set via DSL)
     #[live(2.0)] pub line_width: f32,
@@ -533,39 +552,79 @@ pub struct FlowCanvas {
     #[live] pub selection_color: Vec4,
     #[live] pub edge_color: Vec4,
     #[live] pub edge_selected_color: Vec4,
+    #[live] pub edge_valid_color: Vec4,   // Edge being dragged over an accepting port
+    #[live] pub edge_invalid_color: Vec4, // Edge being dragged over a rejecting port
     #[live(true)] pub animate_edges: bool,
-
-    #[rust] nodes: Vec<FlowNode>,
-    #[rust] edges: Vec<EdgeConnection>,
+    #[live(false)] pub route_edges: bool, // Route all edges orthogonally around nodes
+    #[live] pub grid_style: GridStyle,
+    #[live(20.0)] pub grid_size: f64, // Grid spacing in canvas units, also the snap step
+    #[live] pub grid_color: Vec4,
+    #[live(false)] pub snap_to_grid: bool, // Quantize node positions while dragging and when adding
+    #[live(0.6)] pub detail_zoom: f64, // Below this zoom nodes are drawn as titled blocks, edges without labels or markers
+    #[live(0.35)] pub block_zoom: f64, // Below this zoom nodes are drawn as plain blocks
+
+    #[rust] graph: FlowGraph,
     #[rust] drag_state: DragState,
-    #[rust] selected_nodes: HashSet<usize>,  // Multi-selection support
+    #[rust] selected_nodes: HashSet<String>, // Multi-selection support (node ids)
     #[rust] selected_edges: HashSet<usize>,  // Multi-selection support
     #[rust] pan_offset: DVec2,
     #[rust] zoom: f64,
     #[rust] initialized: bool,
-    #[rust] next_node_id: usize,
     #[rust] animation_timer: Timer,
     #[rust] animation_phase: f64, // 0.0 to 1.0, cycles continuously
-    #[rust] context_menu_node: Option<usize>, // Which node is the context menu for
+    #[rust] context_menu_node: Option<String>, // Which node is the context menu for
     #[rust] context_menu_edge: Option<usize>, // Which edge is the context menu for
     #[rust] context_menu_pos: DVec2, // Position to show context menu
-    #[rust] undo_stack: Vec<HistoryEntry>,   // Undo history
-    #[rust] redo_stack: Vec<HistoryEntry>,   // Redo history
+    #[rust] history: FlowHistory, // Undo/redo steps
+    #[rust] pending_edit: Option<String>, // Label of the drag or live layout the graph is recording
+    #[rust] validator: Option<Box<dyn ConnectionValidator>>, // None uses DefaultValidator
+    #[rust] categories: CategoryRegistry, // Colors and icons by node category
+    #[rust(NodeTemplateRegistry::builtin())] templates: NodeTemplateRegistry, // Node kinds that can be created
+    #[rust] palette_drop: Option<(String, DVec2)>, // Template dragged from a palette over the canvas, centre in canvas coordinates
+    #[rust] theme: FlowTheme, // Colors not covered by the live properties or categories
+    #[rust] router: EdgeRouter, // Cached routes, used when route_edges is on
+    #[rust] spatial: SpatialIndex, // Node and edge bounds for hit tests and culling
+    #[rust] spatial_routed: bool, // Whether the spatial index holds routed edge bounds
+    #[rust] connection_check: Option<Result<(), ConnectionRejection>>, // Result for the port under the cursor while creating an edge
+    #[rust] simulation: Option<ForceSimulation>, // Live force-directed layout
+    #[rust] simulation_timer: Timer,
+    #[rust] draw_revision: u64, // Bumped on every draw so overviews know when to follow
+    #[rust] pointer_pos: DVec2, // Last pointer position in canvas space; paste target
+    #[rust] drag_origin: Vec<(String, DVec2)>, // Positions of the dragged nodes and their children at drag start
+    #[rust] queued_actions: Vec<FlowCanvasAction>, // Raised outside a hit handler; sent from the next handle_event
 }
 
 impl Default for DragState {
     fn default() -> Self { DragState::None }
 }
 
-// Actions for the flow canvas
+// Where a drag took a node, see `FlowCanvasAction::NodesMoved`
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeMove {
+    pub node_id: String,
+    pub from: DVec2,
+    pub to: DVec2,
+}
+
+// Actions for the flow canvas.
+// Every graph edit, including undo and redo, is also reported as `GraphChanged`
+// with the commands that performed it, so a host model can replay it.
 #[derive(Clone, Debug, DefaultNone)]
 pub enum FlowCanvasAction {
     None,
-    NodeAdded,
-    NodeDeleted,
-    EdgeCreated,
-    EdgeDeleted,
-    SelectionChanged,
+    NodeAdded { node_ids: Vec<String> },
+    NodeDeleted { node_ids: Vec<String> },
+    NodesMoved { moves: Vec<NodeMove> }, // Drag finished; children of dragged groups included
+    NodeResized { node_id: String, from: Rect, to: Rect },
+    EdgeCreated { index: usize, edge: EdgeConnection },
+    EdgeDeleted { edges: Vec<EdgeConnection> }, // Includes edges removed with their nodes
+    EdgeReconnected { index: usize, old_edge: EdgeConnection, new_edge: EdgeConnection },
+    GroupChanged { group_ids: Vec<String> }, // Nodes grouped or ungrouped, or a group collapsed/expanded
+    SelectionChanged { nodes: Vec<String>, edges: Vec<usize> },
+    ViewportChanged { viewport: FlowViewport }, // Panned or zoomed
+    GraphChanged { label: String, commands: Vec<FlowCommand> }, // Label as in the history, "Undo "/"Redo " prefixed when replayed
+    ConnectionRejected { reason: ConnectionRejection },
+    LayoutSettled, // Live force-directed layout came to rest
     StatusUpdate { nodes: usize, edges: usize },
 }
 
@@ -578,10 +637,11 @@ impl Widget for FlowCanvas {
             self.initialize(cx);
             // Send initial status
             cx.widget_action(uid, &scope.path, FlowCanvasAction::StatusUpdate {
-                nodes: self.nodes.len(),
-                edges: self.edges.len(),
+                nodes: self.graph.node_count(),
+                edges: self.graph.edge_count(),
             });
         }
+        self.send_queued_actions(cx, uid, scope);
 
         // Handle animation timer
         if self.animation_timer.is_event(event).is_some() {
@@ -593,6 +653,23 @@ impl Widget for FlowCanvas {
             self.view.redraw(cx);
         }
 
+        // Step the live force-directed layout; nodes being dragged stay under the cursor
+        if self.simulation_timer.is_event(event).is_some() {
+            let held = self.dragged_node_ids();
+            let running = match &mut self.simulation {
+                Some(sim) => sim.step(&mut self.graph, &held),
+                None => false,
+            };
+            // An edge being reconnected is out of the graph; settle once it is back
+            // so the layout's undo step does not include its removal
+            let reconnecting = matches!(self.drag_state, DragState::ReconnectingEdge { .. });
+            if !running && !reconnecting {
+                self.stop_simulation(cx);
+                cx.widget_action(uid, &scope.path, FlowCanvasAction::LayoutSettled);
+            }
+            self.view.redraw(cx);
+        }
+
         let area_rect = self.view.area().rect(cx);
 
         // Handle keyboard events
@@ -600,23 +677,23 @@ impl Widget for FlowCanvas {
             Event::KeyDown(ke) => {
                 // Delete key - delete selected
                 if ke.key_code == KeyCode::Delete || ke.key_code == KeyCode::Backspace {
-                    self.delete_selected(cx, uid, scope);
+                    self.delete_selected(cx, uid, scope, "Delete");
                     cx.widget_action(uid, &scope.path, FlowCanvasAction::StatusUpdate {
-                        nodes: self.nodes.len(),
-                        edges: self.edges.len(),
+                        nodes: self.graph.node_count(),
+                        edges: self.graph.edge_count(),
                     });
                 }
                 // Ctrl+A or Cmd+A - select all
                 else if ke.key_code == KeyCode::KeyA && (ke.modifiers.control || ke.modifiers.logo) {
                     self.select_all(cx);
-                    cx.widget_action(uid, &scope.path, FlowCanvasAction::SelectionChanged);
+                    cx.widget_action(uid, &scope.path, self.selection_action());
                 }
                 // Ctrl+Z or Cmd+Z - undo
                 else if ke.key_code == KeyCode::KeyZ && (ke.modifiers.control || ke.modifiers.logo) && !ke.modifiers.shift {
                     self.undo(cx);
                     cx.widget_action(uid, &scope.path, FlowCanvasAction::StatusUpdate {
-                        nodes: self.nodes.len(),
-                        edges: self.edges.len(),
+                        nodes: self.graph.node_count(),
+                        edges: self.graph.edge_count(),
                     });
                 }
                 // Ctrl+Y/Cmd+Y or Ctrl+Shift+Z/Cmd+Shift+Z - redo
@@ -624,10 +701,31 @@ impl Widget for FlowCanvas {
                         (ke.key_code == KeyCode::KeyZ && (ke.modifiers.control || ke.modifiers.logo) && ke.modifiers.shift) {
                     self.redo(cx);
                     cx.widget_action(uid, &scope.path, FlowCanvasAction::StatusUpdate {
-                        nodes: self.nodes.len(),
-                        edges: self.edges.len(),
+                        nodes: self.graph.node_count(),
+                        edges: self.graph.edge_count(),
                     });
                 }
+                // Ctrl+G groups the selection, Ctrl+Shift+G ungroups the selected groups
+                else if ke.key_code == KeyCode::KeyG && (ke.modifiers.control || ke.modifiers.logo) {
+                    let group_ids = if ke.modifiers.shift {
+                        self.ungroup_selected(cx)
+                    } else {
+                        self.group_selected(cx, "Group").into_iter().collect()
+                    };
+                    if !group_ids.is_empty() {
+                        cx.widget_action(uid, &scope.path, FlowCanvasAction::GroupChanged { group_ids });
+                    }
+                }
+                // Ctrl+D or Cmd+D - duplicate selection
+                else if ke.key_code == KeyCode::KeyD && (ke.modifiers.control || ke.modifiers.logo) {
+                    if self.duplicate_selected(cx) {
+                        cx.widget_action(uid, &scope.path, FlowCanvasAction::NodeAdded { node_ids: self.selected_ids() });
+                        cx.widget_action(uid, &scope.path, FlowCanvasAction::StatusUpdate {
+                            nodes: self.graph.node_count(),
+                            edges: self.graph.edge_count(),
+                        });
+                    }
+                }
                 // Escape - deselect all
                 else if ke.key_code == KeyCode::Escape {
                     self.selected_nodes.clear();
@@ -635,7 +733,7 @@ impl Widget for FlowCanvas {
                     self.context_menu_node = None;
                     self.context_menu_edge = None;
                     self.drag_state = DragState::None;
-                    cx.widget_action(uid, &scope.path, FlowCanvasAction::SelectionChanged);
+                    cx.widget_action(uid, &scope.path, self.selection_action());
                     self.view.redraw(cx);
                 }
             }
@@ -646,22 +744,27 @@ impl Widget for FlowCanvas {
         match event.hits_with_options(cx, self.view.area(), HitOptions::new().with_capture_overload(true)) {
             Hit::FingerDown(fe) => {
                 let local = self.screen_to_canvas(fe.abs, area_rect);
+                self.pointer_pos = local;
+                // Key focus routes clipboard events to the canvas
+                cx.set_key_focus(self.view.area());
+                // Hit tests below use the routes and the spatial index
+                self.update_spatial();
 
                 // Check if clicking on node context menu
-                if let Some(node_idx) = self.context_menu_node {
-                    if node_idx < self.nodes.len() {
+                if let Some(node_id) = self.context_menu_node.clone() {
+                    if self.graph.contains_node(&node_id) {
                         let menu_pos = self.context_menu_pos;
                         let menu_width = 120.0;
                         let item_height = 22.0;
                         let padding = 4.0;
 
                         // Check if multi-selection mode
-                        let is_multi = self.selected_nodes.contains(&node_idx) && self.selected_nodes.len() > 1;
+                        let is_multi = self.selected_nodes.contains(&node_id) && self.selected_nodes.len() > 1;
                         let header_offset = if is_multi { item_height } else { 0.0 };
 
                         // Check if click is within menu bounds
                         let in_menu_x = fe.abs.x >= menu_pos.x && fe.abs.x <= menu_pos.x + menu_width;
-                        let num_items = if is_multi { 12.0 } else { 11.0 };
+                        let num_items = if is_multi { 13.5 } else { 12.5 };
                         let menu_height = item_height * num_items + padding * 2.0;
                         let in_menu_y = fe.abs.y >= menu_pos.y && fe.abs.y <= menu_pos.y + menu_height;
 
@@ -677,6 +780,10 @@ impl Widget for FlowCanvas {
                             // Border section: divider + label + 4 items
                             let border_label_start = shape_end + item_height * 0.5;
                             let border_start = border_label_start + label_height;
+                            let border_end = border_start + item_height * 4.0;
+
+                            // Pin section: divider + 1 item
+                            let pin_start = border_end + item_height * 0.5;
 
                             if rel_y >= shape_start && rel_y < shape_end {
                                 // Shape item clicked
@@ -689,28 +796,26 @@ impl Widget for FlowCanvas {
                                     _ => NodeShape::Diamond,
                                 };
                                 // Apply to all selected nodes if multi-selection
-                                if self.selected_nodes.contains(&node_idx) && self.selected_nodes.len() > 1 {
-                                    for &idx in &self.selected_nodes.clone() {
-                                        self.nodes[idx].shape = new_shape;
-                                    }
-                                } else {
-                                    self.nodes[node_idx].shape = new_shape;
-                                }
+                                let targets = if is_multi { self.selected_ids() } else { vec![node_id.clone()] };
+                                self.restyle_nodes("Change Shape", &targets, |node| node.shape = new_shape);
+                                self.context_menu_node = None;
+                                self.view.redraw(cx);
+                                return;
+                            } else if rel_y >= pin_start {
+                                // Pin item clicked: pin all targets unless the clicked node is pinned
+                                let pin = !self.graph.node(&node_id).is_some_and(|n| n.pinned);
+                                let targets = if is_multi { self.selected_ids() } else { vec![node_id.clone()] };
+                                self.restyle_nodes(if pin { "Pin" } else { "Unpin" }, &targets, |node| node.pinned = pin);
                                 self.context_menu_node = None;
                                 self.view.redraw(cx);
                                 return;
-                            } else if rel_y >= border_start {
+                            } else if rel_y >= border_start && rel_y < border_end {
                                 // Border item clicked
                                 let border_idx = ((rel_y - border_start) / item_height) as usize;
                                 let new_border = (border_idx + 1).min(4) as f32;
                                 // Apply to all selected nodes if multi-selection
-                                if self.selected_nodes.contains(&node_idx) && self.selected_nodes.len() > 1 {
-                                    for &idx in &self.selected_nodes.clone() {
-                                        self.nodes[idx].border_width = new_border;
-                                    }
-                                } else {
-                                    self.nodes[node_idx].border_width = new_border;
-                                }
+                                let targets = if is_multi { self.selected_ids() } else { vec![node_id.clone()] };
+                                self.restyle_nodes("Change Border", &targets, |node| node.border_width = new_border);
                                 self.context_menu_node = None;
                                 self.view.redraw(cx);
                                 return;
@@ -729,12 +834,12 @@ impl Widget for FlowCanvas {
 
                 // Check if clicking on edge context menu
                 if let Some(edge_idx) = self.context_menu_edge {
-                    if edge_idx < self.edges.len() {
+                    if self.graph.edge(edge_idx).is_some() {
                         let menu_pos = self.context_menu_pos;
                         let menu_width = 120.0;
                         let item_height = 22.0;
                         let padding = 8.0;
-                        let menu_height = 300.0;
+                        let menu_height = 420.0;
 
                         let in_menu_x = fe.abs.x >= menu_pos.x && fe.abs.x <= menu_pos.x + menu_width;
                         let in_menu_y = fe.abs.y >= menu_pos.y && fe.abs.y <= menu_pos.y + menu_height;
@@ -755,25 +860,39 @@ impl Widget for FlowCanvas {
                             // Animation section: divider + label + 2 items
                             let anim_label_start = width_end + item_height * 0.5;
                             let anim_start = anim_label_start + label_height;
+                            let anim_end = anim_start + item_height * 2.0;
+
+                            // Path section: divider + label + one item per path type
+                            let path_label_start = anim_end + item_height * 0.5;
+                            let path_start = path_label_start + label_height;
+                            let path_end = path_start + item_height * EdgePathType::ALL.len() as f64;
 
                             if rel_y >= style_start && rel_y < style_end {
                                 // Style item clicked
                                 let style_idx = ((rel_y - style_start) / item_height) as usize;
-                                self.edges[edge_idx].style = style_idx.min(2) as f32;
+                                self.restyle_edge("Change Edge Style", edge_idx, |edge| edge.style = style_idx.min(2) as f32);
                                 self.context_menu_edge = None;
                                 self.view.redraw(cx);
                                 return;
                             } else if rel_y >= width_start && rel_y < width_end {
                                 // Width item clicked
                                 let width_idx = ((rel_y - width_start) / item_height) as usize;
-                                self.edges[edge_idx].width = (width_idx + 1).min(4) as f32;
+                                self.restyle_edge("Change Edge Width", edge_idx, |edge| edge.width = (width_idx + 1).min(4) as f32);
                                 self.context_menu_edge = None;
                                 self.view.redraw(cx);
                                 return;
-                            } else if rel_y >= anim_start {
+                            } else if rel_y >= anim_start && rel_y < anim_end {
                                 // Animation item clicked
                                 let anim_idx = ((rel_y - anim_start) / item_height) as usize;
-                                self.edges[edge_idx].animated = anim_idx == 0;
+                                self.restyle_edge("Change Edge Animation", edge_idx, |edge| edge.animated = anim_idx == 0);
+                                self.context_menu_edge = None;
+                                self.view.redraw(cx);
+                                return;
+                            } else if rel_y >= path_start && rel_y < path_end {
+                                // Path type item clicked
+                                let path_idx = ((rel_y - path_start) / item_height) as usize;
+                                let path_type = EdgePathType::ALL[path_idx.min(EdgePathType::ALL.len() - 1)];
+                                self.restyle_edge("Change Edge Path", edge_idx, |edge| edge.path_type = path_type);
                                 self.context_menu_edge = None;
                                 self.view.redraw(cx);
                                 return;
@@ -796,47 +915,78 @@ impl Widget for FlowCanvas {
                     return;
                 }
 
+                // Resize handles on selected nodes
+                if let Some((node_id, corner, original)) = self.resize_handle_at(local) {
+                    self.context_menu_node = None;
+                    self.drag_state = DragState::ResizingNode { node_id, corner, original };
+                    self.begin_edit("Resize Node");
+                    cx.set_cursor(corner.cursor());
+                    return;
+                }
+
                 // Check port clicks first (for edge creation)
-                for (i, node) in self.nodes.iter().enumerate() {
-                    // Check output port
-                    if node.node_type.has_output() {
-                        let port_rect = node.output_port_rect();
-                        if port_rect.contains(local) {
-                            self.drag_state = DragState::CreatingEdge {
-                                from_node: i,
-                                is_output: true,
-                                cursor_pos: local
-                            };
-                            self.view.redraw(cx);
-                            return;
-                        }
+                let candidates = self.spatial.nodes_at(local);
+                for node in candidates.iter().filter_map(|&i| self.graph.nodes().get(i)) {
+                    // Check output ports
+                    if let Some(index) = node.output_port_at(local) {
+                        self.drag_state = DragState::CreatingEdge {
+                            from_node: node.id.clone(),
+                            from_port: node.output_ports[index].id.clone(),
+                            is_output: true,
+                            cursor_pos: local
+                        };
+                        self.view.redraw(cx);
+                        return;
                     }
-                    // Check input port
-                    if node.node_type.has_input() {
-                        let port_rect = node.input_port_rect();
-                        if port_rect.contains(local) {
-                            self.drag_state = DragState::CreatingEdge {
-                                from_node: i,
-                                is_output: false,
-                                cursor_pos: local
-                            };
-                            self.view.redraw(cx);
-                            return;
+                    // Check input ports
+                    if let Some(index) = node.input_port_at(local) {
+                        self.drag_state = DragState::CreatingEdge {
+                            from_node: node.id.clone(),
+                            from_port: node.input_ports[index].id.clone(),
+                            is_output: false,
+                            cursor_pos: local
+                        };
+                        self.view.redraw(cx);
+                        return;
+                    }
+                }
+
+                // Grab an edge near one of its ends to reconnect it
+                if !fe.modifiers.control {
+                    if let Some((index, moving_target)) = self.edge_end_at(local) {
+                        // The edge stays in the graph until the end is actually dragged
+                        self.context_menu_edge = None;
+                        self.drag_state = DragState::PendingReconnect { index, moving_target, start: fe.abs };
+                        cx.set_cursor(MouseCursor::Hand);
+                        return;
+                    }
+                }
+
+                // Double-click on a group's header collapses or expands it
+                if fe.tap_count == 2 {
+                    let group_id = self.node_at(local)
+                        .filter(|n| n.is_group() && local.y < n.y + node::HEADER_HEIGHT)
+                        .map(|n| n.id.clone());
+                    if let Some(group_id) = group_id {
+                        if self.toggle_group(cx, &group_id) {
+                            cx.widget_action(uid, &scope.path, FlowCanvasAction::GroupChanged { group_ids: vec![group_id] });
                         }
+                        return;
                     }
                 }
 
                 // Check node clicks (reverse for z-order)
-                for (i, node) in self.nodes.iter().enumerate().rev() {
+                for node in candidates.iter().rev().filter_map(|&i| self.graph.nodes().get(i)) {
                     if node.contains(local) {
+                        let node_id = node.id.clone();
                         // Check for Ctrl+click on node header for context menu
-                        let header_bottom = node.y + 32.0; // Header is 32px tall
+                        let header_bottom = node.y + node::HEADER_HEIGHT;
                         let is_header_click = local.y < header_bottom;
                         let is_context_click = fe.modifiers.control; // Ctrl+click
 
                         if is_context_click && is_header_click {
                             // Show context menu for this node
-                            self.context_menu_node = Some(i);
+                            self.context_menu_node = Some(node_id);
                             self.context_menu_pos = fe.abs;
                             self.view.redraw(cx);
                             return;
@@ -848,43 +998,43 @@ impl Widget for FlowCanvas {
                         // Multi-selection with shift+click
                         if fe.modifiers.shift {
                             // Toggle selection
-                            if self.selected_nodes.contains(&i) {
-                                self.selected_nodes.remove(&i);
-                            } else {
-                                self.selected_nodes.insert(i);
+                            if !self.selected_nodes.remove(&node_id) {
+                                self.selected_nodes.insert(node_id.clone());
                             }
                         } else {
                             // Single selection - clear others
                             self.selected_nodes.clear();
                             self.selected_edges.clear();
-                            self.selected_nodes.insert(i);
+                            self.selected_nodes.insert(node_id.clone());
                         }
 
                         // Setup drag - handle multi-node drag if multiple selected
-                        if self.selected_nodes.len() > 1 && self.selected_nodes.contains(&i) {
-                            let offsets: Vec<(usize, DVec2)> = self.selected_nodes.iter()
-                                .map(|&idx| {
-                                    let n = &self.nodes[idx];
-                                    (idx, DVec2 { x: local.x - n.x, y: local.y - n.y })
-                                })
+                        let offset = DVec2 { x: local.x - node.x, y: local.y - node.y };
+                        if self.selected_nodes.len() > 1 && self.selected_nodes.contains(&node_id) {
+                            // Children of selected groups move with their group
+                            let offsets: Vec<(String, DVec2)> = self.graph.nodes().iter()
+                                .filter(|n| self.selected_nodes.contains(&n.id))
+                                .filter(|n| !self.selected_nodes.iter().any(|g| self.graph.is_descendant(&n.id, g)))
+                                .map(|n| (n.id.clone(), DVec2 { x: local.x - n.x, y: local.y - n.y }))
                                 .collect();
                             self.drag_state = DragState::DraggingNodes { offsets };
+                            self.begin_edit("Move Nodes");
+                            self.remember_drag_origin();
                         } else {
-                            self.drag_state = DragState::DraggingNode {
-                                index: i,
-                                offset: DVec2 { x: local.x - node.x, y: local.y - node.y },
-                            };
+                            self.drag_state = DragState::DraggingNode { node_id, offset };
+                            self.begin_edit("Move Node");
+                            self.remember_drag_origin();
                         }
                         cx.set_cursor(MouseCursor::Hand);
-                        cx.widget_action(uid, &scope.path, FlowCanvasAction::SelectionChanged);
+                        cx.widget_action(uid, &scope.path, self.selection_action());
                         self.view.redraw(cx);
                         return;
                     }
                 }
 
                 // Check edge clicks for selection or context menu
-                for (i, edge) in self.edges.iter().enumerate() {
-                    if self.point_near_edge(local, edge) {
+                for i in self.spatial.edges_at(local) {
+                    if self.graph.edge(i).is_some_and(|edge| self.point_near_edge(local, i, edge)) {
                         // Ctrl+click shows edge context menu
                         if fe.modifiers.control {
                             self.context_menu_edge = Some(i);
@@ -906,7 +1056,7 @@ impl Widget for FlowCanvas {
                             self.selected_edges.insert(i);
                         }
                         self.context_menu_edge = None;
-                        cx.widget_action(uid, &scope.path, FlowCanvasAction::SelectionChanged);
+                        cx.widget_action(uid, &scope.path, self.selection_action());
                         self.view.redraw(cx);
                         return;
                     }
@@ -921,30 +1071,49 @@ impl Widget for FlowCanvas {
                 }
                 self.context_menu_node = None;
                 self.context_menu_edge = None;
-                cx.widget_action(uid, &scope.path, FlowCanvasAction::SelectionChanged);
+                cx.widget_action(uid, &scope.path, self.selection_action());
                 self.view.redraw(cx);
             }
 
             Hit::FingerMove(fe) => {
                 let local = self.screen_to_canvas(fe.abs, area_rect);
+                self.pointer_pos = local;
+                // Edge and selection drags hit test against the spatial index
+                if matches!(self.drag_state, DragState::CreatingEdge { .. } | DragState::ReconnectingEdge { .. } | DragState::SelectionBox { .. }) {
+                    self.update_spatial();
+                }
 
                 match &self.drag_state {
-                    DragState::DraggingNode { index, offset } => {
-                        let idx = *index;
+                    DragState::DraggingNode { node_id, offset } => {
+                        let node_id = node_id.clone();
                         let off = *offset;
-                        self.nodes[idx].x = (local.x - off.x).max(0.0);
-                        self.nodes[idx].y = (local.y - off.y).max(0.0);
+                        let pos = self.snap_point(DVec2 { x: (local.x - off.x).max(0.0), y: (local.y - off.y).max(0.0) });
+                        self.graph.move_node_with_children(&node_id, pos.x, pos.y);
+                        self.graph.grow_parents(&node_id);
+                        if let Some(sim) = &mut self.simulation {
+                            sim.reheat(simulation::DRAG_REHEAT);
+                        }
                         self.view.redraw(cx);
                     }
                     DragState::DraggingNodes { offsets } => {
                         // Move all selected nodes together
                         let offsets_clone = offsets.clone();
-                        for (idx, off) in offsets_clone {
-                            self.nodes[idx].x = (local.x - off.x).max(0.0);
-                            self.nodes[idx].y = (local.y - off.y).max(0.0);
+                        for (node_id, off) in offsets_clone {
+                            let pos = self.snap_point(DVec2 { x: (local.x - off.x).max(0.0), y: (local.y - off.y).max(0.0) });
+                            self.graph.move_node_with_children(&node_id, pos.x, pos.y);
+                            self.graph.grow_parents(&node_id);
+                        }
+                        if let Some(sim) = &mut self.simulation {
+                            sim.reheat(simulation::DRAG_REHEAT);
                         }
                         self.view.redraw(cx);
                     }
+                    DragState::ResizingNode { node_id, corner, original } => {
+                        let (node_id, corner, original) = (node_id.clone(), *corner, *original);
+                        let pos = self.snap_point(local);
+                        self.resize_node_to(&node_id, corner, original, pos);
+                        self.view.redraw(cx);
+                    }
                     DragState::Panning { start } => {
                         let delta = DVec2 {
                             x: fe.abs.x - start.x,
@@ -953,16 +1122,44 @@ impl Widget for FlowCanvas {
                         self.pan_offset.x += delta.x;
                         self.pan_offset.y += delta.y;
                         self.drag_state = DragState::Panning { start: fe.abs };
+                        self.viewport_changed();
                         self.view.redraw(cx);
                     }
-                    DragState::CreatingEdge { from_node, is_output, .. } => {
+                    DragState::CreatingEdge { from_node, from_port, is_output, .. } => {
+                        let (from_node, from_port, is_output) = (from_node.clone(), from_port.clone(), *is_output);
+                        self.connection_check = self.edge_target(&from_node, &from_port, is_output, local)
+                            .map(|edge| self.validate_connection(&edge));
                         self.drag_state = DragState::CreatingEdge {
-                            from_node: *from_node,
-                            is_output: *is_output,
+                            from_node,
+                            from_port,
+                            is_output,
                             cursor_pos: local,
                         };
                         self.view.redraw(cx);
                     }
+                    DragState::PendingReconnect { index, moving_target, start } => {
+                        let (index, moving_target) = (*index, *moving_target);
+                        if (fe.abs - *start).length() > edge::RECONNECT_DRAG_THRESHOLD {
+                            self.begin_edit("Reconnect Edge");
+                            if let Some(edge) = self.graph.disconnect(index) {
+                                // Edge indices after `index` shift while it is out of the graph
+                                if !self.selected_edges.is_empty() {
+                                    self.selected_edges.clear();
+                                    cx.widget_action(uid, &scope.path, self.selection_action());
+                                }
+                                self.drag_state = DragState::ReconnectingEdge { index, edge, moving_target, cursor_pos: local };
+                                self.view.redraw(cx);
+                            }
+                        }
+                    }
+                    DragState::ReconnectingEdge { edge, moving_target, .. } => {
+                        self.connection_check = self.reconnect_target(edge, *moving_target, local)
+                            .map(|edge| self.validate_connection(&edge));
+                        if let DragState::ReconnectingEdge { cursor_pos, .. } = &mut self.drag_state {
+                            *cursor_pos = local;
+                        }
+                        self.view.redraw(cx);
+                    }
                     DragState::SelectionBox { start, .. } => {
                         // Update selection box and select nodes within
                         let start_pos = *start;
@@ -975,12 +1172,16 @@ impl Widget for FlowCanvas {
                         let max_y = start_pos.y.max(local.y);
 
                         self.selected_nodes.clear();
-                        for (i, node) in self.nodes.iter().enumerate() {
+                        let area = Rect {
+                            pos: DVec2 { x: min_x, y: min_y },
+                            size: DVec2 { x: max_x - min_x, y: max_y - min_y },
+                        };
+                        for node in self.spatial.nodes_in(area).into_iter().filter_map(|i| self.graph.nodes().get(i)) {
                             let node_center_x = node.x + node.width / 2.0;
                             let node_center_y = node.y + node.height / 2.0;
                             if node_center_x >= min_x && node_center_x <= max_x &&
                                node_center_y >= min_y && node_center_y <= max_y {
-                                self.selected_nodes.insert(i);
+                                self.selected_nodes.insert(node.id.clone());
                             }
                         }
                         self.view.redraw(cx);
@@ -991,49 +1192,102 @@ impl Widget for FlowCanvas {
 
             Hit::FingerUp(fe) => {
                 let local = self.screen_to_canvas(fe.abs, area_rect);
+                self.update_spatial();
+
+                // Handle node resize completion
+                if let DragState::ResizingNode { node_id, original, .. } = &self.drag_state {
+                    let resized = self.graph.node(node_id).map(|n| n.rect()).filter(|r| r != original);
+                    if let Some(to) = resized {
+                        cx.widget_action(uid, &scope.path, FlowCanvasAction::NodeResized {
+                            node_id: node_id.clone(),
+                            from: *original,
+                            to,
+                        });
+                    }
+                }
 
-                // Handle edge creation completion
-                if let DragState::CreatingEdge { from_node, is_output, .. } = &self.drag_state {
-                    let from_idx = *from_node;
-                    let from_is_output = *is_output;
-
-                    // Find target port
-                    for (i, node) in self.nodes.iter().enumerate() {
-                        if i == from_idx { continue; }
-
-                        // If dragging from output, look for input ports
-                        if from_is_output && node.node_type.has_input() {
-                            let port_rect = node.input_port_rect();
-                            if port_rect.contains(local) {
-                                // Check if edge already exists
-                                let exists = self.edges.iter().any(|e|
-                                    e.from_node == from_idx && e.to_node == i
-                                );
-                                if !exists {
-                                    self.edges.push(EdgeConnection::new(from_idx, i));
-                                    cx.widget_action(uid, &scope.path, FlowCanvasAction::EdgeCreated);
+                // Report where dragged nodes ended up
+                let moves: Vec<NodeMove> = std::mem::take(&mut self.drag_origin).into_iter()
+                    .filter_map(|(node_id, from)| {
+                        let node = self.graph.node(&node_id)?;
+                        let to = DVec2 { x: node.x, y: node.y };
+                        (to != from).then_some(NodeMove { node_id, from, to })
+                    })
+                    .collect();
+                if !moves.is_empty() {
+                    cx.widget_action(uid, &scope.path, FlowCanvasAction::NodesMoved { moves });
+                }
+
+                // The selection box has been updating the selection while it was dragged
+                if matches!(self.drag_state, DragState::SelectionBox { .. }) {
+                    cx.widget_action(uid, &scope.path, self.selection_action());
+                }
+
+                // A grabbed edge end released without dragging is a click on the edge
+                if let DragState::PendingReconnect { index, .. } = &self.drag_state {
+                    self.selected_nodes.clear();
+                    self.selected_edges.clear();
+                    self.selected_edges.insert(*index);
+                    cx.widget_action(uid, &scope.path, self.selection_action());
+                }
+
+                // Handle edge reconnection completion. The target is validated while the edge
+                // is still out of the graph, so it does not count against its own ports.
+                // Putting the edge back cancels its removal, leaving no undo step.
+                if let DragState::ReconnectingEdge { index, edge, moving_target, .. } = &self.drag_state {
+                    let (index, edge) = (*index, edge.clone());
+                    match self.reconnect_target(&edge, *moving_target, local) {
+                        Some(new_edge) if new_edge.same_endpoints(&edge) => self.graph.insert_edge(index, edge),
+                        Some(new_edge) => match self.validate_connection(&new_edge) {
+                            Ok(()) => {
+                                self.graph.insert_edge(index, new_edge.clone());
+                                cx.widget_action(uid, &scope.path, FlowCanvasAction::EdgeReconnected {
+                                    index,
+                                    old_edge: edge,
+                                    new_edge,
+                                });
+                            }
+                            Err(reason) => {
+                                self.graph.insert_edge(index, edge);
+                                cx.widget_action(uid, &scope.path, FlowCanvasAction::ConnectionRejected { reason });
+                            }
+                        },
+                        // Dropped on empty space: the edge stays deleted
+                        None => {
+                            if self.simulation.is_none() {
+                                if let Some(label) = &mut self.pending_edit {
+                                    *label = "Delete Edge".to_string();
                                 }
-                                break;
                             }
+                            cx.widget_action(uid, &scope.path, FlowCanvasAction::EdgeDeleted { edges: vec![edge] });
                         }
-                        // If dragging from input, look for output ports
-                        if !from_is_output && node.node_type.has_output() {
-                            let port_rect = node.output_port_rect();
-                            if port_rect.contains(local) {
-                                let exists = self.edges.iter().any(|e|
-                                    e.from_node == i && e.to_node == from_idx
-                                );
-                                if !exists {
-                                    self.edges.push(EdgeConnection::new(i, from_idx));
-                                    cx.widget_action(uid, &scope.path, FlowCanvasAction::EdgeCreated);
+                    }
+                }
+
+                // A drag, resize or reconnect is one undo step, begun when the drag started;
+                // during a live layout it joins the layout's step
+                if self.simulation.is_none() {
+                    self.end_edit();
+                }
+
+                // Handle edge creation completion
+                if let DragState::CreatingEdge { from_node, from_port, is_output, .. } = &self.drag_state {
+                    if let Some(edge) = self.edge_target(from_node, from_port, *is_output, local) {
+                        match self.validate_connection(&edge) {
+                            Ok(()) => {
+                                if let Some(index) = self.edit_graph("Connect", |graph| graph.connect(edge.clone())) {
+                                    cx.widget_action(uid, &scope.path, FlowCanvasAction::EdgeCreated { index, edge });
                                 }
-                                break;
+                            }
+                            Err(reason) => {
+                                cx.widget_action(uid, &scope.path, FlowCanvasAction::ConnectionRejected { reason });
                             }
                         }
                     }
                 }
 
                 self.drag_state = DragState::None;
+                self.connection_check = None;
                 cx.set_cursor(MouseCursor::Arrow);
                 self.view.redraw(cx);
             }
@@ -1048,6 +1302,7 @@ impl Widget for FlowCanvas {
                 self.pan_offset.x = se.abs.x - area_rect.pos.x - (local.x * self.zoom);
                 self.pan_offset.y = se.abs.y - area_rect.pos.y - (local.y * self.zoom);
 
+                self.viewport_changed();
                 self.view.redraw(cx);
             }
 
@@ -1055,6 +1310,39 @@ impl Widget for FlowCanvas {
                 cx.set_cursor(MouseCursor::Arrow);
             }
 
+            Hit::FingerHoverOver(fh) => {
+                self.pointer_pos = self.screen_to_canvas(fh.abs, area_rect);
+            }
+
+            // Ctrl+C / Ctrl+X: the selection goes to the system clipboard as a flow document
+            Hit::TextCopy(ce) => {
+                if let Some(text) = self.copy_selection() {
+                    *ce.response.borrow_mut() = Some(text);
+                }
+            }
+
+            Hit::TextCut(ce) => {
+                if let Some(text) = self.copy_selection() {
+                    *ce.response.borrow_mut() = Some(text);
+                    self.delete_selected(cx, uid, scope, "Cut");
+                    cx.widget_action(uid, &scope.path, FlowCanvasAction::StatusUpdate {
+                        nodes: self.graph.node_count(),
+                        edges: self.graph.edge_count(),
+                    });
+                }
+            }
+
+            // Ctrl+V: paste a copied flow document at the pointer
+            Hit::TextInput(te) if te.was_paste => {
+                if self.paste_document(cx, &te.input) {
+                    cx.widget_action(uid, &scope.path, FlowCanvasAction::NodeAdded { node_ids: self.selected_ids() });
+                    cx.widget_action(uid, &scope.path, FlowCanvasAction::StatusUpdate {
+                        nodes: self.graph.node_count(),
+                        edges: self.graph.edge_count(),
+                    });
+                }
+            }
+
             _ => {}
         }
 
@@ -1065,18 +1353,20 @@ impl Widget for FlowCanvas {
             Event::Actions(actions) => {
                 for action in actions {
                     match action.cast() {
-                        FlowCanvasCommand::AddNode => {
-                            self.add_node(cx, NodeType::Custom);
-                            cx.widget_action(uid, &scope.path, FlowCanvasAction::StatusUpdate {
-                                nodes: self.nodes.len(),
-                                edges: self.edges.len(),
-                            });
+                        FlowCanvasCommand::AddNode { template } => {
+                            if self.add_node(cx, &template, None).is_some() {
+                                cx.widget_action(uid, &scope.path, FlowCanvasAction::NodeAdded { node_ids: self.selected_ids() });
+                                cx.widget_action(uid, &scope.path, FlowCanvasAction::StatusUpdate {
+                                    nodes: self.graph.node_count(),
+                                    edges: self.graph.edge_count(),
+                                });
+                            }
                         }
                         FlowCanvasCommand::Delete => {
-                            self.delete_selected(cx, uid, scope);
+                            self.delete_selected(cx, uid, scope, "Delete");
                             cx.widget_action(uid, &scope.path, FlowCanvasAction::StatusUpdate {
-                                nodes: self.nodes.len(),
-                                edges: self.edges.len(),
+                                nodes: self.graph.node_count(),
+                                edges: self.graph.edge_count(),
                             });
                         }
                         FlowCanvasCommand::FitView => {
@@ -1085,8 +1375,8 @@ impl Widget for FlowCanvas {
                         FlowCanvasCommand::Clear => {
                             self.clear(cx);
                             cx.widget_action(uid, &scope.path, FlowCanvasAction::StatusUpdate {
-                                nodes: self.nodes.len(),
-                                edges: self.edges.len(),
+                                nodes: self.graph.node_count(),
+                                edges: self.graph.edge_count(),
                             });
                         }
                         FlowCanvasCommand::SetLineStyle(style) => {
@@ -1098,64 +1388,87 @@ impl Widget for FlowCanvas {
                             self.view.redraw(cx);
                         }
                         FlowCanvasCommand::LoadDataflow { nodes, edges } => {
-                            self.nodes = nodes;
-                            self.edges = edges;
+                            self.graph = FlowGraph::from_parts(nodes, edges);
                             self.selected_nodes.clear();
                             self.selected_edges.clear();
-                            self.undo_stack.clear();
-                            self.redo_stack.clear();
+                            self.selection_changed();
+                            self.pending_edit = None;
+                            self.history.clear();
                             cx.action(FlowCanvasAction::StatusUpdate {
-                                nodes: self.nodes.len(),
-                                edges: self.edges.len(),
+                                nodes: self.graph.node_count(),
+                                edges: self.graph.edge_count(),
                             });
                             self.view.redraw(cx);
                         }
                         FlowCanvasCommand::None => {}
                     }
+
+                    // Entries dragged out of a FlowPalette
+                    match action.cast() {
+                        FlowPaletteAction::Dragging { template, abs } => {
+                            let rect = self.view.area().rect(cx);
+                            let over = rect.contains(abs).then(|| (template, self.screen_to_canvas(abs, rect)));
+                            if over != self.palette_drop {
+                                self.palette_drop = over;
+                                self.view.redraw(cx);
+                            }
+                        }
+                        FlowPaletteAction::Dropped { template, abs } => {
+                            if self.palette_drop.take().is_some() {
+                                self.view.redraw(cx);
+                            }
+                            let rect = self.view.area().rect(cx);
+                            if !rect.contains(abs) {
+                                continue;
+                            }
+                            let Some(template_size) = self.templates.get(&template).map(|t| t.size()) else { continue; };
+                            let center = self.screen_to_canvas(abs, rect);
+                            let pos = DVec2 { x: center.x - template_size.0 / 2.0, y: center.y - template_size.1 / 2.0 };
+                            if self.add_node(cx, &template, Some(pos)).is_some() {
+                                cx.widget_action(uid, &scope.path, FlowCanvasAction::NodeAdded { node_ids: self.selected_ids() });
+                                cx.widget_action(uid, &scope.path, FlowCanvasAction::StatusUpdate {
+                                    nodes: self.graph.node_count(),
+                                    edges: self.graph.edge_count(),
+                                });
+                            }
+                        }
+                        FlowPaletteAction::None => {}
+                    }
                 }
             }
             _ => {}
         }
+        self.send_queued_actions(cx, uid, scope);
     }
 
     fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        self.draw_revision = self.draw_revision.wrapping_add(1);
+
         // Begin drawing
         cx.begin_turtle(walk, Layout::flow_overlay());
 
         // Draw background
         let _ = self.view.draw_walk(cx, scope, walk.with_abs_pos(DVec2::default()));
+        self.draw_background_grid(cx);
+
+        // Only nodes and edges overlapping the view are drawn
+        self.update_spatial();
+        let margin = spatial::CULL_MARGIN / self.zoom;
+        let visible = self.visible_rect(cx);
+        let visible = Rect {
+            pos: DVec2 { x: visible.pos.x - margin, y: visible.pos.y - margin },
+            size: DVec2 { x: visible.size.x + margin * 2.0, y: visible.size.y + margin * 2.0 },
+        };
 
         // Collect edge data first to avoid borrow issues - use per-edge properties
         let anim_phase = self.animation_phase;
-        let edges_to_draw: Vec<_> = self.edges.iter().enumerate()
-            .filter(|(_, edge)| edge.from_node < self.nodes.len() && edge.to_node < self.nodes.len())
-            .map(|(i, edge)| {
-                let from_node = &self.nodes[edge.from_node];
-                let to_node = &self.nodes[edge.to_node];
-
-                // Get port-specific positions if port IDs are specified
-                let from_pos = if !edge.from_port.is_empty() {
-                    if let Some(port_idx) = from_node.output_port_index(&edge.from_port) {
-                        from_node.output_port_pos(port_idx)
-                    } else {
-                        from_node.output_pos()
-                    }
-                } else {
-                    from_node.output_pos()
-                };
-
-                let to_pos = if !edge.to_port.is_empty() {
-                    if let Some(port_idx) = to_node.input_port_index(&edge.to_port) {
-                        to_node.input_port_pos(port_idx)
-                    } else {
-                        to_node.input_pos()
-                    }
-                } else {
-                    to_node.input_pos()
-                };
-
-                let from = self.canvas_to_screen_pt(from_pos);
-                let to = self.canvas_to_screen_pt(to_pos);
+        let edges_to_draw: Vec<_> = self.spatial.edges_in(visible).into_iter()
+            .filter_map(|i| {
+                let edge = self.graph.edge(i)?;
+                let points: Vec<DVec2> = self.edge_canvas_points(i, edge)?.into_iter()
+                    .map(|p| self.canvas_to_screen_pt(p))
+                    .collect();
+                let cubics = self.screen_cubics(self.edge_canvas_cubics(i, edge)?);
                 let selected = self.selected_edges.contains(&i);
                 // Use per-edge properties
                 let edge_style = edge.style;
@@ -1163,29 +1476,34 @@ impl Widget for FlowCanvas {
                 let edge_animated = edge.animated;
                 let marker = edge.marker_end;
                 let label = edge.label.clone();
-                (from, to, selected, edge_style, edge_width, edge_animated, marker, label)
+                Some((points, cubics, selected, edge_style, edge_width, edge_animated, marker, label))
             })
             .collect();
 
-        // Draw edges using DrawColor for line segments
-        for (from, to, selected, edge_style, edge_width, edge_animated, marker, label) in edges_to_draw {
+        // Draw edges with the edge shader, one quad per cubic piece
+        let detail = self.detail_level();
+        for (points, cubics, selected, edge_style, edge_width, edge_animated, marker, label) in edges_to_draw {
             // Use negative value for anim_phase if animation is off (global or per-edge)
             let phase = if self.animate_edges && edge_animated { anim_phase } else { -1.0 };
-            self.draw_bezier_edge(cx, from, to, selected, edge_width, edge_style, phase);
+            self.draw_edge_path(cx, &cubics, selected, edge_width, edge_style, phase);
+
+            if detail < DetailLevel::Full {
+                continue;
+            }
 
             // Draw edge marker (arrow) at endpoint
             if marker != EdgeMarker::None {
-                self.draw_edge_marker(cx, from, to, selected, edge_width, marker);
+                self.draw_edge_marker(cx, &points, selected, edge_width, marker);
             }
 
-            // Draw edge label at midpoint (light theme)
+            // Draw edge label halfway along the path
             if !label.is_empty() {
-                let mid = DVec2 { x: (from.x + to.x) / 2.0, y: (from.y + to.y) / 2.0 };
+                let mid = edge_path::point_along(&points, 0.5);
                 self.draw_text.text_style.font_size = 9.0;
-                self.draw_text.color = if selected { vec4(0.2, 0.4, 0.7, 1.0) } else { vec4(0.4, 0.4, 0.45, 1.0) };
+                self.draw_text.color = if selected { self.theme.edge_label_selected } else { self.theme.edge_label };
                 // Draw label background
                 let label_width = label.len() as f64 * 6.0;
-                self.draw_node_bg.color = vec4(1.0, 1.0, 1.0, 0.95); // White background
+                self.draw_node_bg.color = self.theme.edge_label_background;
                 self.draw_node_bg.draw_abs(cx, Rect {
                     pos: DVec2 { x: mid.x - label_width / 2.0 - 4.0, y: mid.y - 8.0 },
                     size: DVec2 { x: label_width + 8.0, y: 16.0 },
@@ -1195,29 +1513,51 @@ impl Widget for FlowCanvas {
         }
 
         // Draw edge being created
-        let creating_edge_data = if let DragState::CreatingEdge { from_node, is_output, cursor_pos } = &self.drag_state {
-            if *from_node < self.nodes.len() {
-                let node = &self.nodes[*from_node];
-                let port_pos = if *is_output { node.output_pos() } else { node.input_pos() };
-                let from = self.canvas_to_screen_pt(port_pos);
-                let to = self.canvas_to_screen_pt(*cursor_pos);
-                Some((from, to))
-            } else {
-                None
+        let creating_edge_data = match &self.drag_state {
+            DragState::CreatingEdge { from_node, from_port, is_output, cursor_pos } => {
+                self.graph.node(from_node).map(|node| {
+                    let port_pos = if *is_output { node.output_anchor(from_port) } else { node.input_anchor(from_port) };
+                    self.edge_screen_cubics(EdgePathType::Bezier, port_pos, *cursor_pos)
+                })
             }
-        } else {
-            None
+            // The fixed end stays on its port, the dragged end follows the cursor
+            DragState::ReconnectingEdge { edge, moving_target, cursor_pos, .. } => {
+                if *moving_target {
+                    self.graph.node(&edge.from_node)
+                        .map(|node| self.edge_screen_cubics(edge.path_type, node.output_anchor(&edge.from_port), *cursor_pos))
+                } else {
+                    self.graph.node(&edge.to_node)
+                        .map(|node| self.edge_screen_cubics(edge.path_type, *cursor_pos, node.input_anchor(&edge.to_port)))
+                }
+            }
+            _ => None,
         };
-        if let Some((from, to)) = creating_edge_data {
-            // Use defaults for edge being created
-            self.draw_bezier_edge(cx, from, to, true, 2.0, 0.0, anim_phase);
+        if let Some(cubics) = creating_edge_data {
+            // Use defaults for edge being created, tinted by whether the port under the cursor accepts it
+            let selected_color = self.edge_selected_color;
+            match &self.connection_check {
+                Some(Ok(())) => self.edge_selected_color = self.edge_valid_color,
+                Some(Err(_)) => self.edge_selected_color = self.edge_invalid_color,
+                None => {}
+            }
+            self.draw_edge_path(cx, &cubics, true, 2.0, 0.0, anim_phase);
+            self.edge_selected_color = selected_color;
         }
 
         // Draw nodes - clone to avoid borrow issues, use each node's own shape and border
-        let nodes_to_draw: Vec<_> = self.nodes.iter().cloned().enumerate()
-            .map(|(i, node)| (node, self.selected_nodes.contains(&i)))
+        let nodes_to_draw: Vec<_> = self.spatial.nodes_in(visible).into_iter()
+            .filter_map(|i| self.graph.nodes().get(i))
+            .map(|node| (node.clone(), self.selected_nodes.contains(&node.id)))
             .collect();
         for (node, is_selected) in nodes_to_draw {
+            if node.is_frame() {
+                self.draw_frame(cx, &node, is_selected);
+                continue;
+            }
+            if detail < DetailLevel::Full {
+                self.draw_node_block(cx, &node, is_selected, detail);
+                continue;
+            }
             let shape = node.shape;
             let border_width = node.border_width as f64;
             self.draw_node(cx, &node, is_selected, shape, border_width);
@@ -1232,36 +1572,34 @@ impl Widget for FlowCanvas {
             let min_y = start_screen.y.min(current_screen.y);
             let max_y = start_screen.y.max(current_screen.y);
 
-            // Draw selection box fill
-            self.draw_node_bg.color = vec4(0.3, 0.5, 0.8, 0.15);
-            self.draw_node_bg.draw_abs(cx, Rect {
+            self.draw_selection_box(cx, Rect {
                 pos: DVec2 { x: min_x, y: min_y },
                 size: DVec2 { x: max_x - min_x, y: max_y - min_y },
             });
+        }
 
-            // Draw selection box border
-            self.draw_node_bg.color = vec4(0.4, 0.6, 1.0, 0.6);
-            let border = 1.0;
-            // Top
-            self.draw_node_bg.draw_abs(cx, Rect { pos: DVec2 { x: min_x, y: min_y }, size: DVec2 { x: max_x - min_x, y: border } });
-            // Bottom
-            self.draw_node_bg.draw_abs(cx, Rect { pos: DVec2 { x: min_x, y: max_y - border }, size: DVec2 { x: max_x - min_x, y: border } });
-            // Left
-            self.draw_node_bg.draw_abs(cx, Rect { pos: DVec2 { x: min_x, y: min_y }, size: DVec2 { x: border, y: max_y - min_y } });
-            // Right
-            self.draw_node_bg.draw_abs(cx, Rect { pos: DVec2 { x: max_x - border, y: min_y }, size: DVec2 { x: border, y: max_y - min_y } });
+        // Outline of the node a palette entry would drop
+        let drop_preview = self.palette_drop.as_ref().and_then(|(template, center)| {
+            let (width, height) = self.templates.get(template)?.size();
+            Some(Rect {
+                pos: self.canvas_to_screen_pt(DVec2 { x: center.x - width / 2.0, y: center.y - height / 2.0 }),
+                size: DVec2 { x: width * self.zoom, y: height * self.zoom },
+            })
+        });
+        if let Some(rect) = drop_preview {
+            self.draw_selection_box(cx, rect);
         }
 
         // Draw node context menu if open
-        if let Some(node_idx) = self.context_menu_node {
-            if node_idx < self.nodes.len() {
+        if let Some(node_id) = &self.context_menu_node {
+            if self.graph.contains_node(node_id) {
                 self.draw_context_menu(cx, self.context_menu_pos);
             }
         }
 
         // Draw edge context menu if open
         if let Some(edge_idx) = self.context_menu_edge {
-            if edge_idx < self.edges.len() {
+            if edge_idx < self.graph.edge_count() {
                 self.draw_edge_context_menu(cx, self.context_menu_pos, edge_idx);
             }
         }
@@ -1272,214 +1610,50 @@ impl Widget for FlowCanvas {
 }
 
 impl FlowCanvas {
-    /// Draw a bezier curve edge using line segment quads with optional animated flow
-    /// anim_phase < 0 means animation is disabled
-    fn draw_bezier_edge(&mut self, cx: &mut Cx2d, from: DVec2, to: DVec2, selected: bool, thickness: f64, style: f32, anim_phase: f64) {
-        // Get bezier curve points - more segments for smoother curves
-        let points = BezierCurve::points_with_horizontal_tangents(from, to, edge::BEZIER_SEGMENTS);
+    /// Screen-space cubic pieces for an edge between two canvas positions.
+    /// The path is built in canvas space so its shape matches `point_near_edge` at any zoom.
+    fn edge_screen_cubics(&self, path_type: EdgePathType, from: DVec2, to: DVec2) -> Vec<CubicSegment> {
+        self.screen_cubics(path_type.cubics(from, to))
+    }
 
-        // Calculate total curve length for animation
-        let mut total_len = 0.0;
-        for i in 1..points.len() {
-            let dx = points[i].x - points[i - 1].x;
-            let dy = points[i].y - points[i - 1].y;
-            total_len += (dx * dx + dy * dy).sqrt();
-        }
+    fn screen_cubics(&self, cubics: Vec<CubicSegment>) -> Vec<CubicSegment> {
+        cubics.into_iter()
+            .map(|cubic| cubic.map(|p| self.canvas_to_screen_pt(p)))
+            .collect()
+    }
 
+    /// Draw an edge path with the edge shader, one quad per cubic piece.
+    /// anim_phase < 0 means animation is disabled
+    fn draw_edge_path(&mut self, cx: &mut Cx2d, cubics: &[CubicSegment], selected: bool, thickness: f64, style: f32, anim_phase: f64) {
         let animated = anim_phase >= 0.0;
-        let phase = if animated { anim_phase } else { 0.0 };
-
-        let half_thick = thickness * 0.5;
-
-        let mut accumulated_len = 0.0;
-
-        match style as i32 {
-            0 => {
-                // Solid line
-                if animated {
-                    // With animation: dim base line + bright flow particles
-                    let edge_col = if selected { self.edge_selected_color } else { self.edge_color };
-                    let base_color = vec4(edge_col.x * 0.6, edge_col.y * 0.6, edge_col.z * 0.6, 0.5);
-                    let flow_color = vec4(
-                        (edge_col.x * 1.2).min(1.0),
-                        (edge_col.y * 1.2).min(1.0),
-                        (edge_col.z * 1.2).min(1.0),
-                        1.0
-                    );
-
-                    // Draw base line
-                    self.draw_edge.color = base_color;
-                    for i in 1..points.len() {
-                        let prev_pt = points[i - 1];
-                        let pt = points[i];
-                        let dx = pt.x - prev_pt.x;
-                        let dy = pt.y - prev_pt.y;
-                        let seg_len = (dx * dx + dy * dy).sqrt();
-
-                        if seg_len > 0.001 {
-                            let steps = (seg_len / (thickness * 0.3)).max(1.0).ceil() as i32;
-                            for s in 0..=steps {
-                                let t = s as f64 / steps as f64;
-                                let ix = prev_pt.x + dx * t;
-                                let iy = prev_pt.y + dy * t;
-                                self.draw_edge.draw_abs(cx, Rect {
-                                    pos: DVec2 { x: ix - half_thick, y: iy - half_thick },
-                                    size: DVec2 { x: thickness, y: thickness },
-                                });
-                            }
-                        }
-                    }
-
-                    // Draw flow particles
-                    let particle_spacing = 30.0;
-                    let particle_size = thickness * edge::PARTICLE_SIZE_MULTIPLIER;
-                    let half_particle = particle_size * 0.5;
-                    let anim_offset = phase * particle_spacing;
-
-                    self.draw_edge.color = flow_color;
-                    let mut next_particle_at = anim_offset;
-                    accumulated_len = 0.0;
-
-                    for i in 1..points.len() {
-                        let prev_pt = points[i - 1];
-                        let pt = points[i];
-                        let dx = pt.x - prev_pt.x;
-                        let dy = pt.y - prev_pt.y;
-                        let seg_len = (dx * dx + dy * dy).sqrt();
-
-                        while next_particle_at <= accumulated_len + seg_len && next_particle_at <= total_len {
-                            let t = if seg_len > 0.001 {
-                                (next_particle_at - accumulated_len) / seg_len
-                            } else {
-                                0.0
-                            };
-                            let t = t.clamp(0.0, 1.0);
-                            let px = prev_pt.x + dx * t;
-                            let py = prev_pt.y + dy * t;
-
-                            self.draw_edge.draw_abs(cx, Rect {
-                                pos: DVec2 { x: px - half_particle, y: py - half_particle },
-                                size: DVec2 { x: particle_size, y: particle_size },
-                            });
-
-                            next_particle_at += particle_spacing;
-                        }
-
-                        accumulated_len += seg_len;
-                    }
-                } else {
-                    // No animation: just solid bright line
-                    self.draw_edge.color = if selected {
-                        self.edge_selected_color
-                    } else {
-                        self.edge_color
-                    };
-
-                    for i in 1..points.len() {
-                        let prev_pt = points[i - 1];
-                        let pt = points[i];
-                        let dx = pt.x - prev_pt.x;
-                        let dy = pt.y - prev_pt.y;
-                        let seg_len = (dx * dx + dy * dy).sqrt();
-
-                        if seg_len > 0.001 {
-                            let steps = (seg_len / (thickness * 0.3)).max(1.0).ceil() as i32;
-                            for s in 0..=steps {
-                                let t = s as f64 / steps as f64;
-                                let ix = prev_pt.x + dx * t;
-                                let iy = prev_pt.y + dy * t;
-                                self.draw_edge.draw_abs(cx, Rect {
-                                    pos: DVec2 { x: ix - half_thick, y: iy - half_thick },
-                                    size: DVec2 { x: thickness, y: thickness },
-                                });
-                            }
-                        }
-                    }
-                }
-            }
-            1 => {
-                // Dashed line
-                let dash_len = edge::DASH_LENGTH;
-                let dash_gap = edge::DASH_GAP;
-                let cycle = dash_len + dash_gap;
-                let dash_offset = if animated { phase * cycle } else { 0.0 };
-
-                self.draw_edge.color = if selected {
-                    self.edge_selected_color
-                } else {
-                    self.edge_color
-                };
-
-                for i in 1..points.len() {
-                    let prev_pt = points[i - 1];
-                    let pt = points[i];
-                    let dx = pt.x - prev_pt.x;
-                    let dy = pt.y - prev_pt.y;
-                    let seg_len = (dx * dx + dy * dy).sqrt();
-
-                    let pos_in_cycle = (accumulated_len + dash_offset) % cycle;
-
-                    if pos_in_cycle < dash_len && seg_len > 0.001 {
-                        let steps = (seg_len / (thickness * 0.3)).max(1.0).ceil() as i32;
-                        for s in 0..=steps {
-                            let t = s as f64 / steps as f64;
-                            let ix = prev_pt.x + dx * t;
-                            let iy = prev_pt.y + dy * t;
-                            self.draw_edge.draw_abs(cx, Rect {
-                                pos: DVec2 { x: ix - half_thick, y: iy - half_thick },
-                                size: DVec2 { x: thickness, y: thickness },
-                            });
-                        }
-                    }
-
-                    accumulated_len += seg_len;
-                }
-            }
-            2 => {
-                // Dotted line
-                let dot_spacing = edge::DOT_SPACING;
-                let dot_size = thickness * 1.5;
-                let half_dot = dot_size * 0.5;
-                let dot_offset = if animated { phase * dot_spacing } else { 0.0 };
-
-                self.draw_edge.color = if selected {
-                    self.edge_selected_color
-                } else {
-                    self.edge_color
-                };
-
-                let mut next_dot_at = dot_offset;
-
-                for i in 1..points.len() {
-                    let prev_pt = points[i - 1];
-                    let pt = points[i];
-                    let dx = pt.x - prev_pt.x;
-                    let dy = pt.y - prev_pt.y;
-                    let seg_len = (dx * dx + dy * dy).sqrt();
-
-                    while next_dot_at <= accumulated_len + seg_len && next_dot_at <= total_len {
-                        let t = if seg_len > 0.001 {
-                            (next_dot_at - accumulated_len) / seg_len
-                        } else {
-                            0.0
-                        };
-                        let t = t.clamp(0.0, 1.0);
-                        let dot_x = prev_pt.x + dx * t;
-                        let dot_y = prev_pt.y + dy * t;
-
-                        self.draw_edge.draw_abs(cx, Rect {
-                            pos: DVec2 { x: dot_x - half_dot, y: dot_y - half_dot },
-                            size: DVec2 { x: dot_size, y: dot_size },
-                        });
-
-                        next_dot_at += dot_spacing;
-                    }
-
-                    accumulated_len += seg_len;
-                }
-            }
-            _ => {}
-        }
+        let edge_col = if selected { self.edge_selected_color } else { self.edge_color };
+        // Animated solid lines are a dim base line with bright flow particles
+        if animated && style as i32 == 0 {
+            self.draw_edge.color = vec4(edge_col.x * 0.6, edge_col.y * 0.6, edge_col.z * 0.6, 0.5);
+            self.draw_edge.flow_color = vec4(
+                (edge_col.x * 1.2).min(1.0),
+                (edge_col.y * 1.2).min(1.0),
+                (edge_col.z * 1.2).min(1.0),
+                1.0
+            );
+        } else {
+            self.draw_edge.color = edge_col;
+            self.draw_edge.flow_color = edge_col;
+        }
+        self.draw_edge.thickness = thickness as f32;
+        self.draw_edge.style = style;
+        self.draw_edge.phase = if animated { anim_phase as f32 } else { -1.0 };
+        self.draw_edge.dash_length = edge::DASH_LENGTH as f32;
+        self.draw_edge.dash_gap = edge::DASH_GAP as f32;
+        self.draw_edge.dot_spacing = edge::DOT_SPACING as f32;
+        self.draw_edge.dot_radius = (thickness * edge::DOT_SIZE_MULTIPLIER * 0.5) as f32;
+        self.draw_edge.particle_spacing = edge::PARTICLE_SPACING as f32;
+        self.draw_edge.particle_radius = if animated && style as i32 == 0 {
+            (thickness * edge::PARTICLE_SIZE_MULTIPLIER * 0.5) as f32
+        } else {
+            0.0
+        };
+        self.draw_edge.draw_cubics(cx, cubics);
     }
 
     fn initialize(&mut self, cx: &mut Cx) {
@@ -1488,15 +1662,14 @@ impl FlowCanvas {
         self.zoom = self.default_zoom;
         self.pan_offset = DVec2::default();
         self.drag_state = DragState::None;
-        self.next_node_id = 4;
         self.animation_phase = 0.0;
         self.context_menu_node = None;
         self.context_menu_edge = None;
         self.context_menu_pos = DVec2::default();
         self.selected_nodes = HashSet::new();
         self.selected_edges = HashSet::new();
-        self.undo_stack = Vec::new();
-        self.redo_stack = Vec::new();
+        self.history = FlowHistory::default();
+        self.pending_edit = None;
 
         // Start animation timer (~50fps for smooth animation)
         self.animation_timer = cx.start_interval(0.02);
@@ -1509,19 +1682,122 @@ impl FlowCanvas {
 
     fn create_sample_nodes(&mut self) {
         // Create initial nodes
-        self.nodes = vec![
-            FlowNode::new(50.0, 100.0, NodeType::Camera),
-            FlowNode::new(300.0, 50.0, NodeType::Detector),
-            FlowNode::new(300.0, 200.0, NodeType::Tracker),
-            FlowNode::new(550.0, 120.0, NodeType::Visualizer),
-        ];
+        let templates = NodeTemplateRegistry::builtin();
+        let nodes: Vec<FlowNode> = [
+            ("camera", 50.0, 100.0),
+            ("detector", 300.0, 50.0),
+            ("tracker", 300.0, 200.0),
+            ("visualizer", 550.0, 120.0),
+        ].iter()
+            .filter_map(|&(kind, x, y)| templates.get(kind).map(|t| t.instantiate(kind, x, y)))
+            .collect();
 
         // Create initial edges
-        self.edges = vec![
-            EdgeConnection::new(0, 1),
-            EdgeConnection::new(1, 2),
-            EdgeConnection::new(2, 3),
-        ];
+        let edges = nodes.windows(2)
+            .map(|pair| EdgeConnection::new(&pair[0].id, &pair[1].id))
+            .collect();
+        self.graph = FlowGraph::from_parts(nodes, edges);
+    }
+
+    /// Ids of the currently selected nodes, in draw order
+    fn selected_ids(&self) -> Vec<String> {
+        self.graph.nodes().iter()
+            .filter(|n| self.selected_nodes.contains(&n.id))
+            .map(|n| n.id.clone())
+            .collect()
+    }
+
+    /// Round a canvas position to the nearest grid point when snapping is on
+    fn snap_point(&self, p: DVec2) -> DVec2 {
+        if !self.snap_to_grid || self.grid_size <= 0.0 {
+            return p;
+        }
+        DVec2 {
+            x: (p.x / self.grid_size).round() * self.grid_size,
+            y: (p.y / self.grid_size).round() * self.grid_size,
+        }
+    }
+
+    /// Resize handle of a selected node under `pos`, with the node's current rect
+    fn resize_handle_at(&self, pos: DVec2) -> Option<(String, ResizeCorner, Rect)> {
+        // Handles keep their screen size at every zoom level
+        let half = node::RESIZE_HANDLE_SIZE / self.zoom;
+        self.graph.nodes().iter().rev()
+            .filter(|n| self.selected_nodes.contains(&n.id))
+            .find_map(|n| {
+                let rect = n.rect();
+                ResizeCorner::ALL.into_iter()
+                    .find(|c| {
+                        let p = c.point(rect);
+                        (pos.x - p.x).abs() <= half && (pos.y - p.y).abs() <= half
+                    })
+                    .map(|c| (n.id.clone(), c, rect))
+            })
+    }
+
+    /// Move `corner` of a node to `pos`, keeping the opposite corner of `original`
+    /// in place and never shrinking below the node's minimum size
+    fn resize_node_to(&mut self, node_id: &str, corner: ResizeCorner, original: Rect, pos: DVec2) {
+        let Some(min) = self.graph.node(node_id).map(|n| n.min_size()) else { return };
+        let right = original.pos.x + original.size.x;
+        let bottom = original.pos.y + original.size.y;
+        let (x, width) = if corner.moves_left() {
+            let x = pos.x.min(right - min.x).max(0.0);
+            (x, right - x)
+        } else {
+            (original.pos.x, (pos.x - original.pos.x).max(min.x))
+        };
+        let (y, height) = if corner.moves_top() {
+            let y = pos.y.min(bottom - min.y).max(0.0);
+            (y, bottom - y)
+        } else {
+            (original.pos.y, (pos.y - original.pos.y).max(min.y))
+        };
+        self.graph.set_node_rect(node_id, Rect { pos: DVec2 { x, y }, size: DVec2 { x: width, y: height } });
+        self.graph.grow_parents(node_id);
+    }
+
+    /// One quad covering the canvas; the shader draws the pattern
+    fn draw_background_grid(&mut self, cx: &mut Cx2d) {
+        if self.grid_style == GridStyle::None || self.grid_size <= 0.0 {
+            return;
+        }
+        // Merge cells when zoomed out so the pattern does not turn into noise
+        let mut spacing = self.grid_size * self.zoom;
+        while spacing < canvas::GRID_MIN_SCREEN_SPACING {
+            spacing *= 2.0;
+        }
+        let rect = self.view.area().rect(cx);
+        let origin = self.canvas_to_screen_pt(DVec2::default());
+        self.draw_grid.color = self.grid_color;
+        self.draw_grid.spacing = spacing as f32;
+        self.draw_grid.offset = vec2(
+            (origin.x - rect.pos.x).rem_euclid(spacing) as f32,
+            (origin.y - rect.pos.y).rem_euclid(spacing) as f32,
+        );
+        (self.draw_grid.style, self.draw_grid.thickness) = match self.grid_style {
+            GridStyle::Lines => (2.0, canvas::GRID_LINE_WIDTH),
+            _ => (1.0, canvas::GRID_DOT_RADIUS),
+        };
+        self.draw_grid.draw_abs(cx, rect);
+    }
+
+    /// Tinted rect with a 1px border, as used by the selection box
+    fn draw_selection_box(&mut self, cx: &mut Cx2d, rect: Rect) {
+        let Rect { pos, size } = rect;
+        self.draw_node_bg.color = self.theme.selection_box_fill;
+        self.draw_node_bg.draw_abs(cx, rect);
+
+        self.draw_node_bg.color = self.theme.selection_box_border;
+        let border = 1.0;
+        // Top
+        self.draw_node_bg.draw_abs(cx, Rect { pos, size: DVec2 { x: size.x, y: border } });
+        // Bottom
+        self.draw_node_bg.draw_abs(cx, Rect { pos: DVec2 { x: pos.x, y: pos.y + size.y - border }, size: DVec2 { x: size.x, y: border } });
+        // Left
+        self.draw_node_bg.draw_abs(cx, Rect { pos, size: DVec2 { x: border, y: size.y } });
+        // Right
+        self.draw_node_bg.draw_abs(cx, Rect { pos: DVec2 { x: pos.x + size.x - border, y: pos.y }, size: DVec2 { x: border, y: size.y } });
     }
 
     fn screen_to_canvas(&self, screen_pos: DVec2, area_rect: Rect) -> DVec2 {
@@ -1538,35 +1814,141 @@ impl FlowCanvas {
         }
     }
 
-    fn point_near_edge(&self, point: DVec2, edge: &EdgeConnection) -> bool {
-        if edge.from_node >= self.nodes.len() || edge.to_node >= self.nodes.len() {
-            return false;
+    /// The edge that would be created by releasing a drag from `from_node`/`from_port` at `pos`
+    fn edge_target(&self, from_node: &str, from_port: &str, is_output: bool, pos: DVec2) -> Option<EdgeConnection> {
+        for node in self.nodes_near(pos) {
+            // If dragging from output, look for input ports
+            if is_output {
+                if let Some(index) = node.input_port_at(pos) {
+                    return Some(EdgeConnection::new_with_ports(from_node, from_port, &node.id, &node.input_ports[index].id));
+                }
+            }
+            // If dragging from input, look for output ports
+            else if let Some(index) = node.output_port_at(pos) {
+                return Some(EdgeConnection::new_with_ports(&node.id, &node.output_ports[index].id, from_node, from_port));
+            }
         }
-        let from = self.nodes[edge.from_node].output_pos();
-        let to = self.nodes[edge.to_node].input_pos();
-
-        // Simple distance check to bezier (approximate with line segments)
-        let dx = (to.x - from.x) * 0.5;
-        let c0 = DVec2 { x: from.x + dx, y: from.y };
-        let c1 = DVec2 { x: to.x - dx, y: to.y };
+        None
+    }
 
-        for i in 0..32 {
-            let t = i as f64 / 31.0;
-            let t2 = t * t;
-            let t3 = t2 * t;
-            let mt = 1.0 - t;
-            let mt2 = mt * mt;
-            let mt3 = mt2 * mt;
-            let bp = DVec2 {
-                x: mt3 * from.x + 3.0 * mt2 * t * c0.x + 3.0 * mt * t2 * c1.x + t3 * to.x,
-                y: mt3 * from.y + 3.0 * mt2 * t * c0.y + 3.0 * mt * t2 * c1.y + t3 * to.y,
+    /// Edge and end (`true` for the target) whose endpoint is within grabbing distance of `pos`.
+    /// The distance is fixed on screen, so it shrinks in canvas units as the view zooms in.
+    fn edge_end_at(&self, pos: DVec2) -> Option<(usize, bool)> {
+        let radius = (edge::RECONNECT_RADIUS / self.zoom).min(port::HIT_SIZE);
+        for i in self.spatial.edges_at(pos) {
+            let Some(edge) = self.graph.edge(i) else { continue };
+            if !self.point_near_edge(pos, i, edge) {
+                continue;
+            }
+            let (Some(from_node), Some(to_node)) = (self.graph.node(&edge.from_node), self.graph.node(&edge.to_node)) else {
+                continue;
             };
-            let dist = ((point.x - bp.x).powi(2) + (point.y - bp.y).powi(2)).sqrt();
-            if dist < edge::HIT_DISTANCE {
-                return true;
+            let to = to_node.input_anchor(&edge.to_port);
+            if (pos.x - to.x).hypot(pos.y - to.y) <= radius {
+                return Some((i, true));
+            }
+            let from = from_node.output_anchor(&edge.from_port);
+            if (pos.x - from.x).hypot(pos.y - from.y) <= radius {
+                return Some((i, false));
             }
         }
-        false
+        None
+    }
+
+    /// `edge` with its dragged end moved to the port under `pos`, if there is one
+    fn reconnect_target(&self, edge: &EdgeConnection, moving_target: bool, pos: DVec2) -> Option<EdgeConnection> {
+        for node in self.nodes_near(pos) {
+            if moving_target {
+                if let Some(index) = node.input_port_at(pos) {
+                    let mut moved = edge.clone();
+                    moved.to_node = node.id.clone();
+                    moved.to_port = node.input_ports[index].id.clone();
+                    return Some(moved);
+                }
+            } else if let Some(index) = node.output_port_at(pos) {
+                let mut moved = edge.clone();
+                moved.from_node = node.id.clone();
+                moved.from_port = node.output_ports[index].id.clone();
+                return Some(moved);
+            }
+        }
+        None
+    }
+
+    fn validate_connection(&self, edge: &EdgeConnection) -> Result<(), ConnectionRejection> {
+        match &self.validator {
+            Some(validator) => validator.validate(&self.graph, edge),
+            None => DefaultValidator.validate(&self.graph, edge),
+        }
+    }
+
+    /// Bring edge routes up to date with the graph when routing is enabled.
+    /// Returns true if any route changed.
+    fn update_routes(&mut self) -> bool {
+        self.route_edges && self.router.update(&self.graph)
+    }
+
+    /// Update the spatial index, and the routes it is built from, if the graph
+    /// changed since the last update. Hit tests and culling read the index, so
+    /// this runs before them; in between the index may lag one edit behind.
+    fn update_spatial(&mut self) {
+        if self.spatial.is_current(&self.graph) && self.spatial_routed == self.route_edges {
+            return;
+        }
+        let rerouted = self.update_routes();
+        if self.spatial_routed != self.route_edges {
+            self.spatial.invalidate();
+        }
+        let (graph, router, routed) = (&self.graph, &self.router, self.route_edges);
+        self.spatial.update(graph, rerouted, |index, edge| {
+            routed.then(|| router.route(index, edge)).flatten()
+                .and_then(edge_path::bounds)
+                .or_else(|| SpatialIndex::edge_bounds(graph, edge))
+        });
+        self.spatial_routed = routed;
+    }
+
+    /// Nodes whose bounds, including port hit areas, contain `pos`, in draw order
+    fn nodes_near(&self, pos: DVec2) -> impl Iterator<Item = &FlowNode> {
+        self.spatial.nodes_at(pos).into_iter().filter_map(move |i| self.graph.nodes().get(i))
+    }
+
+    /// Topmost node containing `pos`
+    fn node_at(&self, pos: DVec2) -> Option<&FlowNode> {
+        self.spatial.nodes_at(pos).into_iter().rev()
+            .filter_map(|i| self.graph.nodes().get(i))
+            .find(|n| n.contains(pos))
+    }
+
+    /// Canvas-space polyline of the edge at `index`: its route when routing is
+    /// on, otherwise the shape of its path type between its ports
+    fn edge_canvas_points(&self, index: usize, edge: &EdgeConnection) -> Option<Vec<DVec2>> {
+        if self.route_edges {
+            if let Some(route) = self.router.route(index, edge) {
+                return Some(route.to_vec());
+            }
+        }
+        // Port-specific positions, falling back to the first port
+        let from_node = self.graph.node(&edge.from_node)?;
+        let to_node = self.graph.node(&edge.to_node)?;
+        Some(edge.path_type.points(from_node.output_anchor(&edge.from_port), to_node.input_anchor(&edge.to_port)))
+    }
+
+    /// The path of `edge_canvas_points` as cubic pieces for drawing
+    fn edge_canvas_cubics(&self, index: usize, edge: &EdgeConnection) -> Option<Vec<CubicSegment>> {
+        if self.route_edges {
+            if let Some(route) = self.router.route(index, edge) {
+                return Some(edge_path::polyline_cubics(route));
+            }
+        }
+        let from_node = self.graph.node(&edge.from_node)?;
+        let to_node = self.graph.node(&edge.to_node)?;
+        Some(edge.path_type.cubics(from_node.output_anchor(&edge.from_port), to_node.input_anchor(&edge.to_port)))
+    }
+
+    fn point_near_edge(&self, point: DVec2, index: usize, edge: &EdgeConnection) -> bool {
+        self.edge_canvas_points(index, edge)
+            .is_some_and(|points| edge_path::distance_to_path(&points, point) < edge::HIT_DISTANCE)
     }
 
     fn draw_node(&mut self, cx: &mut Cx2d, node: &FlowNode, selected: bool, shape: NodeShape, border_width: f64) {
@@ -1574,27 +1956,27 @@ impl FlowCanvas {
         let size = DVec2 { x: node.width * self.zoom, y: node.height * self.zoom };
         let center = DVec2 { x: pos.x + size.x * 0.5, y: pos.y + size.y * 0.5 };
 
-        // Node background color - light theme (white nodes with colored headers)
-        let (bg_color, header_color) = if node.category == NodeCategory::Default {
-            if selected {
-                // Light blue tint when selected
-                (vec4(0.93, 0.96, 1.0, 1.0), vec4(0.90, 0.93, 0.98, 1.0))
-            } else {
-                // White background, light gray header
-                (vec4(1.0, 1.0, 1.0, 1.0), vec4(0.97, 0.97, 0.97, 1.0))
-            }
-        } else {
+        // Node colors: theme body with the category's header, or the theme's own header
+        let category = self.categories.get(&node.category);
+        let rest_border = category.and_then(|style| style.border_color);
+        let icon = category.and_then(|style| style.icon.clone());
+        let (bg_color, header_color) = if let Some(style) = category {
             // For category nodes, use lighter pastel versions
-            let base_color = node.category.color();
-            let head_color = node.category.header_color();
+            let base_color = style.color;
+            let head_color = style.header_color;
             if selected {
                 // Slightly darker tint when selected
                 (vec4(base_color.x * 0.95 + 0.05, base_color.y * 0.95 + 0.05, base_color.z * 0.95 + 0.05, 1.0),
                  vec4(head_color.x * 0.95, head_color.y * 0.95, head_color.z * 0.95, 1.0))
             } else {
-                // Light pastel body with category header
-                (vec4(1.0, 1.0, 1.0, 1.0), head_color)
+                // Plain body with category header
+                (self.theme.node_body, head_color)
             }
+        } else if selected {
+            // Selection tint
+            (self.theme.node_body_selected, self.theme.node_header_selected)
+        } else {
+            (self.theme.node_body, self.theme.node_header)
         };
         let border_color = self.selection_color;
 
@@ -1604,7 +1986,7 @@ impl FlowCanvas {
                 let corner_r = (node::CORNER_RADIUS * self.zoom) as f32;
                 let header_h = node::HEADER_HEIGHT * self.zoom;
                 let bw = if selected { border_width.max(2.0) as f32 } else { border_width as f32 };
-                let bc = if selected { border_color } else { vec4(0.88, 0.88, 0.88, 1.0) }; // #e0e0e0
+                let bc = if selected { border_color } else { rest_border.unwrap_or(self.theme.node_border) };
                 let inset = bw as f64;
 
                 // 1. Draw body (below header)
@@ -1634,7 +2016,7 @@ impl FlowCanvas {
                 let corner_r = (node::CORNER_RADIUS * self.zoom) as f32;
                 let header_h = node::HEADER_HEIGHT * self.zoom;
                 let bw = if selected { border_width.max(2.0) as f32 } else { border_width as f32 };
-                let bc = if selected { border_color } else { vec4(0.88, 0.88, 0.88, 1.0) }; // #e0e0e0
+                let bc = if selected { border_color } else { rest_border.unwrap_or(self.theme.node_border) };
                 let inset = bw as f64;
 
                 // 1. Draw body with rounded BOTTOM corners only (straight top)
@@ -1669,13 +2051,13 @@ impl FlowCanvas {
                 self.draw_node_bg.color = header_color;
                 self.draw_node_bg.draw_abs(cx, Rect {
                     pos,
-                    size: DVec2 { x: size.x, y: 32.0 * self.zoom }
+                    size: DVec2 { x: size.x, y: node::HEADER_HEIGHT * self.zoom }
                 });
 
                 // Border
                 if selected || border_width > 0.0 {
                     let bw = if selected { border_width.max(2.0) } else { border_width };
-                    self.draw_node_bg.color = if selected { border_color } else { vec4(0.4, 0.4, 0.5, 0.6) };
+                    self.draw_node_bg.color = if selected { border_color } else { rest_border.unwrap_or(self.theme.node_outline) };
                     // Top
                     self.draw_node_bg.draw_abs(cx, Rect { pos, size: DVec2 { x: size.x, y: bw } });
                     // Bottom
@@ -1692,162 +2074,36 @@ impl FlowCanvas {
                     });
                 }
             }
-            NodeShape::Round => {
-                // Draw as circle/ellipse using overlapping squares
-                let radius = size.x.min(size.y) * 0.5;
-                self.draw_node_bg.color = bg_color;
-
-                // Draw filled circle
-                let segments = 36;
-                for i in 0..segments {
-                    let angle = (i as f64 / segments as f64) * std::f64::consts::PI * 2.0;
-                    let next_angle = ((i + 1) as f64 / segments as f64) * std::f64::consts::PI * 2.0;
-
-                    // Draw pie slice as triangular approximation
-                    for r in 0..((radius / 3.0) as i32).max(1) {
-                        let r_ratio = r as f64 / (radius / 3.0);
-                        let inner_r = radius * r_ratio;
-                        let outer_r = radius * (r_ratio + 1.0 / (radius / 3.0)).min(1.0);
-
-                        for a in 0..3 {
-                            let t = a as f64 / 3.0;
-                            let curr_angle = angle + (next_angle - angle) * t;
-                            let px = center.x + curr_angle.cos() * (inner_r + outer_r) * 0.5;
-                            let py = center.y + curr_angle.sin() * (inner_r + outer_r) * 0.5;
-                            self.draw_node_bg.draw_abs(cx, Rect {
-                                pos: DVec2 { x: px - 2.0, y: py - 2.0 },
-                                size: DVec2 { x: 4.0, y: 4.0 },
-                            });
-                        }
-                    }
-                }
-
-                // Draw header portion (top half darker)
-                self.draw_node_bg.color = header_color;
-                for i in 0..(segments / 2) {
-                    let angle = (i as f64 / segments as f64) * std::f64::consts::PI * 2.0 - std::f64::consts::PI * 0.5;
-                    let next_angle = ((i + 1) as f64 / segments as f64) * std::f64::consts::PI * 2.0 - std::f64::consts::PI * 0.5;
-
-                    for r in 0..((radius / 3.0) as i32).max(1) {
-                        let r_ratio = r as f64 / (radius / 3.0);
-                        let inner_r = radius * r_ratio;
-                        let outer_r = radius * (r_ratio + 1.0 / (radius / 3.0)).min(1.0);
-
-                        for a in 0..3 {
-                            let t = a as f64 / 3.0;
-                            let curr_angle = angle + (next_angle - angle) * t;
-                            let px = center.x + curr_angle.cos() * (inner_r + outer_r) * 0.5;
-                            let py = center.y + curr_angle.sin() * (inner_r + outer_r) * 0.5;
-                            if py < center.y {
-                                self.draw_node_bg.draw_abs(cx, Rect {
-                                    pos: DVec2 { x: px - 2.0, y: py - 2.0 },
-                                    size: DVec2 { x: 4.0, y: 4.0 },
-                                });
-                            }
-                        }
-                    }
-                }
-
-                // Border ring
-                if selected || border_width > 0.0 {
-                    let bw = if selected { border_width.max(2.0) } else { border_width };
-                    self.draw_node_bg.color = if selected { border_color } else { vec4(0.4, 0.4, 0.5, 0.6) };
-                    for i in 0..72 {
-                        let angle = (i as f64 / 72.0) * std::f64::consts::PI * 2.0;
-                        let px = center.x + angle.cos() * radius;
-                        let py = center.y + angle.sin() * radius;
-                        self.draw_node_bg.draw_abs(cx, Rect {
-                            pos: DVec2 { x: px - bw * 0.5, y: py - bw * 0.5 },
-                            size: DVec2 { x: bw, y: bw },
-                        });
-                    }
-                }
-            }
-            NodeShape::Diamond => {
-                // Draw diamond shape
-                let half_w = size.x * 0.5;
-                let half_h = size.y * 0.5;
-
-                // Fill diamond with small squares
-                self.draw_node_bg.color = bg_color;
-                let step = 4.0;
-                let mut y = pos.y;
-                while y < pos.y + size.y {
-                    let dy = y - center.y;
-                    let ratio = 1.0 - (dy.abs() / half_h);
-                    let width_at_y = half_w * ratio;
-
-                    let mut x = center.x - width_at_y;
-                    while x < center.x + width_at_y {
-                        self.draw_node_bg.draw_abs(cx, Rect {
-                            pos: DVec2 { x, y },
-                            size: DVec2 { x: step, y: step },
-                        });
-                        x += step;
-                    }
-                    y += step;
-                }
-
-                // Header (top portion)
-                self.draw_node_bg.color = header_color;
-                y = pos.y;
-                while y < center.y - half_h * 0.3 {
-                    let dy = y - center.y;
-                    let ratio = 1.0 - (dy.abs() / half_h);
-                    let width_at_y = half_w * ratio;
-
-                    let mut x = center.x - width_at_y;
-                    while x < center.x + width_at_y {
-                        self.draw_node_bg.draw_abs(cx, Rect {
-                            pos: DVec2 { x, y },
-                            size: DVec2 { x: step, y: step },
-                        });
-                        x += step;
-                    }
-                    y += step;
-                }
-
-                // Diamond border
-                if selected || border_width > 0.0 {
-                    let bw = if selected { border_width.max(2.0) } else { border_width };
-                    self.draw_node_bg.color = if selected { border_color } else { vec4(0.4, 0.4, 0.5, 0.6) };
-
-                    // Draw 4 edges of diamond
-                    let top = DVec2 { x: center.x, y: pos.y };
-                    let right = DVec2 { x: pos.x + size.x, y: center.y };
-                    let bottom = DVec2 { x: center.x, y: pos.y + size.y };
-                    let left = DVec2 { x: pos.x, y: center.y };
-
-                    // Draw each edge
-                    for (start, end) in [(top, right), (right, bottom), (bottom, left), (left, top)] {
-                        let dx = end.x - start.x;
-                        let dy = end.y - start.y;
-                        let len = (dx * dx + dy * dy).sqrt();
-                        let steps = (len / 3.0) as i32;
-                        for s in 0..=steps {
-                            let t = s as f64 / steps as f64;
-                            let px = start.x + dx * t;
-                            let py = start.y + dy * t;
-                            self.draw_node_bg.draw_abs(cx, Rect {
-                                pos: DVec2 { x: px - bw * 0.5, y: py - bw * 0.5 },
-                                size: DVec2 { x: bw, y: bw },
-                            });
-                        }
-                    }
+            NodeShape::Round | NodeShape::Diamond => {
+                self.draw_shape.color = bg_color;
+                self.draw_shape.header_color = header_color;
+                self.draw_shape.border_width = (if selected { border_width.max(2.0) } else { border_width }) as f32;
+                self.draw_shape.border_color = if selected { border_color } else { rest_border.unwrap_or(self.theme.node_outline) };
+                if shape == NodeShape::Round {
+                    // Top half is the header
+                    self.draw_shape.header_bottom = center.y as f32;
+                    self.draw_shape.draw_circle(cx, Rect { pos, size });
+                } else {
+                    self.draw_shape.header_bottom = (center.y - size.y * 0.15) as f32;
+                    self.draw_shape.draw_polygon(cx, &[
+                        DVec2 { x: center.x, y: pos.y },
+                        DVec2 { x: pos.x + size.x, y: center.y },
+                        DVec2 { x: center.x, y: pos.y + size.y },
+                        DVec2 { x: pos.x, y: center.y },
+                    ]);
                 }
             }
         }
 
         // Title text (centered for round/diamond)
         self.draw_text.text_style.font_size = (11.0 * self.zoom) as f32;
-        self.draw_text.color = vec4(0.88, 0.88, 0.88, 1.0);
+        self.draw_text.color = self.theme.node_title;
 
-        // Truncate title to fit within node (max ~20 chars for 180px node)
-        let mut display_title = node.title.clone();
-        let max_chars = 20;
-        if display_title.chars().count() > max_chars {
-            display_title = display_title.chars().take(max_chars - 2).collect::<String>() + "..";
-        }
+        // Truncate title to fit within node (~20 chars for a 180px node), after the category icon
+        let display_title = match &icon {
+            Some(icon) => truncate_label(&format!("{} {}", icon, node.title), (node.width / 9.0) as usize),
+            None => truncate_label(&node.title, (node.width / 9.0) as usize),
+        };
 
         match shape {
             NodeShape::Round | NodeShape::Diamond => {
@@ -1864,66 +2120,60 @@ impl FlowCanvas {
             }
         }
 
+        // Pinned marker in the top-right corner
+        if node.pinned {
+            let marker = 6.0 * self.zoom;
+            self.draw_node_bg.color = self.selection_color;
+            self.draw_node_bg.draw_abs(cx, Rect {
+                pos: DVec2 { x: pos.x + size.x - marker * 2.0, y: pos.y + marker },
+                size: DVec2 { x: marker, y: marker },
+            });
+        }
+
         // Draw ports
         let port_radius = port::RADIUS * self.zoom;
         let port_height = node::PORT_HEIGHT * self.zoom;
         let header_h = node::HEADER_HEIGHT * self.zoom;
 
-        // For Round/Diamond shapes, use legacy single port
+        // Round/Diamond shapes have no room for labels; their ports are drawn at the
+        // same rows the edge anchors and hit tests use
         if matches!(shape, NodeShape::Round | NodeShape::Diamond) {
-            let (input_screen_pos, output_screen_pos) = match shape {
-                NodeShape::Round => {
-                    let radius = size.x.min(size.y) * 0.5;
-                    (
-                        DVec2 { x: center.x - radius, y: center.y },
-                        DVec2 { x: center.x + radius, y: center.y }
-                    )
-                }
-                _ => {
-                    (
-                        DVec2 { x: pos.x, y: center.y },
-                        DVec2 { x: pos.x + size.x, y: center.y }
-                    )
-                }
-            };
-
-            if node.node_type.has_input() {
-                self.draw_node_bg.color = vec4(0.23, 0.51, 0.96, 1.0);
+            self.draw_node_bg.color = self.theme.input_port;
+            for i in 0..node.input_ports.len() {
+                let port_pos = self.canvas_to_screen_pt(node.input_port_pos(i));
                 self.draw_node_bg.draw_abs(cx, Rect {
-                    pos: DVec2 { x: input_screen_pos.x - port_radius, y: input_screen_pos.y - port_radius },
+                    pos: DVec2 { x: port_pos.x - port_radius, y: port_pos.y - port_radius },
                     size: DVec2 { x: port_radius * 2.0, y: port_radius * 2.0 },
                 });
             }
 
-            if node.node_type.has_output() {
-                self.draw_node_bg.color = vec4(0.13, 0.77, 0.37, 1.0);
+            self.draw_node_bg.color = self.theme.output_port;
+            for i in 0..node.output_ports.len() {
+                let port_pos = self.canvas_to_screen_pt(node.output_port_pos(i));
                 self.draw_node_bg.draw_abs(cx, Rect {
-                    pos: DVec2 { x: output_screen_pos.x - port_radius, y: output_screen_pos.y - port_radius },
+                    pos: DVec2 { x: port_pos.x - port_radius, y: port_pos.y - port_radius },
                     size: DVec2 { x: port_radius * 2.0, y: port_radius * 2.0 },
                 });
             }
         } else {
-            // Draw multiple input ports with labels
+            // Draw multiple input ports with labels; each side gets half the node width
             self.draw_text.text_style.font_size = (9.0 * self.zoom) as f32;
-            self.draw_text.color = vec4(0.7, 0.7, 0.8, 1.0);
+            let label_chars = ((node.width / 2.0 - 12.0) / 5.5) as usize;
+            self.draw_text.color = self.theme.port_label;
 
             for (i, port) in node.input_ports.iter().enumerate() {
                 let port_y = pos.y + header_h + (i as f64 * port_height) + port_height / 2.0;
                 let port_x = pos.x;
 
                 // Draw port circle (blue for input)
-                self.draw_node_bg.color = vec4(0.23, 0.51, 0.96, 1.0);
+                self.draw_node_bg.color = self.theme.input_port;
                 self.draw_node_bg.draw_abs(cx, Rect {
                     pos: DVec2 { x: port_x - port_radius, y: port_y - port_radius },
                     size: DVec2 { x: port_radius * 2.0, y: port_radius * 2.0 },
                 });
 
                 // Draw port label
-                let label = if port.label.len() > 12 {
-                    format!("{}...", &port.label[..10])
-                } else {
-                    port.label.clone()
-                };
+                let label = truncate_label(&port.label, label_chars);
                 self.draw_text.draw_abs(cx, DVec2 { x: port_x + port_radius + 4.0, y: port_y - 5.0 }, &label);
             }
 
@@ -1933,23 +2183,113 @@ impl FlowCanvas {
                 let port_x = pos.x + size.x;
 
                 // Draw port circle (green for output)
-                self.draw_node_bg.color = vec4(0.13, 0.77, 0.37, 1.0);
+                self.draw_node_bg.color = self.theme.output_port;
                 self.draw_node_bg.draw_abs(cx, Rect {
                     pos: DVec2 { x: port_x - port_radius, y: port_y - port_radius },
                     size: DVec2 { x: port_radius * 2.0, y: port_radius * 2.0 },
                 });
 
                 // Draw port label (right-aligned)
-                let label = if port.label.len() > 12 {
-                    format!("{}...", &port.label[..10])
-                } else {
-                    port.label.clone()
-                };
+                let label = truncate_label(&port.label, label_chars);
                 let laidout = self.draw_text.layout(cx, 0.0, 0.0, None, false, Align::default(), &label);
                 let text_w = laidout.size_in_lpxs.width as f64;
                 self.draw_text.draw_abs(cx, DVec2 { x: port_x - port_radius - text_w - 4.0, y: port_y - 5.0 }, &label);
             }
         }
+
+        if selected {
+            self.draw_resize_handles(cx, Rect { pos, size });
+        }
+    }
+
+    /// Zoomed out node: a block in the category color, with the title unless
+    /// `detail` is `Blocks`. Titles keep a readable size instead of scaling with zoom.
+    fn draw_node_block(&mut self, cx: &mut Cx2d, node: &FlowNode, selected: bool, detail: DetailLevel) {
+        let pos = self.canvas_to_screen_pt(DVec2 { x: node.x, y: node.y });
+        let size = DVec2 { x: node.width * self.zoom, y: node.height * self.zoom };
+        let fill = self.categories.get(&node.category)
+            .map_or(self.theme.block, |style| style.header_color);
+
+        if detail == DetailLevel::Blocks {
+            self.draw_node_bg.color = if selected { self.selection_color } else { fill };
+            self.draw_node_bg.draw_abs(cx, Rect { pos, size });
+            return;
+        }
+
+        self.draw_rounded_rect.color = fill;
+        self.draw_rounded_rect.radius = (node::CORNER_RADIUS * self.zoom) as f32;
+        self.draw_rounded_rect.border_width = if selected { 2.0 } else { 0.0 };
+        self.draw_rounded_rect.border_color = self.selection_color;
+        self.draw_rounded_rect.draw_abs(cx, Rect { pos, size });
+
+        self.draw_text.text_style.font_size = node::BLOCK_TITLE_FONT_SIZE;
+        self.draw_text.color = self.theme.block_title;
+        let title = truncate_label(&node.title, (size.x / node::BLOCK_TITLE_CHAR_WIDTH) as usize);
+        let laidout = self.draw_text.layout(cx, 0.0, 0.0, None, false, Align::default(), &title);
+        let text_w = laidout.size_in_lpxs.width as f64;
+        let text_h = laidout.size_in_lpxs.height as f64;
+        self.draw_text.draw_abs(cx, DVec2 { x: pos.x + (size.x - text_w) / 2.0, y: pos.y + (size.y - text_h) / 2.0 }, &title);
+
+        if selected {
+            self.draw_resize_handles(cx, Rect { pos, size });
+        }
+    }
+
+    /// How much detail to draw at the current zoom, see `detail_zoom` and `block_zoom`
+    pub fn detail_level(&self) -> DetailLevel {
+        if self.zoom < self.block_zoom {
+            DetailLevel::Blocks
+        } else if self.zoom < self.detail_zoom {
+            DetailLevel::Titles
+        } else {
+            DetailLevel::Full
+        }
+    }
+
+    /// Expanded group: translucent frame behind its children with the title in the header
+    fn draw_frame(&mut self, cx: &mut Cx2d, node: &FlowNode, selected: bool) {
+        let pos = self.canvas_to_screen_pt(DVec2 { x: node.x, y: node.y });
+        let size = DVec2 { x: node.width * self.zoom, y: node.height * self.zoom };
+        let c = self.selection_color;
+
+        self.draw_rounded_rect.color = vec4(c.x, c.y, c.z, 0.06);
+        self.draw_rounded_rect.radius = (node::CORNER_RADIUS * self.zoom) as f32;
+        self.draw_rounded_rect.border_width = if selected { 2.0 } else { 1.0 };
+        self.draw_rounded_rect.border_color = if selected { c } else { vec4(c.x, c.y, c.z, 0.5) };
+        self.draw_rounded_rect.draw_abs(cx, Rect { pos, size });
+
+        if self.detail_level() == DetailLevel::Blocks {
+            return;
+        }
+        self.draw_text.text_style.font_size = (11.0 * self.zoom) as f32;
+        self.draw_text.color = self.theme.group_title;
+        let title = truncate_label(&node.title, (node.width / 9.0) as usize);
+        let laidout = self.draw_text.layout(cx, 0.0, 0.0, None, false, Align::default(), &title);
+        let text_h = laidout.size_in_lpxs.height as f64;
+        let header_h = node::HEADER_HEIGHT * self.zoom;
+        self.draw_text.draw_abs(cx, DVec2 { x: pos.x + 10.0 * self.zoom, y: pos.y + (header_h - text_h) / 2.0 }, &title);
+
+        if selected {
+            self.draw_resize_handles(cx, Rect { pos, size });
+        }
+    }
+
+    /// Square handles on the corners of a selected node's screen rect
+    fn draw_resize_handles(&mut self, cx: &mut Cx2d, rect: Rect) {
+        let handle = node::RESIZE_HANDLE_SIZE;
+        for corner in ResizeCorner::ALL {
+            let p = corner.point(rect);
+            self.draw_node_bg.color = self.selection_color;
+            self.draw_node_bg.draw_abs(cx, Rect {
+                pos: DVec2 { x: p.x - handle / 2.0, y: p.y - handle / 2.0 },
+                size: DVec2 { x: handle, y: handle },
+            });
+            self.draw_node_bg.color = self.theme.handle;
+            self.draw_node_bg.draw_abs(cx, Rect {
+                pos: DVec2 { x: p.x - handle / 2.0 + 1.5, y: p.y - handle / 2.0 + 1.5 },
+                size: DVec2 { x: handle - 3.0, y: handle - 3.0 },
+            });
+        }
     }
 
     /// Draw context menu at screen position
@@ -1959,23 +2299,23 @@ impl FlowCanvas {
         let padding = 4.0;
 
         // Check if this is a multi-selection context menu
-        let node_idx = self.context_menu_node.unwrap_or(0);
-        let is_multi = self.selected_nodes.contains(&node_idx) && self.selected_nodes.len() > 1;
+        let is_multi = self.context_menu_node.as_ref()
+            .is_some_and(|id| self.selected_nodes.contains(id)) && self.selected_nodes.len() > 1;
         let multi_count = if is_multi { self.selected_nodes.len() } else { 1 };
 
         // Add extra space for multi-selection header
-        let num_items = if is_multi { 12 } else { 11 }; // +1 for multi header
-        let menu_height = item_height * num_items as f64 + padding * 2.0;
+        let num_items = if is_multi { 13.5 } else { 12.5 }; // +1 for multi header
+        let menu_height = item_height * num_items + padding * 2.0;
 
-        // Menu background (light theme)
-        self.draw_node_bg.color = vec4(1.0, 1.0, 1.0, 0.98);
+        // Menu background
+        self.draw_node_bg.color = self.theme.menu_background;
         self.draw_node_bg.draw_abs(cx, Rect {
             pos,
             size: DVec2 { x: menu_width, y: menu_height },
         });
 
-        // Border (light theme)
-        self.draw_node_bg.color = vec4(0.88, 0.88, 0.88, 1.0); // #e0e0e0
+        // Border
+        self.draw_node_bg.color = self.theme.menu_border;
         // Top
         self.draw_node_bg.draw_abs(cx, Rect { pos, size: DVec2 { x: menu_width, y: 1.0 } });
         // Bottom
@@ -1993,7 +2333,7 @@ impl FlowCanvas {
 
         let mut y = pos.y + padding;
 
-        // Multi-selection header (light theme)
+        // Multi-selection header
         if is_multi {
             self.draw_text.text_style.font_size = 9.0;
             self.draw_text.color = self.selection_color;
@@ -2001,170 +2341,408 @@ impl FlowCanvas {
             y += item_height;
         }
 
-        // Section label - Shape (light theme)
+        // Section label - Shape
         self.draw_text.text_style.font_size = 9.0;
-        self.draw_text.color = vec4(0.6, 0.6, 0.6, 1.0); // #999999
+        self.draw_text.color = self.theme.menu_heading;
         self.draw_text.draw_abs(cx, DVec2 { x: pos.x + 8.0, y }, "Shape");
         y += item_height * 0.8;
 
-        // Shape items (light theme)
+        // Shape items
         let shape_items = ["Rounded Rect", "Double Rounded", "Rectangle", "Round", "Diamond"];
         self.draw_text.text_style.font_size = 10.0;
-        self.draw_text.color = vec4(0.2, 0.2, 0.2, 1.0); // #333333
+        self.draw_text.color = self.theme.menu_text;
         for label in shape_items {
             self.draw_text.draw_abs(cx, DVec2 { x: pos.x + 12.0, y }, label);
             y += item_height;
         }
 
-        // Divider (light theme)
-        self.draw_node_bg.color = vec4(0.88, 0.88, 0.88, 1.0); // #e0e0e0
+        // Divider
+        self.draw_node_bg.color = self.theme.menu_border;
         self.draw_node_bg.draw_abs(cx, Rect {
             pos: DVec2 { x: pos.x + 8.0, y: y + 2.0 },
             size: DVec2 { x: menu_width - 16.0, y: 1.0 }
         });
         y += item_height * 0.5;
 
-        // Section label - Border (light theme)
+        // Section label - Border
         self.draw_text.text_style.font_size = 9.0;
-        self.draw_text.color = vec4(0.6, 0.6, 0.6, 1.0); // #999999
+        self.draw_text.color = self.theme.menu_heading;
         self.draw_text.draw_abs(cx, DVec2 { x: pos.x + 8.0, y }, "Border");
         y += item_height * 0.8;
 
-        // Border items (light theme)
+        // Border items
         let border_items = ["1px", "2px", "3px", "4px"];
         self.draw_text.text_style.font_size = 10.0;
-        self.draw_text.color = vec4(0.2, 0.2, 0.2, 1.0); // #333333
+        self.draw_text.color = self.theme.menu_text;
         for label in border_items {
             self.draw_text.draw_abs(cx, DVec2 { x: pos.x + 12.0, y }, label);
             y += item_height;
         }
+
+        // Divider
+        self.draw_node_bg.color = self.theme.menu_border;
+        self.draw_node_bg.draw_abs(cx, Rect {
+            pos: DVec2 { x: pos.x + 8.0, y: y + 2.0 },
+            size: DVec2 { x: menu_width - 16.0, y: 1.0 }
+        });
+        y += item_height * 0.5;
+
+        // Pin toggle, labelled for the node the menu was opened on
+        let pinned = self.context_menu_node.as_ref()
+            .and_then(|id| self.graph.node(id))
+            .is_some_and(|n| n.pinned);
+        self.draw_text.draw_abs(cx, DVec2 { x: pos.x + 12.0, y }, if pinned { "Unpin" } else { "Pin" });
     }
 
-    pub fn add_node(&mut self, cx: &mut Cx, node_type: NodeType) {
-        self.save_undo_state();
+    /// Node kinds that can be created on this canvas
+    pub fn templates(&self) -> &NodeTemplateRegistry {
+        &self.templates
+    }
 
-        // Position new node at center of view
-        let x = (-self.pan_offset.x / self.zoom) + 200.0;
-        let y = (-self.pan_offset.y / self.zoom) + 150.0;
+    pub fn set_templates(&mut self, templates: NodeTemplateRegistry) {
+        self.templates = templates;
+    }
 
-        let mut node = FlowNode::new(x, y, node_type);
-        if node_type == NodeType::Custom {
-            node.title = format!("Node {}", self.next_node_id);
-        }
-        self.next_node_id += 1;
+    /// Create a node from the template `template_id` and select it.
+    /// `pos` is the node's top-left corner in canvas coordinates; `None` places it
+    /// near the top-left of the view. Returns `None` for an unknown template.
+    pub fn add_node(&mut self, cx: &mut Cx, template_id: &str, pos: Option<DVec2>) -> Option<String> {
+        let template = self.templates.get(template_id)?;
+        let pos = self.snap_point(pos.unwrap_or(DVec2 {
+            x: (-self.pan_offset.x / self.zoom) + 200.0,
+            y: (-self.pan_offset.y / self.zoom) + 150.0,
+        }));
 
-        self.nodes.push(node);
+        // Named after the template; the graph adds a suffix if the id is taken
+        let node = template.instantiate(template_id, pos.x, pos.y);
+        let node_id = self.edit_graph("Add Node", |graph| graph.add_node(node));
         self.selected_nodes.clear();
-        self.selected_nodes.insert(self.nodes.len() - 1);
+        self.selected_nodes.insert(node_id.clone());
+        self.selection_changed();
         self.view.redraw(cx);
+        Some(node_id)
     }
 
-    fn delete_selected(&mut self, cx: &mut Cx, uid: WidgetUid, scope: &Scope) {
+    /// Remove the selected nodes and edges as one undo step named `label`
+    fn delete_selected(&mut self, cx: &mut Cx, uid: WidgetUid, scope: &Scope, label: &str) {
         if self.selected_edges.is_empty() && self.selected_nodes.is_empty() {
             return;
         }
 
-        self.save_undo_state();
-
-        // Delete selected edges first (simpler - no index updates needed for other edges)
-        if !self.selected_edges.is_empty() {
-            let mut edges_to_remove: Vec<usize> = self.selected_edges.iter().cloned().collect();
-            edges_to_remove.sort_by(|a, b| b.cmp(a)); // Sort descending to remove from end first
-            for idx in edges_to_remove {
-                if idx < self.edges.len() {
-                    self.edges.remove(idx);
-                }
-            }
-            self.selected_edges.clear();
-            cx.widget_action(uid, &scope.path, FlowCanvasAction::EdgeDeleted);
-        }
-
-        // Delete selected nodes
-        if !self.selected_nodes.is_empty() {
-            let mut nodes_to_remove: Vec<usize> = self.selected_nodes.iter().cloned().collect();
-            nodes_to_remove.sort_by(|a, b| b.cmp(a)); // Sort descending
-
-            for node_idx in nodes_to_remove {
-                if node_idx < self.nodes.len() {
-                    // Remove edges connected to this node
-                    self.edges.retain(|e| e.from_node != node_idx && e.to_node != node_idx);
-                    // Update edge indices for remaining edges
-                    for edge in &mut self.edges {
-                        if edge.from_node > node_idx { edge.from_node -= 1; }
-                        if edge.to_node > node_idx { edge.to_node -= 1; }
-                    }
-                    self.nodes.remove(node_idx);
-                }
-            }
-            self.selected_nodes.clear();
-            cx.widget_action(uid, &scope.path, FlowCanvasAction::NodeDeleted);
+        let node_ids = self.selected_ids();
+        let edges = std::mem::take(&mut self.selected_edges);
+        let nodes = std::mem::take(&mut self.selected_nodes);
+        let removed_edges: Vec<EdgeConnection> = self.graph.edges().iter().enumerate()
+            .filter(|(i, e)| edges.contains(i) || nodes.contains(&e.from_node) || nodes.contains(&e.to_node))
+            .map(|(_, e)| e.clone())
+            .collect();
+        self.edit_graph(label, |graph| {
+            // Delete selected edges first, while their indices are still valid
+            graph.remove_edges(&edges);
+            // Then the selected nodes together with their connected edges
+            graph.remove_nodes(&nodes);
+        });
+        if !removed_edges.is_empty() {
+            cx.widget_action(uid, &scope.path, FlowCanvasAction::EdgeDeleted { edges: removed_edges });
+        }
+        if !node_ids.is_empty() {
+            cx.widget_action(uid, &scope.path, FlowCanvasAction::NodeDeleted { node_ids });
         }
+        cx.widget_action(uid, &scope.path, self.selection_action());
 
         self.view.redraw(cx);
     }
 
-    fn save_undo_state(&mut self) {
-        self.undo_stack.push(HistoryEntry {
-            nodes: self.nodes.clone(),
-            edges: self.edges.clone(),
-        });
-        // Limit undo stack size
-        if self.undo_stack.len() > canvas::UNDO_STACK_SIZE {
-            self.undo_stack.remove(0);
+    /// Selected nodes and the edges among them as flow document JSON
+    pub fn copy_selection(&self) -> Option<String> {
+        if self.selected_nodes.is_empty() {
+            return None;
         }
-        // Clear redo stack on new action
-        self.redo_stack.clear();
+        let subgraph = self.graph.subgraph(&self.selected_nodes);
+        FlowDocument::new(&subgraph, FlowViewport::default()).to_json().ok()
     }
 
-    fn undo(&mut self, cx: &mut Cx) {
-        if let Some(state) = self.undo_stack.pop() {
-            // Save current state to redo stack
-            self.redo_stack.push(HistoryEntry {
-                nodes: self.nodes.clone(),
-                edges: self.edges.clone(),
-            });
-            // Restore previous state
-            self.nodes = state.nodes;
-            self.edges = state.edges;
-            self.selected_nodes.clear();
-            self.selected_edges.clear();
-            self.view.redraw(cx);
-        }
+    /// Paste flow document JSON with its top-left corner at the pointer; undoable.
+    /// Returns false for text that is not a non-empty flow document.
+    pub fn paste_document(&mut self, cx: &mut Cx, json: &str) -> bool {
+        let Some(doc) = FlowDocument::from_json(json).ok().filter(|d| !d.nodes.is_empty()) else {
+            return false;
+        };
+        let at = self.snap_point(self.pointer_pos);
+        self.insert_graph(cx, "Paste", doc.into_graph(), at)
     }
 
-    fn redo(&mut self, cx: &mut Cx) {
-        if let Some(state) = self.redo_stack.pop() {
-            // Save current state to undo stack
-            self.undo_stack.push(HistoryEntry {
-                nodes: self.nodes.clone(),
-                edges: self.edges.clone(),
-            });
-            // Restore redo state
-            self.nodes = state.nodes;
-            self.edges = state.edges;
-            self.selected_nodes.clear();
-            self.selected_edges.clear();
-            self.view.redraw(cx);
+    /// Copy the selection next to itself; undoable
+    pub fn duplicate_selected(&mut self, cx: &mut Cx) -> bool {
+        if self.selected_nodes.is_empty() {
+            return false;
         }
+        let subgraph = self.graph.subgraph(&self.selected_nodes);
+        let Some(bounds) = subgraph.bounds() else { return false; };
+        let at = DVec2 { x: bounds.pos.x + canvas::DUPLICATE_OFFSET, y: bounds.pos.y + canvas::DUPLICATE_OFFSET };
+        self.insert_graph(cx, "Duplicate", subgraph, at)
     }
 
-    fn select_all(&mut self, cx: &mut Cx) {
-        self.selected_nodes.clear();
+    /// Add a copied graph with its top-left corner at `at` and select the new nodes
+    fn insert_graph(&mut self, cx: &mut Cx, label: &str, graph: FlowGraph, at: DVec2) -> bool {
+        let Some(bounds) = graph.bounds() else { return false; };
+        let offset = DVec2 { x: at.x - bounds.pos.x, y: at.y - bounds.pos.y };
+        self.selected_nodes = self.edit_graph(label, |g| g.insert_graph(graph, offset)).into_iter().collect();
         self.selected_edges.clear();
-        for i in 0..self.nodes.len() {
-            self.selected_nodes.insert(i);
+        self.selection_changed();
+        self.view.redraw(cx);
+        true
+    }
+
+    /// Put nodes into a new group; undoable. Returns the group id.
+    pub fn group_nodes(&mut self, cx: &mut Cx, title: &str, node_ids: &[String]) -> Option<String> {
+        if !node_ids.iter().any(|id| self.graph.contains_node(id)) {
+            return None;
         }
+        let group_id = self.edit_graph("Group", |graph| graph.group_nodes("group", title, node_ids))?;
+        self.selected_nodes = HashSet::from([group_id.clone()]);
+        self.selected_edges.clear();
+        self.selection_changed();
         self.view.redraw(cx);
+        Some(group_id)
     }
 
-    pub fn fit_view(&mut self, cx: &mut Cx) {
-        if self.nodes.is_empty() { return; }
+    pub fn group_selected(&mut self, cx: &mut Cx, title: &str) -> Option<String> {
+        let ids = self.selected_ids();
+        self.group_nodes(cx, title, &ids)
+    }
 
-        // Find bounding box of all nodes
+    /// Dissolve the selected groups, keeping their children. Returns the dissolved group ids.
+    pub fn ungroup_selected(&mut self, cx: &mut Cx) -> Vec<String> {
+        let groups: Vec<String> = self.selected_ids().into_iter()
+            .filter(|id| self.graph.node(id).is_some_and(|n| n.is_group()))
+            .collect();
+        if groups.is_empty() {
+            return groups;
+        }
+        self.edit_graph("Ungroup", |graph| {
+            for id in &groups {
+                graph.ungroup(id);
+            }
+        });
+        for id in &groups {
+            self.selected_nodes.remove(id);
+        }
+        self.selected_edges.clear();
+        self.selection_changed();
+        self.view.redraw(cx);
+        groups
+    }
+
+    /// Collapse an expanded group or expand a collapsed one; undoable
+    pub fn toggle_group(&mut self, cx: &mut Cx, group_id: &str) -> bool {
+        let Some(collapsed) = self.graph.node(group_id).filter(|n| n.is_group()).map(|n| n.is_collapsed()) else {
+            return false;
+        };
+        if collapsed {
+            self.edit_graph("Expand Group", |graph| graph.expand_group(group_id));
+        } else {
+            self.edit_graph("Collapse Group", |graph| graph.collapse_group(group_id));
+        }
+        // Hidden children drop out of the selection; edge indices have changed
+        self.selected_nodes.retain(|id| self.graph.contains_node(id));
+        self.selected_edges.clear();
+        self.selection_changed();
+        self.view.redraw(cx);
+        true
+    }
+
+    /// Apply `edit` to the graph and record what it changed as one undo step
+    fn edit_graph<R>(&mut self, label: &str, edit: impl FnOnce(&mut FlowGraph) -> R) -> R {
+        // A pending drag or layout is split around the edit so each undoes on its own
+        let resume = self.end_edit();
+        self.graph.begin_recording();
+        let result = edit(&mut self.graph);
+        let commands = self.graph.end_recording();
+        self.record_step(label, commands);
+        if let Some(label) = resume {
+            self.begin_edit(&label);
+        }
+        result
+    }
+
+    /// Start an edit spanning several events (drag, resize, live layout);
+    /// everything up to `end_edit` becomes one undo step
+    fn begin_edit(&mut self, label: &str) {
+        if self.pending_edit.is_none() {
+            self.pending_edit = Some(label.to_string());
+            self.graph.begin_recording();
+        }
+    }
+
+    /// Record the pending edit, if any. Returns its label.
+    fn end_edit(&mut self) -> Option<String> {
+        let label = self.pending_edit.take()?;
+        let commands = self.graph.end_recording();
+        self.record_step(&label, commands);
+        Some(label)
+    }
+
+    /// Record already applied commands as one undo step and report it
+    fn record_step(&mut self, label: &str, commands: Vec<FlowCommand>) {
+        if let Some(entry) = self.history.record_commands(label, commands) {
+            self.queued_actions.push(FlowCanvasAction::GraphChanged {
+                label: entry.label.clone(),
+                commands: entry.commands.clone(),
+            });
+        }
+    }
+
+    /// Send actions raised where no scope was at hand, e.g. from `FlowCanvasRef` calls
+    fn send_queued_actions(&mut self, cx: &mut Cx, uid: WidgetUid, scope: &Scope) {
+        for action in std::mem::take(&mut self.queued_actions) {
+            cx.widget_action(uid, &scope.path, action);
+        }
+    }
+
+    fn selection_action(&self) -> FlowCanvasAction {
+        let mut edges: Vec<usize> = self.selected_edges.iter().copied().collect();
+        edges.sort_unstable();
+        FlowCanvasAction::SelectionChanged { nodes: self.selected_ids(), edges }
+    }
+
+    /// Queue a `SelectionChanged` for a selection changed by an edit or API call
+    /// rather than by the pointer, replacing one that has not been sent yet
+    fn selection_changed(&mut self) {
+        self.queued_actions.retain(|action| !matches!(action, FlowCanvasAction::SelectionChanged { .. }));
+        let action = self.selection_action();
+        self.queued_actions.push(action);
+    }
+
+    /// Queue a `ViewportChanged`, replacing one that has not been sent yet
+    fn viewport_changed(&mut self) {
+        if matches!(self.queued_actions.last(), Some(FlowCanvasAction::ViewportChanged { .. })) {
+            self.queued_actions.pop();
+        }
+        let viewport = self.viewport();
+        self.queued_actions.push(FlowCanvasAction::ViewportChanged { viewport });
+    }
+
+    fn restyle_nodes(&mut self, label: &str, node_ids: &[String], restyle: impl Fn(&mut FlowNode)) {
+        self.edit_graph(label, |graph| {
+            for id in node_ids {
+                graph.update_node(id, &restyle);
+            }
+        });
+    }
+
+    fn restyle_edge(&mut self, label: &str, index: usize, restyle: impl FnOnce(&mut EdgeConnection)) {
+        self.edit_graph(label, |graph| graph.update_edge(index, restyle));
+    }
+
+    /// Start animating a force-directed layout. The whole run is one undo step.
+    pub fn start_simulation(&mut self, cx: &mut Cx, options: ForceLayout) {
+        self.stop_simulation(cx);
+        self.end_edit();
+        self.begin_edit("Force Layout");
+        self.simulation = Some(ForceSimulation::new(options));
+        self.simulation_timer = cx.start_interval(simulation::INTERVAL);
+    }
+
+    pub fn stop_simulation(&mut self, cx: &mut Cx) {
+        cx.stop_timer(self.simulation_timer);
+        self.simulation_timer = Timer::default();
+        if self.simulation.take().is_some() {
+            self.end_edit();
+            // A drag still in progress continues as its own step
+            if let Some(label) = self.drag_label() {
+                self.begin_edit(label);
+            }
+        }
+    }
+
+    /// Remember where the dragged nodes and their children start, for `NodesMoved`
+    fn remember_drag_origin(&mut self) {
+        let mut ids = Vec::new();
+        for id in self.dragged_node_ids() {
+            ids.extend(self.graph.descendants(&id));
+            ids.push(id);
+        }
+        self.drag_origin = ids.into_iter()
+            .filter_map(|id| {
+                let node = self.graph.node(&id)?;
+                let pos = DVec2 { x: node.x, y: node.y };
+                Some((id, pos))
+            })
+            .collect();
+    }
+
+    /// Undo label of the drag in progress, if any
+    fn drag_label(&self) -> Option<&'static str> {
+        match &self.drag_state {
+            DragState::DraggingNode { .. } => Some("Move Node"),
+            DragState::DraggingNodes { .. } => Some("Move Nodes"),
+            DragState::ResizingNode { .. } => Some("Resize Node"),
+            _ => None,
+        }
+    }
+
+    /// Ids of the nodes currently being dragged or resized
+    fn dragged_node_ids(&self) -> HashSet<String> {
+        match &self.drag_state {
+            DragState::DraggingNode { node_id, .. } => HashSet::from([node_id.clone()]),
+            DragState::DraggingNodes { offsets } => offsets.iter().map(|(id, _)| id.clone()).collect(),
+            DragState::ResizingNode { node_id, .. } => HashSet::from([node_id.clone()]),
+            _ => HashSet::new(),
+        }
+    }
+
+    fn undo(&mut self, cx: &mut Cx) {
+        // Record whatever is in progress first so it is what gets undone
+        self.stop_simulation(cx);
+        let resume = self.end_edit();
+        if let Some(entry) = self.history.undo(&mut self.graph) {
+            self.queued_actions.push(FlowCanvasAction::GraphChanged {
+                label: format!("Undo {}", entry.label),
+                commands: entry.inverse_commands(),
+            });
+            self.selected_nodes.clear();
+            self.selected_edges.clear();
+            self.selection_changed();
+            self.view.redraw(cx);
+        }
+        if let Some(label) = resume {
+            self.begin_edit(&label);
+        }
+    }
+
+    fn redo(&mut self, cx: &mut Cx) {
+        self.stop_simulation(cx);
+        let resume = self.end_edit();
+        if let Some(entry) = self.history.redo(&mut self.graph) {
+            self.queued_actions.push(FlowCanvasAction::GraphChanged {
+                label: format!("Redo {}", entry.label),
+                commands: entry.commands.clone(),
+            });
+            self.selected_nodes.clear();
+            self.selected_edges.clear();
+            self.selection_changed();
+            self.view.redraw(cx);
+        }
+        if let Some(label) = resume {
+            self.begin_edit(&label);
+        }
+    }
+
+    fn select_all(&mut self, cx: &mut Cx) {
+        self.selected_edges.clear();
+        self.selected_nodes = self.graph.nodes().iter().map(|n| n.id.clone()).collect();
+        self.view.redraw(cx);
+    }
+
+    pub fn fit_view(&mut self, cx: &mut Cx) {
+        if self.graph.is_empty() { return; }
+
+        // Find bounding box of all nodes
         let mut min_x = f64::MAX;
         let mut min_y = f64::MAX;
 
-        for node in &self.nodes {
+        for node in self.graph.nodes() {
             min_x = min_x.min(node.x);
             min_y = min_y.min(node.y);
         }
@@ -2177,31 +2755,24 @@ impl FlowCanvas {
         // Reset to fit
         self.zoom = 1.0;
         self.pan_offset = DVec2 { x: -min_x, y: -min_y };
+        self.viewport_changed();
         self.view.redraw(cx);
     }
 
     pub fn clear(&mut self, cx: &mut Cx) {
-        self.save_undo_state();
-        self.nodes.clear();
-        self.edges.clear();
+        self.edit_graph("Clear", |graph| graph.clear());
         self.selected_nodes.clear();
         self.selected_edges.clear();
+        self.selection_changed();
         self.view.redraw(cx);
     }
 
     /// Draw arrow marker at edge endpoint
-    fn draw_edge_marker(&mut self, cx: &mut Cx2d, from: DVec2, to: DVec2, selected: bool, thickness: f64, marker: EdgeMarker) {
-        // Calculate direction at endpoint using bezier tangent
-        let dx = to.x - from.x;
-        let control_offset = dx.abs() * 0.5;
-        // Approximate tangent at end point (towards to from control point)
-        let c1 = DVec2 { x: to.x - control_offset, y: to.y };
-        let dir_x = to.x - c1.x;
-        let dir_y = to.y - c1.y;
-        let len = (dir_x * dir_x + dir_y * dir_y).sqrt();
-        if len < 0.001 { return; }
-        let nx = dir_x / len;
-        let ny = dir_y / len;
+    fn draw_edge_marker(&mut self, cx: &mut Cx2d, points: &[DVec2], selected: bool, thickness: f64, marker: EdgeMarker) {
+        // Direction the path arrives at its endpoint
+        let (Some(&to), Some(dir)) = (points.last(), edge_path::end_direction(points)) else { return; };
+        let nx = dir.x;
+        let ny = dir.y;
 
         let arrow_size = thickness * edge::ARROW_SIZE_MULTIPLIER;
 
@@ -2219,113 +2790,169 @@ impl FlowCanvas {
                 let left = DVec2 { x: tip.x - nx * back + px * width, y: tip.y - ny * back + py * width };
                 let right = DVec2 { x: tip.x - nx * back - px * width, y: tip.y - ny * back - py * width };
 
-                self.draw_edge.color = if selected {
-                    self.edge_selected_color
-                } else {
-                    self.edge_color
-                };
-
+                let color = if selected { self.edge_selected_color } else { self.edge_color };
                 if marker == EdgeMarker::ArrowFilled {
-                    // Fill triangle
-                    self.fill_triangle(cx, tip, left, right);
+                    self.draw_shape.color = color;
+                    self.draw_shape.header_bottom = f32::MIN;
+                    self.draw_shape.border_width = 0.0;
+                    self.draw_shape.draw_polygon(cx, &[tip, left, right]);
                 } else {
-                    // Draw outline
-                    self.draw_line(cx, tip, left, thickness);
-                    self.draw_line(cx, tip, right, thickness);
+                    // Outline as a solid path through both barbs
+                    let cubics = edge_path::polyline_cubics(&[left, tip, right]);
+                    self.draw_edge_path(cx, &cubics, selected, thickness, 0.0, -1.0);
                 }
             }
             EdgeMarker::Circle => {
                 let radius = arrow_size;
-                self.draw_edge.color = if selected {
-                    self.edge_selected_color
-                } else {
-                    self.edge_color
-                };
-                // Draw circle at endpoint
-                for i in 0..16 {
-                    let angle = (i as f64 / 16.0) * std::f64::consts::PI * 2.0;
-                    let px = to.x + angle.cos() * radius;
-                    let py = to.y + angle.sin() * radius;
-                    self.draw_edge.draw_abs(cx, Rect {
-                        pos: DVec2 { x: px - thickness * 0.5, y: py - thickness * 0.5 },
-                        size: DVec2 { x: thickness, y: thickness },
-                    });
-                }
+                // Ring in the edge color around a transparent center
+                self.draw_shape.color = vec4(0.0, 0.0, 0.0, 0.0);
+                self.draw_shape.header_bottom = f32::MIN;
+                self.draw_shape.border_color = if selected { self.edge_selected_color } else { self.edge_color };
+                self.draw_shape.border_width = thickness as f32;
+                let half = radius + thickness * 0.5;
+                self.draw_shape.draw_circle(cx, Rect {
+                    pos: DVec2 { x: to.x - half, y: to.y - half },
+                    size: DVec2 { x: half * 2.0, y: half * 2.0 },
+                });
             }
             EdgeMarker::None => {}
         }
     }
 
-    fn fill_triangle(&mut self, cx: &mut Cx2d, p0: DVec2, p1: DVec2, p2: DVec2) {
-        // Simple scanline fill for triangle
-        let min_y = p0.y.min(p1.y).min(p2.y);
-        let max_y = p0.y.max(p1.y).max(p2.y);
-        let min_x = p0.x.min(p1.x).min(p2.x);
-        let max_x = p0.x.max(p1.x).max(p2.x);
+    pub fn node_count(&self) -> usize { self.graph.node_count() }
+    pub fn edge_count(&self) -> usize { self.graph.edge_count() }
 
-        let step = 2.0;
-        let mut y = min_y;
-        while y <= max_y {
-            let mut x = min_x;
-            while x <= max_x {
-                if self.point_in_triangle(DVec2 { x, y }, p0, p1, p2) {
-                    self.draw_edge.draw_abs(cx, Rect {
-                        pos: DVec2 { x, y },
-                        size: DVec2 { x: step, y: step },
-                    });
-                }
-                x += step;
-            }
-            y += step;
-        }
+    /// Load nodes and edges from external source
+    pub fn load_graph(&mut self, cx: &mut Cx, nodes: Vec<FlowNode>, edges: Vec<EdgeConnection>) {
+        self.set_graph(cx, FlowGraph::from_parts(nodes, edges));
+    }
+
+    /// Get reference to nodes
+    pub fn nodes(&self) -> &[FlowNode] {
+        self.graph.nodes()
     }
 
-    fn point_in_triangle(&self, p: DVec2, p0: DVec2, p1: DVec2, p2: DVec2) -> bool {
-        let area = 0.5 * (-p1.y * p2.x + p0.y * (-p1.x + p2.x) + p0.x * (p1.y - p2.y) + p1.x * p2.y);
-        let s = 1.0 / (2.0 * area) * (p0.y * p2.x - p0.x * p2.y + (p2.y - p0.y) * p.x + (p0.x - p2.x) * p.y);
-        let t = 1.0 / (2.0 * area) * (p0.x * p1.y - p0.y * p1.x + (p0.y - p1.y) * p.x + (p1.x - p0.x) * p.y);
-        s >= 0.0 && t >= 0.0 && (s + t) <= 1.0
+    /// Get reference to edges
+    pub fn edges(&self) -> &[EdgeConnection] {
+        self.graph.edges()
     }
 
-    fn draw_line(&mut self, cx: &mut Cx2d, from: DVec2, to: DVec2, thickness: f64) {
-        let dx = to.x - from.x;
-        let dy = to.y - from.y;
-        let len = (dx * dx + dy * dy).sqrt();
-        let steps = (len / (thickness * 0.5)).max(1.0).ceil() as i32;
-        let half = thickness * 0.5;
+    /// Look up a node by its id
+    pub fn node(&self, node_id: &str) -> Option<&FlowNode> {
+        self.graph.node(node_id)
+    }
 
-        for i in 0..=steps {
-            let t = i as f64 / steps as f64;
-            let x = from.x + dx * t;
-            let y = from.y + dy * t;
-            self.draw_edge.draw_abs(cx, Rect {
-                pos: DVec2 { x: x - half, y: y - half },
-                size: DVec2 { x: thickness, y: thickness },
-            });
-        }
+    /// The graph model this canvas displays
+    pub fn graph(&self) -> &FlowGraph {
+        &self.graph
     }
 
-    pub fn node_count(&self) -> usize { self.nodes.len() }
-    pub fn edge_count(&self) -> usize { self.edges.len() }
+    /// Undo/redo steps recorded for this canvas
+    pub fn history(&self) -> &FlowHistory {
+        &self.history
+    }
 
-    /// Load nodes and edges from external source
-    pub fn load_graph(&mut self, cx: &mut Cx, nodes: Vec<FlowNode>, edges: Vec<EdgeConnection>) {
-        self.save_undo_state();
-        self.nodes = nodes;
-        self.edges = edges;
+    /// Styles nodes are drawn with, by category
+    pub fn categories(&self) -> &CategoryRegistry {
+        &self.categories
+    }
+
+    pub fn set_categories(&mut self, cx: &mut Cx, categories: CategoryRegistry) {
+        self.categories = categories;
+        self.view.redraw(cx);
+    }
+
+    /// Colors the canvas draws with
+    pub fn theme(&self) -> &FlowTheme {
+        &self.theme
+    }
+
+    /// Switch colors, including the background, selection, edge and grid
+    /// colors set in the DSL. The graph and viewport are kept.
+    pub fn set_theme(&mut self, cx: &mut Cx, theme: &FlowTheme) {
+        self.theme = theme.clone();
+        self.selection_color = theme.selection;
+        self.edge_color = theme.edge;
+        self.edge_selected_color = theme.edge_selected;
+        self.edge_valid_color = theme.edge_valid;
+        self.edge_invalid_color = theme.edge_invalid;
+        self.grid_color = theme.grid;
+        self.view.apply_over(cx, live! {
+            draw_bg: { color: (theme.background) }
+        });
+        self.view.redraw(cx);
+    }
+
+    /// Replace the displayed graph, keeping the viewport.
+    /// Like `LoadDataflow` this is not undoable and starts a fresh history.
+    pub fn set_graph(&mut self, cx: &mut Cx, graph: FlowGraph) {
+        self.graph.end_recording();
+        self.graph.replace_with(graph);
         self.selected_nodes.clear();
         self.selected_edges.clear();
+        self.selection_changed();
+        self.pending_edit = None;
+        self.history.clear();
         self.view.redraw(cx);
     }
 
-    /// Get reference to nodes
-    pub fn nodes(&self) -> &Vec<FlowNode> {
-        &self.nodes
+    /// Current pan offset and zoom
+    pub fn viewport(&self) -> FlowViewport {
+        FlowViewport { pan_x: self.pan_offset.x, pan_y: self.pan_offset.y, zoom: self.zoom }
     }
 
-    /// Get reference to edges
-    pub fn edges(&self) -> &Vec<EdgeConnection> {
-        &self.edges
+    pub fn set_viewport(&mut self, cx: &mut Cx, viewport: FlowViewport) {
+        self.pan_offset = DVec2 { x: viewport.pan_x, y: viewport.pan_y };
+        self.zoom = viewport.zoom.clamp(canvas::MIN_ZOOM, canvas::MAX_ZOOM);
+        self.viewport_changed();
+        self.view.redraw(cx);
+    }
+
+    /// Part of the canvas (in canvas coordinates) currently on screen
+    pub fn visible_rect(&self, cx: &Cx) -> Rect {
+        let size = self.view.area().rect(cx).size;
+        Rect {
+            pos: DVec2 { x: -self.pan_offset.x / self.zoom, y: -self.pan_offset.y / self.zoom },
+            size: DVec2 { x: size.x / self.zoom, y: size.y / self.zoom },
+        }
+    }
+
+    /// Pan so that `point` (canvas coordinates) is in the middle of the view
+    pub fn center_on(&mut self, cx: &mut Cx, point: DVec2) {
+        let size = self.view.area().rect(cx).size;
+        self.pan_offset = DVec2 {
+            x: size.x / 2.0 - point.x * self.zoom,
+            y: size.y / 2.0 - point.y * self.zoom,
+        };
+        self.viewport_changed();
+        self.view.redraw(cx);
+    }
+
+    /// Increases every time the canvas draws
+    pub fn draw_revision(&self) -> u64 {
+        self.draw_revision
+    }
+
+    /// Snapshot of the graph and viewport for persistence
+    pub fn document(&self) -> FlowDocument {
+        FlowDocument::new(&self.graph, self.viewport())
+    }
+
+    /// Serialize the graph and viewport to a versioned JSON document
+    pub fn save_to_json(&self) -> Result<String, DocumentError> {
+        self.document().to_json()
+    }
+
+    /// Replace the graph and viewport with a saved JSON document
+    pub fn load_from_json(&mut self, cx: &mut Cx, json: &str) -> Result<(), DocumentError> {
+        let document = FlowDocument::from_json(json)?;
+        // Make sure the sample graph created on first event cannot overwrite the loaded one
+        self.initialize(cx);
+        let viewport = document.viewport;
+        // Starts a fresh history, so undo cannot bring back the sample graph
+        self.set_graph(cx, document.into_graph());
+        self.set_viewport(cx, viewport);
+        Ok(())
     }
 
     /// Draw edge context menu at screen position
@@ -2333,27 +2960,27 @@ impl FlowCanvas {
         let menu_width = 120.0;
         let item_height = 22.0;
         let padding = 8.0;
-        // Total: 3 labels + 9 items + 2 dividers + top/bottom padding
-        // = 3*18 + 9*22 + 2*11 + 16 = 54 + 198 + 22 + 16 = 290
-        let menu_height = 300.0;
+        // Total: 4 labels + 13 items + 3 dividers + top/bottom padding
+        // = 4*17.6 + 13*22 + 3*11 + 16 = 70.4 + 286 + 33 + 16 = 405.4,
+        // rounded up to leave some room below the last item
+        let menu_height = 420.0;
 
         // Get current edge properties for highlighting
-        let (current_style, current_width, current_animated) = if edge_idx < self.edges.len() {
-            let edge = &self.edges[edge_idx];
-            (edge.style as i32, edge.width as i32, edge.animated)
+        let (current_style, current_width, current_animated, current_path) = if let Some(edge) = self.graph.edge(edge_idx) {
+            (edge.style as i32, edge.width as i32, edge.animated, edge.path_type)
         } else {
-            (0, 2, true)
+            (0, 2, true, EdgePathType::Bezier)
         };
 
-        // Menu background (light theme)
-        self.draw_node_bg.color = vec4(1.0, 1.0, 1.0, 0.98);
+        // Menu background
+        self.draw_node_bg.color = self.theme.menu_background;
         self.draw_node_bg.draw_abs(cx, Rect {
             pos,
             size: DVec2 { x: menu_width, y: menu_height },
         });
 
-        // Border (light theme)
-        self.draw_node_bg.color = vec4(0.88, 0.88, 0.88, 1.0); // #e0e0e0
+        // Border
+        self.draw_node_bg.color = self.theme.menu_border;
         self.draw_node_bg.draw_abs(cx, Rect { pos, size: DVec2 { x: menu_width, y: 1.0 } });
         self.draw_node_bg.draw_abs(cx, Rect {
             pos: DVec2 { x: pos.x, y: pos.y + menu_height - 1.0 },
@@ -2368,13 +2995,13 @@ impl FlowCanvas {
         let mut y = pos.y + padding;
         let label_height = item_height * 0.8;
 
-        // Section label - Style (light theme)
+        // Section label - Style
         self.draw_text.text_style.font_size = 9.0;
-        self.draw_text.color = vec4(0.6, 0.6, 0.6, 1.0); // #999999
+        self.draw_text.color = self.theme.menu_heading;
         self.draw_text.draw_abs(cx, DVec2 { x: pos.x + 8.0, y }, "Style");
         y += label_height;
 
-        // Style items (light theme)
+        // Style items
         let style_items = ["Solid", "Dashed", "Dotted"];
         for (i, label) in style_items.iter().enumerate() {
             let is_selected = current_style == i as i32;
@@ -2382,28 +3009,28 @@ impl FlowCanvas {
             self.draw_text.color = if is_selected {
                 self.selection_color
             } else {
-                vec4(0.2, 0.2, 0.2, 1.0) // #333333
+                self.theme.menu_text
             };
             let prefix = if is_selected { "> " } else { "  " };
             self.draw_text.draw_abs(cx, DVec2 { x: pos.x + 8.0, y }, &format!("{}{}", prefix, label));
             y += item_height;
         }
 
-        // Divider (light theme)
-        self.draw_node_bg.color = vec4(0.88, 0.88, 0.88, 1.0); // #e0e0e0
+        // Divider
+        self.draw_node_bg.color = self.theme.menu_border;
         self.draw_node_bg.draw_abs(cx, Rect {
             pos: DVec2 { x: pos.x + 8.0, y: y + 2.0 },
             size: DVec2 { x: menu_width - 16.0, y: 1.0 }
         });
         y += item_height * 0.5;
 
-        // Section label - Width (light theme)
+        // Section label - Width
         self.draw_text.text_style.font_size = 9.0;
-        self.draw_text.color = vec4(0.6, 0.6, 0.6, 1.0); // #999999
+        self.draw_text.color = self.theme.menu_heading;
         self.draw_text.draw_abs(cx, DVec2 { x: pos.x + 8.0, y }, "Width");
         y += label_height;
 
-        // Width items (light theme)
+        // Width items
         let width_items = ["1px", "2px", "3px", "4px"];
         for (i, label) in width_items.iter().enumerate() {
             let is_selected = current_width == (i + 1) as i32;
@@ -2411,28 +3038,28 @@ impl FlowCanvas {
             self.draw_text.color = if is_selected {
                 self.selection_color
             } else {
-                vec4(0.2, 0.2, 0.2, 1.0) // #333333
+                self.theme.menu_text
             };
             let prefix = if is_selected { "> " } else { "  " };
             self.draw_text.draw_abs(cx, DVec2 { x: pos.x + 8.0, y }, &format!("{}{}", prefix, label));
             y += item_height;
         }
 
-        // Divider (light theme)
-        self.draw_node_bg.color = vec4(0.88, 0.88, 0.88, 1.0); // #e0e0e0
+        // Divider
+        self.draw_node_bg.color = self.theme.menu_border;
         self.draw_node_bg.draw_abs(cx, Rect {
             pos: DVec2 { x: pos.x + 8.0, y: y + 2.0 },
             size: DVec2 { x: menu_width - 16.0, y: 1.0 }
         });
         y += item_height * 0.5;
 
-        // Section label - Animation (light theme)
+        // Section label - Animation
         self.draw_text.text_style.font_size = 9.0;
-        self.draw_text.color = vec4(0.6, 0.6, 0.6, 1.0); // #999999
+        self.draw_text.color = self.theme.menu_heading;
         self.draw_text.draw_abs(cx, DVec2 { x: pos.x + 8.0, y }, "Animation");
         y += label_height;
 
-        // Animation items (light theme)
+        // Animation items
         let anim_items = ["On", "Off"];
         for (i, label) in anim_items.iter().enumerate() {
             let is_selected = (i == 0) == current_animated;
@@ -2440,40 +3067,100 @@ impl FlowCanvas {
             self.draw_text.color = if is_selected {
                 self.selection_color
             } else {
-                vec4(0.2, 0.2, 0.2, 1.0) // #333333
+                self.theme.menu_text
             };
             let prefix = if is_selected { "> " } else { "  " };
             self.draw_text.draw_abs(cx, DVec2 { x: pos.x + 8.0, y }, &format!("{}{}", prefix, label));
             y += item_height;
         }
+
+        // Divider
+        self.draw_node_bg.color = self.theme.menu_border;
+        self.draw_node_bg.draw_abs(cx, Rect {
+            pos: DVec2 { x: pos.x + 8.0, y: y + 2.0 },
+            size: DVec2 { x: menu_width - 16.0, y: 1.0 }
+        });
+        y += item_height * 0.5;
+
+        // Section label - Path
+        self.draw_text.text_style.font_size = 9.0;
+        self.draw_text.color = self.theme.menu_heading;
+        self.draw_text.draw_abs(cx, DVec2 { x: pos.x + 8.0, y }, "Path");
+        y += label_height;
+
+        // Path type items
+        for path_type in EdgePathType::ALL {
+            let is_selected = path_type == current_path;
+            self.draw_text.text_style.font_size = 10.0;
+            self.draw_text.color = if is_selected {
+                self.selection_color
+            } else {
+                self.theme.menu_text
+            };
+            let prefix = if is_selected { "> " } else { "  " };
+            self.draw_text.draw_abs(cx, DVec2 { x: pos.x + 8.0, y }, &format!("{}{}", prefix, path_type.label()));
+            y += item_height;
+        }
     }
 }
 
 /// Type-safe widget reference for FlowCanvas
 impl FlowCanvasRef {
-    /// Add a new node to the canvas
-    pub fn add_node(&self, cx: &mut Cx, node: FlowNode) {
+    /// Add a new node to the canvas.
+    /// Returns the id the node was stored under, which gets a numeric suffix
+    /// if another node already uses the requested id.
+    pub fn add_node(&self, cx: &mut Cx, mut node: FlowNode) -> Option<String> {
+        let mut inner = self.borrow_mut()?;
+        let pos = inner.snap_point(DVec2 { x: node.x, y: node.y });
+        node.x = pos.x;
+        node.y = pos.y;
+        let node_id = inner.edit_graph("Add Node", |graph| graph.add_node(node));
+        inner.queued_actions.push(FlowCanvasAction::NodeAdded { node_ids: vec![node_id.clone()] });
+        inner.view.redraw(cx);
+        Some(node_id)
+    }
+
+    /// Create a node from a registered template with its top-left corner at
+    /// `pos` in canvas coordinates, see [`NodeTemplateRegistry`]
+    pub fn add_node_from_template(&self, cx: &mut Cx, template_id: &str, pos: DVec2) -> Option<String> {
+        let mut inner = self.borrow_mut()?;
+        let node_id = inner.add_node(cx, template_id, Some(pos))?;
+        inner.queued_actions.push(FlowCanvasAction::NodeAdded { node_ids: vec![node_id.clone()] });
+        Some(node_id)
+    }
+
+    /// Add a node kind, replacing any template with the same id
+    pub fn register_template(&self, template: NodeTemplate) {
         if let Some(mut inner) = self.borrow_mut() {
-            inner.save_undo_state();
-            inner.nodes.push(node);
-            inner.view.redraw(cx);
+            inner.templates.register(template);
         }
     }
 
+    /// Replace the node kinds that can be created
+    pub fn set_templates(&self, templates: NodeTemplateRegistry) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.set_templates(templates);
+        }
+    }
+
+    /// Get a copy of the registered node templates
+    pub fn templates(&self) -> NodeTemplateRegistry {
+        self.borrow().map(|inner| inner.templates.clone()).unwrap_or_default()
+    }
+
     /// Remove a node by ID
     pub fn remove_node(&self, cx: &mut Cx, node_id: &str) -> bool {
         if let Some(mut inner) = self.borrow_mut() {
-            if let Some(idx) = inner.nodes.iter().position(|n| n.id == node_id) {
-                inner.save_undo_state();
-                // Remove connected edges
-                inner.edges.retain(|e| e.from_node != idx && e.to_node != idx);
-                // Update edge indices
-                for edge in &mut inner.edges {
-                    if edge.from_node > idx { edge.from_node -= 1; }
-                    if edge.to_node > idx { edge.to_node -= 1; }
+            if inner.graph.contains_node(node_id) {
+                // Removes connected edges as well
+                let removed = inner.edit_graph("Delete Node", |graph| graph.remove_node(node_id));
+                if let Some((_, edges)) = removed.filter(|(_, edges)| !edges.is_empty()) {
+                    inner.queued_actions.push(FlowCanvasAction::EdgeDeleted { edges });
                 }
-                inner.nodes.remove(idx);
-                inner.selected_nodes.clear();
+                inner.queued_actions.push(FlowCanvasAction::NodeDeleted { node_ids: vec![node_id.to_string()] });
+                inner.selected_nodes.remove(node_id);
+                inner.selected_edges.clear();
+                inner.selection_changed();
                 inner.view.redraw(cx);
                 return true;
             }
@@ -2484,21 +3171,230 @@ impl FlowCanvasRef {
     /// Add an edge between nodes
     pub fn add_edge(&self, cx: &mut Cx, edge: EdgeConnection) {
         if let Some(mut inner) = self.borrow_mut() {
-            inner.save_undo_state();
-            inner.edges.push(edge);
+            if let Some(index) = inner.edit_graph("Connect", |graph| graph.connect(edge.clone())) {
+                inner.queued_actions.push(FlowCanvasAction::EdgeCreated { index, edge });
+            }
+            inner.view.redraw(cx);
+        }
+    }
+
+    /// Change the line shape of an edge. Returns false if the index is out of range.
+    pub fn set_edge_path_type(&self, cx: &mut Cx, edge_index: usize, path_type: EdgePathType) -> bool {
+        let Some(mut inner) = self.borrow_mut() else { return false; };
+        if inner.graph.edge(edge_index).is_none() {
+            return false;
+        }
+        inner.restyle_edge("Change Edge Path", edge_index, |edge| edge.path_type = path_type);
+        inner.view.redraw(cx);
+        true
+    }
+
+    /// Change the background pattern
+    pub fn set_grid_style(&self, cx: &mut Cx, style: GridStyle) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.grid_style = style;
             inner.view.redraw(cx);
         }
     }
 
+    /// Turn snapping of dragged and added nodes to the grid on or off
+    pub fn set_snap_to_grid(&self, snap: bool) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.snap_to_grid = snap;
+        }
+    }
+
+    pub fn snap_to_grid(&self) -> bool {
+        self.borrow().is_some_and(|inner| inner.snap_to_grid)
+    }
+
+    /// Zoom levels below which nodes are drawn as titled blocks and as plain blocks
+    pub fn set_detail_zoom(&self, cx: &mut Cx, detail_zoom: f64, block_zoom: f64) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.detail_zoom = detail_zoom;
+            inner.block_zoom = block_zoom;
+            inner.view.redraw(cx);
+        }
+    }
+
+    /// Detail drawn at the current zoom
+    pub fn detail_level(&self) -> DetailLevel {
+        self.borrow().map(|inner| inner.detail_level()).unwrap_or(DetailLevel::Full)
+    }
+
+    /// Route edges orthogonally around nodes instead of using their path types
+    pub fn set_edge_routing(&self, cx: &mut Cx, enabled: bool) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.route_edges = enabled;
+            if !enabled {
+                inner.router.invalidate();
+            }
+            inner.view.redraw(cx);
+        }
+    }
+
+    /// Reposition all nodes with a layered layout. Undoable.
+    pub fn auto_layout(&self, cx: &mut Cx, layout: &LayeredLayout) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.stop_simulation(cx);
+            inner.edit_graph("Auto Layout", |graph| layout.apply(graph));
+            inner.view.redraw(cx);
+        }
+    }
+
+    /// Reposition unpinned nodes with a force-directed layout in one go. Undoable.
+    pub fn force_layout(&self, cx: &mut Cx, layout: &ForceLayout) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.stop_simulation(cx);
+            inner.edit_graph("Force Layout", |graph| layout.apply(graph));
+            inner.view.redraw(cx);
+        }
+    }
+
+    /// Animate a force-directed layout until it settles (`FlowCanvasAction::LayoutSettled`).
+    /// Nodes can be dragged while it runs.
+    pub fn start_force_simulation(&self, cx: &mut Cx, layout: ForceLayout) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.start_simulation(cx, layout);
+        }
+    }
+
+    /// Stop the live layout, leaving nodes where they are
+    pub fn stop_force_simulation(&self, cx: &mut Cx) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.stop_simulation(cx);
+        }
+    }
+
+    pub fn is_simulating(&self) -> bool {
+        self.borrow().is_some_and(|inner| inner.simulation.is_some())
+    }
+
+    /// Pin or unpin a node so the force-directed layout leaves it in place
+    pub fn set_node_pinned(&self, cx: &mut Cx, node_id: &str, pinned: bool) -> bool {
+        let Some(mut inner) = self.borrow_mut() else { return false; };
+        if !inner.graph.contains_node(node_id) {
+            return false;
+        }
+        inner.restyle_nodes(if pinned { "Pin" } else { "Unpin" }, &[node_id.to_string()], |node| node.pinned = pinned);
+        inner.view.redraw(cx);
+        true
+    }
+
+    /// Selected nodes and the edges among them as flow document JSON, the
+    /// format Ctrl+C puts on the clipboard
+    pub fn copy_selection(&self) -> Option<String> {
+        self.borrow()?.copy_selection()
+    }
+
+    /// Paste flow document JSON at the last pointer position; undoable
+    pub fn paste(&self, cx: &mut Cx, json: &str) -> bool {
+        self.borrow_mut().is_some_and(|mut inner| inner.paste_document(cx, json))
+    }
+
+    /// Duplicate the selection next to itself; undoable
+    pub fn duplicate_selection(&self, cx: &mut Cx) -> bool {
+        self.borrow_mut().is_some_and(|mut inner| inner.duplicate_selected(cx))
+    }
+
+    /// Put nodes into a new group whose frame encloses them; undoable
+    pub fn group_nodes(&self, cx: &mut Cx, title: &str, node_ids: &[String]) -> Option<String> {
+        self.borrow_mut()?.group_nodes(cx, title, node_ids)
+    }
+
+    /// Group the selected nodes; undoable
+    pub fn group_selected(&self, cx: &mut Cx, title: &str) -> Option<String> {
+        self.borrow_mut()?.group_selected(cx, title)
+    }
+
+    /// Dissolve a group, keeping its children; undoable
+    pub fn ungroup(&self, cx: &mut Cx, group_id: &str) -> bool {
+        let Some(mut inner) = self.borrow_mut() else { return false; };
+        if !inner.graph.node(group_id).is_some_and(|n| n.is_group()) {
+            return false;
+        }
+        inner.edit_graph("Ungroup", |graph| graph.ungroup(group_id));
+        inner.selected_nodes.remove(group_id);
+        inner.selected_edges.clear();
+        inner.selection_changed();
+        inner.view.redraw(cx);
+        true
+    }
+
+    /// Collapse or expand a group; undoable. Returns false if nothing changed.
+    pub fn set_group_collapsed(&self, cx: &mut Cx, group_id: &str, collapsed: bool) -> bool {
+        let Some(mut inner) = self.borrow_mut() else { return false; };
+        let is_collapsed = inner.graph.node(group_id).filter(|n| n.is_group()).map(|n| n.is_collapsed());
+        if is_collapsed != Some(!collapsed) {
+            return false;
+        }
+        inner.toggle_group(cx, group_id)
+    }
+
+    /// Resize a node, clamped to its minimum size; undoable
+    pub fn resize_node(&self, cx: &mut Cx, node_id: &str, width: f64, height: f64) -> bool {
+        let Some(mut inner) = self.borrow_mut() else { return false; };
+        let Some(node) = inner.graph.node(node_id) else { return false; };
+        let min = node.min_size();
+        let mut rect = node.rect();
+        rect.size = DVec2 { x: width.max(min.x), y: height.max(min.y) };
+        inner.edit_graph("Resize Node", |graph| graph.set_node_rect(node_id, rect));
+        inner.view.redraw(cx);
+        true
+    }
+
+    /// Replace the rules used to accept interactively created edges
+    pub fn set_connection_validator(&self, validator: impl ConnectionValidator + 'static) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.validator = Some(Box::new(validator));
+        }
+    }
+
+    /// Replace the styles nodes are drawn with, by category
+    pub fn set_categories(&self, cx: &mut Cx, categories: CategoryRegistry) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.set_categories(cx, categories);
+        }
+    }
+
+    /// Add or replace the categories of a JSON theme file, see [`CategoryRegistry`]
+    pub fn load_categories(&self, cx: &mut Cx, json: &str) -> Result<(), CategoryError> {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.categories.load_json(json)?;
+            inner.view.redraw(cx);
+        }
+        Ok(())
+    }
+
+    /// Get a copy of the category styles
+    pub fn categories(&self) -> CategoryRegistry {
+        self.borrow().map(|inner| inner.categories.clone()).unwrap_or_default()
+    }
+
+    /// Switch colors at runtime, see [`FlowTheme`]
+    pub fn set_theme(&self, cx: &mut Cx, theme: &FlowTheme) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.set_theme(cx, theme);
+        }
+    }
+
+    /// Get a copy of the current colors
+    pub fn theme(&self) -> FlowTheme {
+        self.borrow().map(|inner| inner.theme.clone()).unwrap_or_default()
+    }
+
+    /// Check an edge against the canvas's validator without adding it
+    pub fn can_connect(&self, edge: &EdgeConnection) -> Result<(), ConnectionRejection> {
+        match self.borrow() {
+            Some(inner) => inner.validate_connection(edge),
+            None => Err(ConnectionRejection::Custom("canvas is not available".to_string())),
+        }
+    }
+
     /// Load a complete graph (nodes and edges)
     pub fn load_graph(&self, cx: &mut Cx, nodes: Vec<FlowNode>, edges: Vec<EdgeConnection>) {
         if let Some(mut inner) = self.borrow_mut() {
-            inner.save_undo_state();
-            inner.nodes = nodes;
-            inner.edges = edges;
-            inner.selected_nodes.clear();
-            inner.selected_edges.clear();
-            inner.view.redraw(cx);
+            inner.load_graph(cx, nodes, edges);
         }
     }
 
@@ -2518,18 +3414,18 @@ impl FlowCanvasRef {
 
     /// Get the number of nodes
     pub fn node_count(&self) -> usize {
-        self.borrow().map(|inner| inner.nodes.len()).unwrap_or(0)
+        self.borrow().map(|inner| inner.graph.node_count()).unwrap_or(0)
     }
 
     /// Get the number of edges
     pub fn edge_count(&self) -> usize {
-        self.borrow().map(|inner| inner.edges.len()).unwrap_or(0)
+        self.borrow().map(|inner| inner.graph.edge_count()).unwrap_or(0)
     }
 
-    /// Get selected node indices
-    pub fn selected_nodes(&self) -> Vec<usize> {
+    /// Get the ids of the selected nodes
+    pub fn selected_nodes(&self) -> Vec<String> {
         self.borrow()
-            .map(|inner| inner.selected_nodes.iter().cloned().collect())
+            .map(|inner| inner.selected_ids())
             .unwrap_or_default()
     }
 
@@ -2540,22 +3436,81 @@ impl FlowCanvasRef {
             .unwrap_or_default()
     }
 
-    /// Select a node by index
-    pub fn select_node(&self, cx: &mut Cx, index: usize) {
+    /// Select a node by id
+    pub fn select_node(&self, cx: &mut Cx, node_id: &str) {
         if let Some(mut inner) = self.borrow_mut() {
-            if index < inner.nodes.len() {
+            if inner.graph.contains_node(node_id) {
                 inner.selected_nodes.clear();
-                inner.selected_nodes.insert(index);
+                inner.selected_nodes.insert(node_id.to_string());
+                inner.selection_changed();
                 inner.view.redraw(cx);
             }
         }
     }
 
+    /// Get a copy of a node by id
+    pub fn node(&self, node_id: &str) -> Option<FlowNode> {
+        self.borrow().and_then(|inner| inner.graph.node(node_id).cloned())
+    }
+
+    /// Get a copy of the whole graph model
+    pub fn graph(&self) -> FlowGraph {
+        self.borrow().map(|inner| inner.graph.clone()).unwrap_or_default()
+    }
+
+    /// Replace the displayed graph, keeping the viewport. Starts a fresh undo history.
+    pub fn set_graph(&self, cx: &mut Cx, graph: FlowGraph) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.set_graph(cx, graph);
+        }
+    }
+
+    /// Get the current pan offset and zoom
+    pub fn viewport(&self) -> FlowViewport {
+        self.borrow().map(|inner| inner.viewport()).unwrap_or_default()
+    }
+
+    /// Set pan offset and zoom
+    pub fn set_viewport(&self, cx: &mut Cx, viewport: FlowViewport) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.set_viewport(cx, viewport);
+        }
+    }
+
+    /// Part of the canvas (in canvas coordinates) currently on screen
+    pub fn visible_rect(&self, cx: &Cx) -> Rect {
+        self.borrow().map(|inner| inner.visible_rect(cx)).unwrap_or_default()
+    }
+
+    /// Pan so that `point` (canvas coordinates) is in the middle of the view
+    pub fn center_on(&self, cx: &mut Cx, point: DVec2) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.center_on(cx, point);
+        }
+    }
+
+    /// Serialize the graph and viewport to a versioned JSON document
+    pub fn save_to_json(&self) -> Result<String, DocumentError> {
+        match self.borrow() {
+            Some(inner) => inner.save_to_json(),
+            None => FlowDocument::new(&FlowGraph::new(), FlowViewport::default()).to_json(),
+        }
+    }
+
+    /// Replace the graph and viewport with a saved JSON document
+    pub fn load_from_json(&self, cx: &mut Cx, json: &str) -> Result<(), DocumentError> {
+        match self.borrow_mut() {
+            Some(mut inner) => inner.load_from_json(cx, json),
+            None => Ok(()),
+        }
+    }
+
     /// Clear selection
     pub fn clear_selection(&self, cx: &mut Cx) {
         if let Some(mut inner) = self.borrow_mut() {
             inner.selected_nodes.clear();
             inner.selected_edges.clear();
+            inner.selection_changed();
             inner.view.redraw(cx);
         }
     }
@@ -2564,6 +3519,7 @@ impl FlowCanvasRef {
     pub fn set_zoom(&self, cx: &mut Cx, zoom: f64) {
         if let Some(mut inner) = self.borrow_mut() {
             inner.zoom = zoom.clamp(canvas::MIN_ZOOM, canvas::MAX_ZOOM);
+            inner.viewport_changed();
             inner.view.redraw(cx);
         }
     }
@@ -2586,4 +3542,23 @@ impl FlowCanvasRef {
             inner.redo(cx);
         }
     }
+
+    /// Label of the step `undo` would revert, e.g. "Move Node"
+    pub fn undo_label(&self) -> Option<String> {
+        self.borrow()?.history.undo_label().map(str::to_string)
+    }
+
+    /// Label of the step `redo` would reapply
+    pub fn redo_label(&self) -> Option<String> {
+        self.borrow()?.history.redo_label().map(str::to_string)
+    }
+}
+
+// Shorten `text` to at most `max_chars` characters, marking the cut with ".."
+fn truncate_label(text: &str, max_chars: usize) -> String {
+    let max_chars = max_chars.max(4);
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    text.chars().take(max_chars - 2).collect::<String>() + ".."
 }
@@ -0,0 +1,1593 @@
+//! Headless graph model
+//!
+//! Node, port and edge types plus [`FlowGraph`], which owns a set of nodes and
+//! edges and the mutation operations on them. Nothing in this module needs a
+//! running `Cx`, so it can back CLIs, servers and tests as well as `FlowCanvas`.
+
+use makepad_widgets::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::constants::{node, port, edge, group};
+use crate::history::FlowCommand;
+
+/// Free-form key/value data carried by nodes, ports and edges.
+/// The canvas ignores it; importers such as the dora module use it to keep
+/// fields they have no dedicated struct member for, so they can be written back.
+pub type Metadata = BTreeMap<String, serde_json::Value>;
+
+// Node shape types
+#[derive(Clone, Copy, PartialEq, Default, Debug, Serialize, Deserialize)]
+pub enum NodeShape {
+    #[default]
+    RoundedRect,
+    DoubleRoundedRect,
+    Rectangle,
+    Round,
+    Diamond,
+}
+
+/// Name of the category a node belongs to. Colors and icons for each name
+/// come from a [`CategoryRegistry`](crate::category::CategoryRegistry).
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NodeCategory(String);
+
+impl NodeCategory {
+    /// Name of the category nodes get when none is set
+    pub const DEFAULT: &'static str = "Default";
+
+    pub fn new(name: &str) -> Self {
+        Self(name.to_string())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.0 == Self::DEFAULT
+    }
+}
+
+impl Default for NodeCategory {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT)
+    }
+}
+
+impl From<&str> for NodeCategory {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl std::fmt::Display for NodeCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// Port definition
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Port {
+    pub id: String,
+    pub label: String,
+    /// Data type carried by the port; `None` connects to anything
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_type: Option<String>,
+    /// Maximum number of edges on this port; `None` means unlimited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_connections: Option<usize>,
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    pub metadata: Metadata,
+}
+
+impl Port {
+    pub fn new(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            label: id.to_string(),
+            data_type: None,
+            max_connections: None,
+            metadata: Metadata::new(),
+        }
+    }
+
+    pub fn with_type(mut self, data_type: &str) -> Self {
+        self.data_type = Some(data_type.to_string());
+        self
+    }
+
+    pub fn with_max_connections(mut self, max: usize) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+}
+
+// Node data
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FlowNode {
+    pub id: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    /// Id of the [`NodeTemplate`](crate::template::NodeTemplate) the node was created from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    pub title: String,
+    pub shape: NodeShape,
+    pub border_width: f32,
+    pub category: NodeCategory,
+    pub input_ports: Vec<Port>,
+    pub output_ports: Vec<Port>,
+    /// Kept in place by the force-directed layout
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Id of the group node this node belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// Set on group nodes, see [`FlowGraph::group_nodes`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<GroupState>,
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    pub metadata: Metadata,
+}
+
+/// State of a group node.
+///
+/// An expanded group is a frame drawn behind its children, which stay ordinary
+/// nodes of the graph. Collapsing moves the children and the edges among them
+/// in here; edges crossing the frame are rewired to ports of the group labelled
+/// `child/port`, so the graph keeps every connection while the group is closed.
+/// `proxy_ports` records which child port each group port stands for.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GroupState {
+    pub collapsed: bool,
+    /// Frame size to restore on expand
+    pub expanded_width: f64,
+    pub expanded_height: f64,
+    /// Children while collapsed, positioned relative to the group's top-left corner
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hidden_nodes: Vec<FlowNode>,
+    /// Edges between children while collapsed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hidden_edges: Vec<EdgeConnection>,
+    /// Ports of the collapsed group and the child ports they stand for
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub proxy_ports: Vec<ProxyPort>,
+}
+
+/// A port of a collapsed group standing in for a port of one of its children
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProxyPort {
+    /// Id of the port on the group
+    pub port: String,
+    /// Whether it is one of the group's output ports
+    pub output: bool,
+    pub node: String,
+    pub node_port: String,
+}
+
+impl GroupState {
+    // Whether a hidden node, at any depth, has id or parent `id`, or a hidden edge ends at it
+    fn mentions(&self, id: &str) -> bool {
+        self.hidden_edges.iter().any(|e| e.touches(id))
+            || self.proxy_ports.iter().any(|p| p.node == id)
+            || self.hidden_nodes.iter().any(|n| {
+                n.id == id || n.parent.as_deref() == Some(id) || n.group.as_ref().is_some_and(|g| g.mentions(id))
+            })
+    }
+
+    // Replace the node id `old` with `new` in hidden nodes and edges, at any depth
+    fn rename(&mut self, old: &str, new: &str) {
+        for proxy in &mut self.proxy_ports {
+            if proxy.node == old { proxy.node = new.to_string(); }
+        }
+        for edge in &mut self.hidden_edges {
+            if edge.from_node == old { edge.from_node = new.to_string(); }
+            if edge.to_node == old { edge.to_node = new.to_string(); }
+        }
+        for node in &mut self.hidden_nodes {
+            if node.id == old { node.id = new.to_string(); }
+            if node.parent.as_deref() == Some(old) { node.parent = Some(new.to_string()); }
+            if let Some(group) = &mut node.group {
+                group.rename(old, new);
+            }
+        }
+    }
+}
+
+impl FlowNode {
+    pub fn new_dataflow(id: &str, x: f64, y: f64, title: &str, category: NodeCategory, inputs: Vec<Port>, outputs: Vec<Port>) -> Self {
+        let height = Self::height_for_ports(inputs.len().max(outputs.len()));
+
+        Self {
+            id: id.to_string(),
+            x, y,
+            width: node::WIDTH,
+            height,
+            template: None,
+            title: title.to_string(),
+            shape: NodeShape::DoubleRoundedRect,
+            border_width: node::BORDER_WIDTH,
+            category,
+            input_ports: inputs,
+            output_ports: outputs,
+            pinned: false,
+            parent: None,
+            group: None,
+            metadata: Metadata::new(),
+        }
+    }
+
+    /// Height of a dataflow node with `port_rows` rows of ports
+    pub fn height_for_ports(port_rows: usize) -> f64 {
+        (node::HEADER_HEIGHT + (port_rows as f64 * node::PORT_HEIGHT) + node::BODY_PADDING).max(node::MIN_HEIGHT)
+    }
+
+    pub fn is_group(&self) -> bool {
+        self.group.is_some()
+    }
+
+    pub fn is_collapsed(&self) -> bool {
+        self.group.as_ref().is_some_and(|g| g.collapsed)
+    }
+
+    /// Expanded group: drawn as a frame around its children, without ports
+    pub fn is_frame(&self) -> bool {
+        self.group.as_ref().is_some_and(|g| !g.collapsed)
+    }
+
+    pub fn rect(&self) -> Rect {
+        Rect { pos: DVec2 { x: self.x, y: self.y }, size: DVec2 { x: self.width, y: self.height } }
+    }
+
+    pub fn set_rect(&mut self, rect: Rect) {
+        self.x = rect.pos.x;
+        self.y = rect.pos.y;
+        self.width = rect.size.x;
+        self.height = rect.size.y;
+    }
+
+    // Smallest size that still fits the header and every port row
+    pub fn min_size(&self) -> DVec2 {
+        let port_rows = self.input_ports.len().max(self.output_ports.len()).max(1);
+        DVec2 {
+            x: node::MIN_WIDTH,
+            y: node::HEADER_HEIGHT + (port_rows as f64 * node::PORT_HEIGHT) + node::BODY_PADDING,
+        }
+    }
+
+    pub fn contains(&self, pos: DVec2) -> bool {
+        pos.x >= self.x && pos.x <= self.x + self.width &&
+        pos.y >= self.y && pos.y <= self.y + self.height
+    }
+
+    // Get position for a specific input port by index
+    pub fn input_port_pos(&self, index: usize) -> DVec2 {
+        let y = self.y + node::HEADER_HEIGHT + (index as f64 * node::PORT_HEIGHT) + node::PORT_HEIGHT / 2.0;
+        DVec2 { x: self.x, y }
+    }
+
+    // Get position for a specific output port by index
+    pub fn output_port_pos(&self, index: usize) -> DVec2 {
+        let y = self.y + node::HEADER_HEIGHT + (index as f64 * node::PORT_HEIGHT) + node::PORT_HEIGHT / 2.0;
+        DVec2 { x: self.x + self.width, y }
+    }
+
+    // Legacy: first output port position
+    pub fn output_pos(&self) -> DVec2 {
+        if self.output_ports.is_empty() {
+            DVec2 { x: self.x + self.width, y: self.y + node::LEGACY_PORT_Y_OFFSET }
+        } else {
+            self.output_port_pos(0)
+        }
+    }
+
+    // Legacy: first input port position
+    pub fn input_pos(&self) -> DVec2 {
+        if self.input_ports.is_empty() {
+            DVec2 { x: self.x, y: self.y + node::LEGACY_PORT_Y_OFFSET }
+        } else {
+            self.input_port_pos(0)
+        }
+    }
+
+    pub fn output_port_rect(&self) -> Rect {
+        let pos = self.output_pos();
+        Rect { pos: DVec2 { x: pos.x - port::HIT_OFFSET_X, y: pos.y - port::HIT_OFFSET_Y }, size: DVec2 { x: port::HIT_SIZE, y: port::HIT_SIZE } }
+    }
+
+    pub fn input_port_rect(&self) -> Rect {
+        let pos = self.input_pos();
+        Rect { pos: DVec2 { x: pos.x - port::HIT_OFFSET_Y, y: pos.y - port::HIT_OFFSET_Y }, size: DVec2 { x: port::HIT_SIZE, y: port::HIT_SIZE } }
+    }
+
+    // Hit area for a specific output port, centred on the port row
+    pub fn output_port_rect_at(&self, index: usize) -> Rect {
+        let pos = self.output_port_pos(index);
+        Rect { pos: DVec2 { x: pos.x - port::HIT_OFFSET_X, y: pos.y - port::HIT_SIZE / 2.0 }, size: DVec2 { x: port::HIT_SIZE, y: port::HIT_SIZE } }
+    }
+
+    // Hit area for a specific input port, centred on the port row
+    pub fn input_port_rect_at(&self, index: usize) -> Rect {
+        let pos = self.input_port_pos(index);
+        Rect { pos: DVec2 { x: pos.x - port::HIT_OFFSET_Y, y: pos.y - port::HIT_SIZE / 2.0 }, size: DVec2 { x: port::HIT_SIZE, y: port::HIT_SIZE } }
+    }
+
+    // Index of the output port under `pos`, if any
+    pub fn output_port_at(&self, pos: DVec2) -> Option<usize> {
+        (0..self.output_ports.len()).find(|&i| self.output_port_rect_at(i).contains(pos))
+    }
+
+    // Index of the input port under `pos`, if any
+    pub fn input_port_at(&self, pos: DVec2) -> Option<usize> {
+        (0..self.input_ports.len()).find(|&i| self.input_port_rect_at(i).contains(pos))
+    }
+
+    // Find port index by id
+    pub fn input_port_index(&self, port_id: &str) -> Option<usize> {
+        self.input_ports.iter().position(|p| p.id == port_id)
+    }
+
+    pub fn output_port_index(&self, port_id: &str) -> Option<usize> {
+        self.output_ports.iter().position(|p| p.id == port_id)
+    }
+
+    // Anchor point for an edge leaving `port_id`; unknown or empty ids use the legacy position
+    pub fn output_anchor(&self, port_id: &str) -> DVec2 {
+        match self.output_port_index(port_id) {
+            Some(index) => self.output_port_pos(index),
+            None => self.output_pos(),
+        }
+    }
+
+    // Anchor point for an edge entering `port_id`; unknown or empty ids use the legacy position
+    pub fn input_anchor(&self, port_id: &str) -> DVec2 {
+        match self.input_port_index(port_id) {
+            Some(index) => self.input_port_pos(index),
+            None => self.input_pos(),
+        }
+    }
+}
+
+// Edge marker type
+#[derive(Clone, Copy, PartialEq, Default, Debug, Serialize, Deserialize)]
+pub enum EdgeMarker {
+    #[default]
+    None,
+    Arrow,
+    ArrowFilled,
+    Circle,
+}
+
+// Shape of the line between two ports
+#[derive(Clone, Copy, PartialEq, Default, Debug, Serialize, Deserialize)]
+pub enum EdgePathType {
+    #[default]
+    Bezier,
+    Straight,
+    Step,       // orthogonal segments
+    SmoothStep, // orthogonal segments with rounded corners
+}
+
+impl EdgePathType {
+    pub const ALL: [EdgePathType; 4] = [
+        EdgePathType::Bezier,
+        EdgePathType::Straight,
+        EdgePathType::Step,
+        EdgePathType::SmoothStep,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            EdgePathType::Bezier => "Bezier",
+            EdgePathType::Straight => "Straight",
+            EdgePathType::Step => "Step",
+            EdgePathType::SmoothStep => "Smooth step",
+        }
+    }
+}
+
+// Edge connection with per-edge properties.
+// Endpoints reference nodes by their stable `FlowNode::id`, so edges stay valid
+// when other nodes are inserted or removed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EdgeConnection {
+    pub from_node: String,
+    pub to_node: String,
+    pub from_port: String,  // output port id
+    pub to_port: String,    // input port id
+    pub style: f32,         // 0=solid, 1=dashed, 2=dotted
+    pub width: f32,         // line width
+    pub animated: bool,     // animation on/off
+    pub label: String,      // edge label text
+    pub marker_end: EdgeMarker, // marker at end
+    #[serde(default)]
+    pub path_type: EdgePathType, // line shape
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    pub metadata: Metadata,
+}
+
+impl EdgeConnection {
+    pub fn new(from_node: &str, to_node: &str) -> Self {
+        Self {
+            from_node: from_node.to_string(),
+            to_node: to_node.to_string(),
+            from_port: String::new(),
+            to_port: String::new(),
+            style: 0.0,      // solid by default
+            width: edge::WIDTH_F32,
+            animated: true,  // animated by default
+            label: String::new(),
+            marker_end: EdgeMarker::Arrow, // arrow by default
+            path_type: EdgePathType::Bezier,
+            metadata: Metadata::new(),
+        }
+    }
+
+    pub fn new_with_ports(from_node: &str, from_port: &str, to_node: &str, to_port: &str) -> Self {
+        Self {
+            from_node: from_node.to_string(),
+            to_node: to_node.to_string(),
+            from_port: from_port.to_string(),
+            to_port: to_port.to_string(),
+            style: 0.0,
+            width: edge::WIDTH_F32,
+            animated: false,  // dataflow edges not animated by default
+            label: String::new(),
+            marker_end: EdgeMarker::Arrow,
+            path_type: EdgePathType::Bezier,
+            metadata: Metadata::new(),
+        }
+    }
+
+    /// Whether this edge starts or ends at the given node
+    pub fn touches(&self, node_id: &str) -> bool {
+        self.from_node == node_id || self.to_node == node_id
+    }
+
+    /// Whether both edges join the same nodes through the same ports
+    pub fn same_endpoints(&self, other: &EdgeConnection) -> bool {
+        self.from_node == other.from_node && self.from_port == other.from_port &&
+        self.to_node == other.to_node && self.to_port == other.to_port
+    }
+}
+
+/// A set of nodes and the edges between them.
+///
+/// Nodes are addressed by their `FlowNode::id`; the id -> index lookup is kept
+/// up to date by every mutation, so callers never deal with positions in the
+/// node list. Node order is also the draw order (later nodes are on top).
+///
+/// Every mutation is made of a few primitive changes, each matching a
+/// [`FlowCommand`]. Between [`begin_recording`](Self::begin_recording) and
+/// [`end_recording`](Self::end_recording) the graph collects those commands,
+/// which is how the canvas builds its undo steps.
+#[derive(Clone, Debug, Default)]
+pub struct FlowGraph {
+    nodes: Vec<FlowNode>,
+    edges: Vec<EdgeConnection>,
+    node_index: HashMap<String, usize>,
+    revision: u64,
+    structure_revision: u64,
+    journal: Option<Journal>,
+}
+
+/// Commands recorded since `begin_recording`
+#[derive(Clone, Debug, Default)]
+struct Journal {
+    commands: Vec<FlowCommand>,
+    // Per node id, the last command that further changes to the node can be
+    // merged into, so a drag records one move per node rather than one per event
+    open: HashMap<String, usize>,
+}
+
+impl Journal {
+    fn push(&mut self, command: FlowCommand) {
+        if self.merge(&command) || self.cancel(&command) {
+            return;
+        }
+        match &command {
+            FlowCommand::MoveNode { id, .. } => {
+                self.open.insert(id.clone(), self.commands.len());
+            }
+            FlowCommand::UpdateNode { before, after } if before.id == after.id => {
+                self.open.insert(after.id.clone(), self.commands.len());
+            }
+            FlowCommand::UpdateNode { before, after } => {
+                self.open.remove(&before.id);
+                self.open.remove(&after.id);
+            }
+            FlowCommand::AddNode { node, .. } | FlowCommand::RemoveNode { node, .. } => {
+                self.open.remove(&node.id);
+            }
+            _ => {}
+        }
+        self.commands.push(command);
+    }
+
+    // Fold `command` into the open command for the same node, or into the
+    // previous command if both update the same edge
+    fn merge(&mut self, command: &FlowCommand) -> bool {
+        if let FlowCommand::UpdateEdge { index, after, .. } = command {
+            if let Some(FlowCommand::UpdateEdge { index: last, after: merged, .. }) = self.commands.last_mut() {
+                if last == index {
+                    *merged = after.clone();
+                    return true;
+                }
+            }
+            return false;
+        }
+        let id = match command {
+            FlowCommand::MoveNode { id, .. } => id,
+            FlowCommand::UpdateNode { before, after } if before.id == after.id => &after.id,
+            _ => return false,
+        };
+        let Some(&open) = self.open.get(id) else { return false };
+        let merged = match (&self.commands[open], command) {
+            (FlowCommand::MoveNode { id, from, .. }, FlowCommand::MoveNode { to, .. }) => {
+                FlowCommand::MoveNode { id: id.clone(), from: *from, to: *to }
+            }
+            (FlowCommand::UpdateNode { before, after }, FlowCommand::MoveNode { to, .. }) => {
+                FlowCommand::UpdateNode { before: before.clone(), after: FlowNode { x: to.x, y: to.y, ..after.clone() } }
+            }
+            (FlowCommand::MoveNode { from, .. }, FlowCommand::UpdateNode { before, after }) => {
+                FlowCommand::UpdateNode { before: FlowNode { x: from.x, y: from.y, ..before.clone() }, after: after.clone() }
+            }
+            (FlowCommand::UpdateNode { before, .. }, FlowCommand::UpdateNode { after, .. }) => {
+                FlowCommand::UpdateNode { before: before.clone(), after: after.clone() }
+            }
+            _ => return false,
+        };
+        self.commands[open] = merged;
+        true
+    }
+
+    // Drop the last edge command if `command` puts the same edge back where it
+    // was taken out, e.g. a reconnect that ends on the original port
+    fn cancel(&mut self, command: &FlowCommand) -> bool {
+        let FlowCommand::Connect { index, edge } = command else { return false };
+        let Some(last) = self.commands.iter().rposition(|command| matches!(command,
+            FlowCommand::Connect { .. } | FlowCommand::Disconnect { .. } | FlowCommand::UpdateEdge { .. }))
+        else {
+            return false;
+        };
+        match &self.commands[last] {
+            FlowCommand::Disconnect { index: taken, edge: taken_edge } if taken == index && taken_edge == edge => {}
+            _ => return false,
+        }
+        self.commands.remove(last);
+        for open in self.open.values_mut() {
+            if *open > last {
+                *open -= 1;
+            }
+        }
+        true
+    }
+
+    /// The recorded commands, without changes that were undone again
+    fn finish(self) -> Vec<FlowCommand> {
+        self.commands.into_iter()
+            .filter(|command| match command {
+                FlowCommand::MoveNode { from, to, .. } => from != to,
+                FlowCommand::UpdateNode { before, after } => before != after,
+                FlowCommand::UpdateEdge { before, after, .. } => before != after,
+                _ => true,
+            })
+            .collect()
+    }
+}
+
+/// Source of graph revisions, shared by all graphs so that two different
+/// graphs never report the same revision
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(1);
+
+impl FlowGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a graph from existing nodes and edges.
+    /// Edges whose endpoints do not exist are kept but ignored when drawing.
+    pub fn from_parts(nodes: Vec<FlowNode>, edges: Vec<EdgeConnection>) -> Self {
+        let mut graph = Self { nodes, edges, ..Self::default() };
+        graph.rebuild_index();
+        graph
+    }
+
+    pub fn into_parts(self) -> (Vec<FlowNode>, Vec<EdgeConnection>) {
+        (self.nodes, self.edges)
+    }
+
+    fn rebuild_index(&mut self) {
+        self.touch();
+        self.node_index = self.nodes.iter()
+            .enumerate()
+            .map(|(i, node)| (node.id.clone(), i))
+            .collect();
+    }
+
+    // Fix the lookup entries of the nodes from `start` on after an insert or removal
+    fn reindex_from(&mut self, start: usize) {
+        for (i, node) in self.nodes.iter().enumerate().skip(start) {
+            match self.node_index.get_mut(&node.id) {
+                Some(index) => *index = i,
+                None => {
+                    self.node_index.insert(node.id.clone(), i);
+                }
+            }
+        }
+    }
+
+    fn touch(&mut self) {
+        self.revision = NEXT_REVISION.fetch_add(1, Ordering::Relaxed);
+        self.structure_revision = self.revision;
+    }
+
+    // A change that only moved or resized nodes
+    fn touch_geometry(&mut self) {
+        self.revision = NEXT_REVISION.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record(&mut self, command: FlowCommand) {
+        if let Some(journal) = &mut self.journal {
+            journal.push(command);
+        }
+    }
+
+    // ---- Recording ----
+
+    /// Start collecting a [`FlowCommand`] for every following change,
+    /// dropping anything recorded before
+    pub fn begin_recording(&mut self) {
+        self.journal = Some(Journal::default());
+    }
+
+    /// Stop recording and return the commands that turn the graph as it was at
+    /// `begin_recording` into the current graph. Repeated changes to the same
+    /// node or edge are merged, so a drag comes back as one move per node,
+    /// and an edge put back where it was taken out records nothing.
+    pub fn end_recording(&mut self) -> Vec<FlowCommand> {
+        self.journal.take().map(Journal::finish).unwrap_or_default()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.journal.is_some()
+    }
+
+    // ---- Queries ----
+
+    /// Changes whenever a node or edge may have changed. Caches built from the
+    /// graph, such as the canvas' spatial index, compare it to know when to rebuild.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Like [`revision`](Self::revision), but unchanged by changes that only
+    /// move or resize nodes ([`move_node`](Self::move_node), [`set_node_rect`](Self::set_node_rect)).
+    /// While it stays the same, caches can update just the nodes whose rect changed.
+    pub fn structure_revision(&self) -> u64 {
+        self.structure_revision
+    }
+
+    pub fn nodes(&self) -> &[FlowNode] {
+        &self.nodes
+    }
+
+    pub fn edges(&self) -> &[EdgeConnection] {
+        &self.edges
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn contains_node(&self, node_id: &str) -> bool {
+        self.node_index.contains_key(node_id)
+    }
+
+    /// Position of a node in `nodes()` (its draw order)
+    pub fn node_index(&self, node_id: &str) -> Option<usize> {
+        self.node_index.get(node_id).copied()
+    }
+
+    pub fn node(&self, node_id: &str) -> Option<&FlowNode> {
+        self.node_index(node_id).map(|idx| &self.nodes[idx])
+    }
+
+    pub fn edge(&self, index: usize) -> Option<&EdgeConnection> {
+        self.edges.get(index)
+    }
+
+    /// Whether both endpoints of an edge exist in this graph
+    pub fn edge_is_valid(&self, edge: &EdgeConnection) -> bool {
+        self.contains_node(&edge.from_node) && self.contains_node(&edge.to_node)
+    }
+
+    /// Index of an edge with the same endpoints and ports, if any
+    pub fn find_edge(&self, from_node: &str, from_port: &str, to_node: &str, to_port: &str) -> Option<usize> {
+        self.edges.iter().position(|e|
+            e.from_node == from_node && e.from_port == from_port &&
+            e.to_node == to_node && e.to_port == to_port
+        )
+    }
+
+    /// Indices of all edges starting or ending at a node
+    pub fn connected_edges(&self, node_id: &str) -> Vec<usize> {
+        self.edges.iter()
+            .enumerate()
+            .filter(|(_, e)| e.touches(node_id))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Ids of nodes with an edge into `node_id`
+    pub fn incomers(&self, node_id: &str) -> Vec<&str> {
+        let mut ids: Vec<&str> = self.edges.iter()
+            .filter(|e| e.to_node == node_id)
+            .map(|e| e.from_node.as_str())
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    /// Ids of nodes with an edge from `node_id`
+    pub fn outgoers(&self, node_id: &str) -> Vec<&str> {
+        let mut ids: Vec<&str> = self.edges.iter()
+            .filter(|e| e.from_node == node_id)
+            .map(|e| e.to_node.as_str())
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    /// Topmost node containing a canvas-space point
+    pub fn node_at(&self, pos: DVec2) -> Option<&FlowNode> {
+        self.nodes.iter().rev().find(|n| n.contains(pos))
+    }
+
+    /// Bounding rectangle of all nodes, or `None` for an empty graph
+    pub fn bounds(&self) -> Option<Rect> {
+        let first = self.nodes.first()?;
+        let (mut min, mut max) = (
+            DVec2 { x: first.x, y: first.y },
+            DVec2 { x: first.x + first.width, y: first.y + first.height },
+        );
+        for n in &self.nodes[1..] {
+            min.x = min.x.min(n.x);
+            min.y = min.y.min(n.y);
+            max.x = max.x.max(n.x + n.width);
+            max.y = max.y.max(n.y + n.height);
+        }
+        Some(Rect { pos: min, size: DVec2 { x: max.x - min.x, y: max.y - min.y } })
+    }
+
+    /// Return `base` if no node uses it yet, otherwise `base_2`, `base_3`, ...
+    pub fn unique_node_id(&self, base: &str) -> String {
+        if !self.id_in_use(base) {
+            return base.to_string();
+        }
+        let mut n = 2;
+        loop {
+            let candidate = format!("{}_{}", base, n);
+            if !self.id_in_use(&candidate) {
+                return candidate;
+            }
+            n += 1;
+        }
+    }
+
+    // Nodes hidden inside collapsed groups keep their ids reserved
+    fn id_in_use(&self, id: &str) -> bool {
+        fn hidden(nodes: &[FlowNode], id: &str) -> bool {
+            nodes.iter()
+                .filter_map(|n| n.group.as_ref())
+                .any(|g| g.hidden_nodes.iter().any(|h| h.id == id) || hidden(&g.hidden_nodes, id))
+        }
+        self.contains_node(id) || hidden(&self.nodes, id)
+    }
+
+    // ---- Mutations ----
+
+    /// Add a node on top of all others.
+    /// Returns the id it was stored under, which gets a numeric suffix if
+    /// another node already uses the requested id.
+    pub fn add_node(&mut self, mut node: FlowNode) -> String {
+        node.id = self.unique_node_id(&node.id);
+        let node_id = node.id.clone();
+        self.insert_node_at(self.nodes.len(), node);
+        node_id
+    }
+
+    /// Put a node at a position in the draw order exactly as given, without
+    /// renaming it or touching edges. Used to replay history.
+    pub(crate) fn insert_node_at(&mut self, index: usize, node: FlowNode) {
+        let index = index.min(self.nodes.len());
+        self.touch();
+        if self.is_recording() {
+            self.record(FlowCommand::AddNode { index, node: node.clone() });
+        }
+        self.nodes.insert(index, node);
+        self.reindex_from(index);
+    }
+
+    /// Take the node at a position in the draw order, leaving its edges and
+    /// children alone. Used to replay history.
+    pub(crate) fn remove_node_at(&mut self, index: usize) -> Option<FlowNode> {
+        let node = (index < self.nodes.len()).then(|| self.nodes.remove(index))?;
+        self.touch();
+        self.node_index.remove(&node.id);
+        self.reindex_from(index);
+        if self.is_recording() {
+            self.record(FlowCommand::RemoveNode { index, node: node.clone() });
+        }
+        Some(node)
+    }
+
+    /// Change a node in place. Returns `None` if there is no node `node_id`.
+    ///
+    /// Changing the `id` this way leaves edges and children pointing at the
+    /// old id; use [`rename_node`](Self::rename_node) for that.
+    pub fn update_node<R>(&mut self, node_id: &str, update: impl FnOnce(&mut FlowNode) -> R) -> Option<R> {
+        let idx = self.node_index(node_id)?;
+        let before = self.is_recording().then(|| self.nodes[idx].clone());
+        let result = update(&mut self.nodes[idx]);
+        self.touch();
+        if self.nodes[idx].id != node_id {
+            self.node_index.remove(node_id);
+            self.node_index.insert(self.nodes[idx].id.clone(), idx);
+        }
+        if let Some(before) = before {
+            self.record_node_change(before, idx);
+        }
+        Some(result)
+    }
+
+    /// Move and resize a node to `rect`
+    pub fn set_node_rect(&mut self, node_id: &str, rect: Rect) -> bool {
+        let Some(idx) = self.node_index(node_id) else { return false };
+        let before = self.is_recording().then(|| self.nodes[idx].clone());
+        self.nodes[idx].set_rect(rect);
+        self.touch_geometry();
+        if let Some(before) = before {
+            self.record_node_change(before, idx);
+        }
+        true
+    }
+
+    // Record the change of the node at `idx` from `before`, as a move if that is all it was
+    fn record_node_change(&mut self, before: FlowNode, idx: usize) {
+        let after = &self.nodes[idx];
+        let moved_only = FlowNode { x: after.x, y: after.y, ..before.clone() } == *after;
+        let command = if moved_only {
+            FlowCommand::MoveNode {
+                id: after.id.clone(),
+                from: DVec2 { x: before.x, y: before.y },
+                to: DVec2 { x: after.x, y: after.y },
+            }
+        } else {
+            FlowCommand::UpdateNode { before, after: after.clone() }
+        };
+        self.record(command);
+    }
+
+    /// Change the edge at `index` in place. Returns `None` if there is no such edge.
+    pub fn update_edge<R>(&mut self, index: usize, update: impl FnOnce(&mut EdgeConnection) -> R) -> Option<R> {
+        let before = self.is_recording().then(|| self.edges.get(index).cloned()).flatten();
+        let result = update(self.edges.get_mut(index)?);
+        self.touch();
+        if let Some(before) = before {
+            let after = self.edges[index].clone();
+            self.record(FlowCommand::UpdateEdge { index, before, after });
+        }
+        Some(result)
+    }
+
+    /// Remove a node and every edge connected to it.
+    /// Children of a removed group move up to the group's parent.
+    /// Returns the node and the removed edges.
+    pub fn remove_node(&mut self, node_id: &str) -> Option<(FlowNode, Vec<EdgeConnection>)> {
+        let idx = self.node_index(node_id)?;
+        let mut removed = Vec::new();
+        for i in (0..self.edges.len()).rev() {
+            if self.edges[i].touches(node_id) {
+                removed.extend(self.disconnect(i));
+            }
+        }
+        removed.reverse();
+        let parent = self.nodes[idx].parent.clone();
+        let children: Vec<String> = self.children(node_id).into_iter().map(str::to_string).collect();
+        for child in children {
+            self.update_node(&child, |c| c.parent = parent.clone());
+        }
+        let node = self.remove_node_at(idx)?;
+        Some((node, removed))
+    }
+
+    /// Remove several nodes and their edges at once. Returns how many nodes were removed.
+    pub fn remove_nodes(&mut self, node_ids: &HashSet<String>) -> usize {
+        let before = self.nodes.len();
+        let parents: HashMap<String, Option<String>> = self.nodes.iter()
+            .filter(|n| node_ids.contains(&n.id))
+            .map(|n| (n.id.clone(), n.parent.clone()))
+            .collect();
+        for i in (0..self.edges.len()).rev() {
+            let edge = &self.edges[i];
+            if node_ids.contains(&edge.from_node) || node_ids.contains(&edge.to_node) {
+                self.disconnect(i);
+            }
+        }
+        // Children of removed groups move up to the nearest kept ancestor
+        let reparented: Vec<(String, Option<String>)> = self.nodes.iter()
+            .filter(|n| !node_ids.contains(&n.id))
+            .filter_map(|n| {
+                let mut parent = n.parent.clone();
+                while let Some(next) = parent.as_ref().and_then(|p| parents.get(p)) {
+                    parent = next.clone();
+                }
+                (parent != n.parent).then(|| (n.id.clone(), parent))
+            })
+            .collect();
+        for (id, parent) in reparented {
+            self.update_node(&id, |n| n.parent = parent);
+        }
+        for i in (0..self.nodes.len()).rev() {
+            if node_ids.contains(&self.nodes[i].id) {
+                self.remove_node_at(i);
+            }
+        }
+        before - self.nodes.len()
+    }
+
+    /// Give a node a new id, updating every edge, child and collapsed group
+    /// content that references it. Fails if the node does not exist or the new id is taken.
+    pub fn rename_node(&mut self, node_id: &str, new_id: &str) -> bool {
+        if self.id_in_use(new_id) || !self.contains_node(node_id) {
+            return false;
+        }
+        self.update_node(node_id, |n| n.id = new_id.to_string());
+        for i in 0..self.edges.len() {
+            if self.edges[i].touches(node_id) {
+                self.update_edge(i, |edge| {
+                    if edge.from_node == node_id { edge.from_node = new_id.to_string(); }
+                    if edge.to_node == node_id { edge.to_node = new_id.to_string(); }
+                });
+            }
+        }
+        let children: Vec<String> = self.children(node_id).into_iter().map(str::to_string).collect();
+        for child in children {
+            self.update_node(&child, |c| c.parent = Some(new_id.to_string()));
+        }
+        let groups: Vec<String> = self.nodes.iter()
+            .filter(|n| n.group.as_ref().is_some_and(|g| g.mentions(node_id)))
+            .map(|n| n.id.clone())
+            .collect();
+        for id in groups {
+            self.update_node(&id, |n| {
+                if let Some(group) = &mut n.group {
+                    group.rename(node_id, new_id);
+                }
+            });
+        }
+        true
+    }
+
+    /// Move a node to an absolute canvas position
+    pub fn move_node(&mut self, node_id: &str, x: f64, y: f64) -> bool {
+        let Some(idx) = self.node_index(node_id) else { return false };
+        let node = &mut self.nodes[idx];
+        let from = DVec2 { x: node.x, y: node.y };
+        node.x = x;
+        node.y = y;
+        self.touch_geometry();
+        if self.is_recording() {
+            self.record(FlowCommand::MoveNode { id: node_id.to_string(), from, to: DVec2 { x, y } });
+        }
+        true
+    }
+
+    /// Add an edge if both endpoints exist and the same connection is not
+    /// already present. Returns the index of the new edge.
+    pub fn connect(&mut self, edge: EdgeConnection) -> Option<usize> {
+        if !self.edge_is_valid(&edge) {
+            return None;
+        }
+        if self.find_edge(&edge.from_node, &edge.from_port, &edge.to_node, &edge.to_port).is_some() {
+            return None;
+        }
+        let index = self.edges.len();
+        self.insert_edge(index, edge);
+        Some(index)
+    }
+
+    /// Put an edge at a specific position, e.g. back where [`disconnect`](Self::disconnect)
+    /// took it from. Unlike [`connect`](Self::connect) this does no validation.
+    pub fn insert_edge(&mut self, index: usize, edge: EdgeConnection) {
+        let index = index.min(self.edges.len());
+        self.touch();
+        if self.is_recording() {
+            self.record(FlowCommand::Connect { index, edge: edge.clone() });
+        }
+        self.edges.insert(index, edge);
+    }
+
+    /// Remove an edge by index
+    pub fn disconnect(&mut self, index: usize) -> Option<EdgeConnection> {
+        let edge = (index < self.edges.len()).then(|| self.edges.remove(index))?;
+        self.touch();
+        if self.is_recording() {
+            self.record(FlowCommand::Disconnect { index, edge: edge.clone() });
+        }
+        Some(edge)
+    }
+
+    /// Remove several edges by index. Returns how many edges were removed.
+    pub fn remove_edges(&mut self, indices: &HashSet<usize>) -> usize {
+        let mut sorted: Vec<usize> = indices.iter().copied().filter(|&i| i < self.edges.len()).collect();
+        sorted.sort_unstable_by(|a, b| b.cmp(a));
+        for &i in &sorted {
+            self.disconnect(i);
+        }
+        sorted.len()
+    }
+
+    /// Copy of the given nodes and the edges between them. Groups bring their
+    /// children along; nodes whose parent is not copied become top-level.
+    pub fn subgraph(&self, node_ids: &HashSet<String>) -> FlowGraph {
+        let mut ids = node_ids.clone();
+        for id in node_ids {
+            ids.extend(self.descendants(id));
+        }
+        let nodes = self.nodes.iter()
+            .filter(|n| ids.contains(&n.id))
+            .map(|n| {
+                let mut n = n.clone();
+                if n.parent.as_ref().is_some_and(|p| !ids.contains(p)) {
+                    n.parent = None;
+                }
+                n
+            })
+            .collect();
+        let edges = self.edges.iter()
+            .filter(|e| ids.contains(&e.from_node) && ids.contains(&e.to_node))
+            .cloned()
+            .collect();
+        FlowGraph::from_parts(nodes, edges)
+    }
+
+    /// Add every node and edge of `other` on top, moved by `offset`.
+    /// Nodes whose id is taken get a fresh one and edges follow the rename.
+    /// Returns the ids the added nodes are visible under.
+    pub fn insert_graph(&mut self, mut other: FlowGraph, offset: DVec2) -> Vec<String> {
+        // Open collapsed groups so their hidden children are renamed too
+        let mut collapsed = Vec::new();
+        while let Some(id) = other.nodes.iter().find(|n| n.is_collapsed()).map(|n| n.id.clone()) {
+            other.expand_group(&id);
+            collapsed.push(id);
+        }
+
+        let (nodes, edges) = other.into_parts();
+        let mut renamed: HashMap<String, String> = HashMap::new();
+        let mut added = Vec::with_capacity(nodes.len());
+        for mut node in nodes {
+            let old_id = node.id.clone();
+            node.x += offset.x;
+            node.y += offset.y;
+            let new_id = self.add_node(node);
+            renamed.insert(old_id, new_id.clone());
+            added.push(new_id);
+        }
+        for id in &added {
+            self.update_node(id, |node| {
+                node.parent = node.parent.as_ref().and_then(|p| renamed.get(p).cloned());
+            });
+        }
+        for mut edge in edges {
+            let (Some(from), Some(to)) = (renamed.get(&edge.from_node), renamed.get(&edge.to_node)) else {
+                continue;
+            };
+            edge.from_node = from.clone();
+            edge.to_node = to.clone();
+            self.connect(edge);
+        }
+
+        // Inner groups first, so outer groups take them along when they collapse
+        for id in collapsed.iter().rev() {
+            if let Some(new_id) = renamed.get(id) {
+                self.collapse_group(new_id);
+            }
+        }
+        added.retain(|id| self.contains_node(id));
+        added
+    }
+
+    pub fn clear(&mut self) {
+        self.touch();
+        while !self.edges.is_empty() {
+            self.disconnect(self.edges.len() - 1);
+        }
+        while !self.nodes.is_empty() {
+            self.remove_node_at(self.nodes.len() - 1);
+        }
+    }
+
+    /// Swap every node and edge for those of `other`, as changes to this graph
+    pub fn replace_with(&mut self, other: FlowGraph) {
+        self.clear();
+        let (nodes, edges) = other.into_parts();
+        for node in nodes {
+            self.insert_node_at(self.nodes.len(), node);
+        }
+        for edge in edges {
+            self.insert_edge(self.edges.len(), edge);
+        }
+    }
+
+    // ---- Groups ----
+
+    /// Ids of the nodes whose parent is `group_id`
+    pub fn children(&self, group_id: &str) -> Vec<&str> {
+        self.nodes.iter()
+            .filter(|n| n.parent.as_deref() == Some(group_id))
+            .map(|n| n.id.as_str())
+            .collect()
+    }
+
+    /// Children of `group_id`, their children, and so on
+    pub fn descendants(&self, group_id: &str) -> Vec<String> {
+        let mut out = Vec::new();
+        let mut stack = vec![group_id.to_string()];
+        while let Some(id) = stack.pop() {
+            for child in self.children(&id) {
+                out.push(child.to_string());
+                stack.push(child.to_string());
+            }
+        }
+        out
+    }
+
+    /// Whether `node_id` sits inside `group_id`, directly or through nested groups
+    pub fn is_descendant(&self, node_id: &str, group_id: &str) -> bool {
+        let mut current = self.node(node_id).and_then(|n| n.parent.as_deref());
+        while let Some(id) = current {
+            if id == group_id {
+                return true;
+            }
+            current = self.node(id).and_then(|n| n.parent.as_deref());
+        }
+        false
+    }
+
+    /// Put nodes into a new group whose frame encloses them.
+    /// Nodes inside another listed node's group come along with it, and the
+    /// group joins the members' parent if they all share one.
+    /// Returns the id the group was stored under, or `None` if no listed node exists.
+    pub fn group_nodes(&mut self, group_id: &str, title: &str, node_ids: &[String]) -> Option<String> {
+        let listed: HashSet<&String> = node_ids.iter().filter(|id| self.contains_node(id)).collect();
+        let members: Vec<String> = listed.iter()
+            .filter(|id| !listed.iter().any(|other| self.is_descendant(id, other)))
+            .map(|id| id.to_string())
+            .collect();
+        let first = members.iter().filter_map(|id| self.node_index(id)).min()?;
+        let parent = self.nodes[first].parent.clone()
+            .filter(|p| members.iter().all(|id| self.node(id).is_some_and(|n| n.parent.as_ref() == Some(p))));
+
+        let mut frame = FlowNode::new_dataflow(&self.unique_node_id(group_id), 0.0, 0.0, title, NodeCategory::default(), vec![], vec![]);
+        frame.parent = parent;
+        frame.group = Some(GroupState::default());
+        let group_id = frame.id.clone();
+        // Below the members so they draw on top of the frame
+        self.insert_node_at(first, frame);
+        for id in &members {
+            self.update_node(id, |node| node.parent = Some(group_id.clone()));
+        }
+        self.fit_group(&group_id);
+        Some(group_id)
+    }
+
+    /// Dissolve a group, handing its children to the group's parent
+    pub fn ungroup(&mut self, group_id: &str) -> bool {
+        if !self.node(group_id).is_some_and(|n| n.is_group()) {
+            return false;
+        }
+        self.expand_group(group_id);
+        self.remove_node(group_id).is_some()
+    }
+
+    /// Fold an expanded group's children into it. The group then looks like a
+    /// regular node with one port per child port that has an edge leaving the group.
+    pub fn collapse_group(&mut self, group_id: &str) -> bool {
+        let Some(frame) = self.node(group_id).filter(|n| n.is_frame()) else {
+            return false;
+        };
+        let origin = DVec2 { x: frame.x, y: frame.y };
+        let members: HashSet<String> = self.descendants(group_id).into_iter().collect();
+
+        // Edges crossing the frame move to the group's ports, edges inside it go away
+        let (mut inputs, mut outputs, mut inner) = (Vec::<Port>::new(), Vec::<Port>::new(), Vec::new());
+        let mut proxy_ports = Vec::new();
+        for i in 0..self.edges.len() {
+            let mut edge = self.edges[i].clone();
+            let from_inside = members.contains(&edge.from_node);
+            let to_inside = members.contains(&edge.to_node);
+            if from_inside && to_inside {
+                inner.push(i);
+                continue;
+            }
+            if from_inside {
+                let port = self.group_port(&edge.from_node, &edge.from_port, true, &mut outputs, &mut proxy_ports);
+                (edge.from_node, edge.from_port) = (group_id.to_string(), port);
+            }
+            if to_inside {
+                let port = self.group_port(&edge.to_node, &edge.to_port, false, &mut inputs, &mut proxy_ports);
+                (edge.to_node, edge.to_port) = (group_id.to_string(), port);
+            }
+            if from_inside || to_inside {
+                self.update_edge(i, |e| *e = edge);
+            }
+        }
+        let mut hidden_edges: Vec<EdgeConnection> = inner.into_iter().rev()
+            .filter_map(|i| self.disconnect(i))
+            .collect();
+        hidden_edges.reverse();
+
+        let mut hidden_nodes = Vec::new();
+        for i in (0..self.nodes.len()).rev() {
+            if members.contains(&self.nodes[i].id) {
+                if let Some(mut node) = self.remove_node_at(i) {
+                    node.x -= origin.x;
+                    node.y -= origin.y;
+                    hidden_nodes.push(node);
+                }
+            }
+        }
+        hidden_nodes.reverse();
+
+        self.update_node(group_id, |node| {
+            node.group = Some(GroupState {
+                collapsed: true,
+                expanded_width: node.width,
+                expanded_height: node.height,
+                hidden_nodes,
+                hidden_edges,
+                proxy_ports,
+            });
+            node.width = node::WIDTH;
+            node.height = FlowNode::height_for_ports(inputs.len().max(outputs.len()));
+            node.input_ports = inputs;
+            node.output_ports = outputs;
+        }).is_some()
+    }
+
+    /// Put a collapsed group's children back around it and rewire its edges
+    /// to the children's own ports
+    pub fn expand_group(&mut self, group_id: &str) -> bool {
+        let Some(idx) = self.node_index(group_id) else { return false };
+        if !self.nodes[idx].is_collapsed() {
+            return false;
+        }
+        let (hidden_nodes, hidden_edges, proxy_ports, origin) = self.update_node(group_id, |node| {
+            let state = node.group.get_or_insert_with(GroupState::default);
+            state.collapsed = false;
+            let hidden_nodes = std::mem::take(&mut state.hidden_nodes);
+            let hidden_edges = std::mem::take(&mut state.hidden_edges);
+            let proxy_ports = std::mem::take(&mut state.proxy_ports);
+            node.width = state.expanded_width;
+            node.height = state.expanded_height;
+            node.input_ports.clear();
+            node.output_ports.clear();
+            (hidden_nodes, hidden_edges, proxy_ports, DVec2 { x: node.x, y: node.y })
+        }).unwrap_or_default();
+        let proxy = |port: &str, output: bool| {
+            proxy_ports.iter()
+                .find(|p| p.port == port && p.output == output)
+                .map(|p| (p.node.clone(), p.node_port.clone()))
+        };
+
+        // Right above the group so the children draw on top of its frame
+        for (i, mut child) in hidden_nodes.into_iter().enumerate() {
+            child.x += origin.x;
+            child.y += origin.y;
+            self.insert_node_at(idx + 1 + i, child);
+        }
+
+        for i in 0..self.edges.len() {
+            let mut edge = self.edges[i].clone();
+            if edge.from_node == group_id {
+                if let Some(member) = proxy(&edge.from_port, true) {
+                    (edge.from_node, edge.from_port) = member;
+                }
+            }
+            if edge.to_node == group_id {
+                if let Some(member) = proxy(&edge.to_port, false) {
+                    (edge.to_node, edge.to_port) = member;
+                }
+            }
+            if edge != self.edges[i] {
+                self.update_edge(i, |e| *e = edge);
+            }
+        }
+        // An expanded group has no ports of its own
+        for i in (0..self.edges.len()).rev() {
+            if self.edges[i].touches(group_id) {
+                self.disconnect(i);
+            }
+        }
+        for edge in hidden_edges {
+            self.insert_edge(self.edges.len(), edge);
+        }
+        true
+    }
+
+    /// Expand every collapsed group, including nested ones
+    pub fn expand_all(&mut self) {
+        while let Some(id) = self.nodes.iter().find(|n| n.is_collapsed()).map(|n| n.id.clone()) {
+            self.expand_group(&id);
+        }
+    }
+
+    /// Move a node to an absolute position, taking the children of a group along
+    pub fn move_node_with_children(&mut self, node_id: &str, x: f64, y: f64) -> bool {
+        let Some(node) = self.node(node_id) else { return false };
+        let (dx, dy) = (x - node.x, y - node.y);
+        for id in self.descendants(node_id) {
+            if let Some(child) = self.node(&id) {
+                let (cx, cy) = (child.x + dx, child.y + dy);
+                self.move_node(&id, cx, cy);
+            }
+        }
+        self.move_node(node_id, x, y)
+    }
+
+    /// Resize an expanded group's frame to enclose exactly its children
+    pub fn fit_group(&mut self, group_id: &str) -> bool {
+        let Some(bounds) = self.children_bounds(group_id) else { return false };
+        if !self.node(group_id).is_some_and(|n| n.is_frame()) {
+            return false;
+        }
+        self.set_node_rect(group_id, bounds)
+    }
+
+    /// Fit every expanded group to its children, inner groups first
+    pub fn fit_groups(&mut self) {
+        let depth = |graph: &Self, id: &str| {
+            let mut depth = 0;
+            let mut current = graph.node(id).and_then(|n| n.parent.as_deref());
+            while let Some(parent) = current {
+                depth += 1;
+                current = graph.node(parent).and_then(|n| n.parent.as_deref());
+            }
+            depth
+        };
+        let mut frames: Vec<(usize, String)> = self.nodes.iter()
+            .filter(|n| n.is_frame())
+            .map(|n| (depth(self, &n.id), n.id.clone()))
+            .collect();
+        frames.sort_by_key(|frame| std::cmp::Reverse(frame.0));
+        for (_, id) in frames {
+            self.fit_group(&id);
+        }
+    }
+
+    /// Grow the frames around `node_id` so they still enclose it
+    pub fn grow_parents(&mut self, node_id: &str) {
+        let mut current = self.node(node_id).and_then(|n| n.parent.clone());
+        while let Some(id) = current {
+            let frame = self.node(&id).filter(|n| n.is_frame()).map(|n| n.rect());
+            if let (Some(rect), Some(bounds)) = (frame, self.children_bounds(&id)) {
+                let min = DVec2 { x: rect.pos.x.min(bounds.pos.x), y: rect.pos.y.min(bounds.pos.y) };
+                let max = DVec2 {
+                    x: (rect.pos.x + rect.size.x).max(bounds.pos.x + bounds.size.x),
+                    y: (rect.pos.y + rect.size.y).max(bounds.pos.y + bounds.size.y),
+                };
+                let grown = Rect { pos: min, size: DVec2 { x: max.x - min.x, y: max.y - min.y } };
+                if grown != rect {
+                    self.set_node_rect(&id, grown);
+                }
+            }
+            current = self.node(&id).and_then(|n| n.parent.clone());
+        }
+    }
+
+    /// Frame rectangle around a group's children: padding plus room for the header
+    fn children_bounds(&self, group_id: &str) -> Option<Rect> {
+        let mut children = self.nodes.iter().filter(|n| n.parent.as_deref() == Some(group_id));
+        let first = children.next()?.rect();
+        let (mut min, mut max) = (first.pos, DVec2 { x: first.pos.x + first.size.x, y: first.pos.y + first.size.y });
+        for n in children {
+            min.x = min.x.min(n.x);
+            min.y = min.y.min(n.y);
+            max.x = max.x.max(n.x + n.width);
+            max.y = max.y.max(n.y + n.height);
+        }
+        let pos = DVec2 { x: min.x - group::PADDING, y: min.y - group::PADDING - node::HEADER_HEIGHT };
+        Some(Rect {
+            pos,
+            size: DVec2 { x: max.x + group::PADDING - pos.x, y: max.y + group::PADDING - pos.y },
+        })
+    }
+
+    // Id of the collapsed group's port standing in for `port_id` of its member
+    // `node_id`, adding the port to `ports` and `proxies` on first use
+    fn group_port(&self, node_id: &str, port_id: &str, output: bool, ports: &mut Vec<Port>, proxies: &mut Vec<ProxyPort>) -> String {
+        if let Some(proxy) = proxies.iter().find(|p| p.output == output && p.node == node_id && p.node_port == port_id) {
+            return proxy.port.clone();
+        }
+        let source = self.node(node_id).and_then(|n| {
+            let ports = if output { &n.output_ports } else { &n.input_ports };
+            ports.iter().find(|p| p.id == port_id)
+        });
+        let mut port = source.cloned().unwrap_or_else(|| Port::new(port_id));
+        // Ids may contain the separator themselves, so the joined id only has to be unique
+        let base = format!("{}{}{}", node_id, group::PORT_SEPARATOR, port_id);
+        let mut id = base.clone();
+        let mut n = 2;
+        while ports.iter().any(|p| p.id == id) {
+            id = format!("{}_{}", base, n);
+            n += 1;
+        }
+        port.id = id.clone();
+        port.label = format!("{}{}{}", node_id, group::PORT_SEPARATOR, port.label);
+        ports.push(port);
+        proxies.push(ProxyPort { port: id.clone(), output, node: node_id.to_string(), node_port: port_id.to_string() });
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: &str) -> FlowNode {
+        FlowNode::new_dataflow(id, 0.0, 0.0, id, NodeCategory::default(), vec![Port::new("in")], vec![Port::new("out")])
+    }
+
+    fn edge(from: &str, to: &str) -> EdgeConnection {
+        EdgeConnection::new_with_ports(from, "out", to, "in")
+    }
+
+    fn chain(ids: &[&str]) -> FlowGraph {
+        let mut graph = FlowGraph::new();
+        for id in ids {
+            graph.add_node(node(id));
+        }
+        for pair in ids.windows(2) {
+            graph.connect(edge(pair[0], pair[1]));
+        }
+        graph
+    }
+
+    fn assert_index_consistent(graph: &FlowGraph) {
+        for (i, node) in graph.nodes().iter().enumerate() {
+            assert_eq!(graph.node_index(&node.id), Some(i), "index of {}", node.id);
+        }
+        assert_eq!(graph.node_index.len(), graph.node_count());
+    }
+
+    #[test]
+    fn add_node_makes_ids_unique() {
+        let mut graph = FlowGraph::new();
+        assert_eq!(graph.add_node(node("a")), "a");
+        assert_eq!(graph.add_node(node("a")), "a_2");
+        assert_eq!(graph.add_node(node("a")), "a_3");
+        assert_eq!(graph.node_count(), 3);
+        assert!(graph.contains_node("a_2"));
+        assert_index_consistent(&graph);
+    }
+
+    #[test]
+    fn remove_node_drops_its_edges() {
+        let mut graph = chain(&["a", "b", "c"]);
+        graph.connect(edge("a", "c"));
+        let (removed, edges) = graph.remove_node("b").unwrap();
+        assert_eq!(removed.id, "b");
+        assert_eq!(edges, vec![edge("a", "b"), edge("b", "c")]);
+        assert_eq!(graph.edges(), &[edge("a", "c")]);
+        assert!(graph.remove_node("b").is_none());
+        assert_index_consistent(&graph);
+    }
+
+    #[test]
+    fn connect_and_disconnect() {
+        let mut graph = chain(&["a", "b"]);
+        assert_eq!(graph.edge_count(), 1);
+        // Duplicates and dangling endpoints are refused
+        assert_eq!(graph.connect(edge("a", "b")), None);
+        assert_eq!(graph.connect(edge("a", "missing")), None);
+        assert_eq!(graph.connect(edge("b", "a")), Some(1));
+        assert_eq!(graph.find_edge("b", "out", "a", "in"), Some(1));
+
+        assert_eq!(graph.disconnect(0), Some(edge("a", "b")));
+        assert_eq!(graph.disconnect(5), None);
+        assert_eq!(graph.edges(), &[edge("b", "a")]);
+        assert_eq!(graph.connected_edges("a"), vec![0]);
+    }
+
+    #[test]
+    fn rename_node_rewrites_edges() {
+        let mut graph = chain(&["a", "b", "c"]);
+        assert!(graph.rename_node("b", "middle"));
+        assert_eq!(graph.edges(), &[edge("a", "middle"), edge("middle", "c")]);
+        assert!(graph.node("b").is_none());
+        assert_eq!(graph.node("middle").map(|n| n.id.as_str()), Some("middle"));
+        // Taken ids and missing nodes are refused
+        assert!(!graph.rename_node("a", "c"));
+        assert!(!graph.rename_node("b", "x"));
+        assert_index_consistent(&graph);
+    }
+
+    #[test]
+    fn rename_node_rewrites_collapsed_groups() {
+        let mut graph = chain(&["a", "b", "c"]);
+        let inner = graph.group_nodes("inner", "Inner", &["b".to_string()]).unwrap();
+        let outer = graph.group_nodes("outer", "Outer", &[inner.clone(), "c".to_string()]).unwrap();
+        graph.collapse_group(&inner);
+        graph.collapse_group(&outer);
+
+        assert!(graph.rename_node(&outer, "renamed"));
+        let state = graph.node("renamed").and_then(|n| n.group.as_ref()).unwrap();
+        assert!(!state.mentions(&outer));
+        assert!(state.hidden_nodes.iter().all(|n| n.parent.as_deref() == Some("renamed")));
+
+        graph.expand_all();
+        assert!(graph.node(&inner).is_some_and(|n| n.parent.as_deref() == Some("renamed")));
+        assert_eq!(graph.node("c").and_then(|n| n.parent.as_deref()), Some("renamed"));
+        assert_eq!(graph.edges().len(), 2);
+        assert!(graph.edges().iter().all(|e| graph.edge_is_valid(e)));
+        assert_index_consistent(&graph);
+    }
+
+    #[test]
+    fn collapse_and_expand_keep_ids_with_separators() {
+        // "a/b" + "c" and "a" + "b/c" would both be joined into "a/b/c"
+        let mut graph = FlowGraph::new();
+        let mut left = node("a/b");
+        left.output_ports = vec![Port::new("c")];
+        let mut right = node("a");
+        right.output_ports = vec![Port::new("b/c")];
+        graph.add_node(left);
+        graph.add_node(right);
+        graph.add_node(node("sink"));
+        graph.connect(EdgeConnection::new_with_ports("a/b", "c", "sink", "in"));
+        graph.connect(EdgeConnection::new_with_ports("a", "b/c", "sink", "in"));
+        let original = graph.edges().to_vec();
+
+        let group = graph.group_nodes("group", "Group", &["a/b".to_string(), "a".to_string()]).unwrap();
+        assert!(graph.collapse_group(&group));
+        let ports = &graph.node(&group).unwrap().output_ports;
+        assert_eq!(ports.len(), 2);
+        assert_ne!(ports[0].id, ports[1].id);
+
+        assert!(graph.expand_group(&group));
+        assert_eq!(graph.edges(), original.as_slice());
+    }
+
+    #[test]
+    fn index_stays_consistent_after_removals() {
+        let mut graph = chain(&["a", "b", "c", "d", "e"]);
+        graph.remove_node("b");
+        assert_index_consistent(&graph);
+        graph.remove_nodes(&HashSet::from(["a".to_string(), "d".to_string()]));
+        assert_index_consistent(&graph);
+        assert_eq!(graph.nodes().iter().map(|n| n.id.as_str()).collect::<Vec<_>>(), ["c", "e"]);
+        assert!(graph.edges().is_empty());
+        graph.add_node(node("f"));
+        assert_index_consistent(&graph);
+        graph.clear();
+        assert!(graph.is_empty());
+        assert_index_consistent(&graph);
+    }
+}
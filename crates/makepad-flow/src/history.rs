@@ -0,0 +1,346 @@
+//! Undo/redo history
+//!
+//! Every edit of a [`FlowGraph`] is stored as a [`HistoryEntry`]: a short
+//! label plus the [`FlowCommand`]s that turn the graph before the edit into the
+//! graph after it. Each command has an exact inverse, so undo replays the
+//! inverses in reverse order.
+//!
+//! The graph records the commands itself as it is changed, so an edit costs
+//! only what it touches and no copy of the graph is made:
+//!
+//! ```ignore
+//! graph.begin_recording();
+//! graph.move_node("camera", 40.0, 80.0);
+//! history.record_commands("Move Node", graph.end_recording());
+//! history.undo(&mut graph);
+//! ```
+
+use makepad_widgets::*;
+use crate::constants::canvas;
+use crate::graph::*;
+
+/// One reversible change to a graph.
+///
+/// Indices are positions in `FlowGraph::nodes()` / `FlowGraph::edges()` at the
+/// moment the command is applied.
+// Node commands carry whole nodes so they can be undone without looking anything up
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum FlowCommand {
+    /// Insert a node at a position in the draw order
+    AddNode { index: usize, node: FlowNode },
+    /// Take out the node at a position; its edges are separate commands
+    RemoveNode { index: usize, node: FlowNode },
+    /// Change only the position of a node
+    MoveNode { id: String, from: DVec2, to: DVec2 },
+    /// Any other change to a node: style, size, ports, group state
+    UpdateNode { before: FlowNode, after: FlowNode },
+    /// Insert an edge at a position
+    Connect { index: usize, edge: EdgeConnection },
+    /// Take out the edge at a position
+    Disconnect { index: usize, edge: EdgeConnection },
+    /// Restyle or relabel the edge at a position
+    UpdateEdge { index: usize, before: EdgeConnection, after: EdgeConnection },
+}
+
+impl FlowCommand {
+    pub fn apply(&self, graph: &mut FlowGraph) {
+        match self {
+            FlowCommand::AddNode { index, node } => graph.insert_node_at(*index, node.clone()),
+            FlowCommand::RemoveNode { index, .. } => {
+                graph.remove_node_at(*index);
+            }
+            FlowCommand::MoveNode { id, to, .. } => {
+                graph.move_node(id, to.x, to.y);
+            }
+            FlowCommand::UpdateNode { before, after } => {
+                graph.update_node(&before.id, |node| *node = after.clone());
+            }
+            FlowCommand::Connect { index, edge } => graph.insert_edge(*index, edge.clone()),
+            FlowCommand::Disconnect { index, .. } => {
+                graph.disconnect(*index);
+            }
+            FlowCommand::UpdateEdge { index, after, .. } => {
+                graph.update_edge(*index, |edge| *edge = after.clone());
+            }
+        }
+    }
+
+    /// The command that undoes this one
+    pub fn inverse(&self) -> FlowCommand {
+        match self.clone() {
+            FlowCommand::AddNode { index, node } => FlowCommand::RemoveNode { index, node },
+            FlowCommand::RemoveNode { index, node } => FlowCommand::AddNode { index, node },
+            FlowCommand::MoveNode { id, from, to } => FlowCommand::MoveNode { id, from: to, to: from },
+            FlowCommand::UpdateNode { before, after } => FlowCommand::UpdateNode { before: after, after: before },
+            FlowCommand::Connect { index, edge } => FlowCommand::Disconnect { index, edge },
+            FlowCommand::Disconnect { index, edge } => FlowCommand::Connect { index, edge },
+            FlowCommand::UpdateEdge { index, before, after } => FlowCommand::UpdateEdge { index, before: after, after: before },
+        }
+    }
+}
+
+/// One undo step
+#[derive(Clone, Debug)]
+pub struct HistoryEntry {
+    /// Shown to the user, e.g. "Undo Move Node"
+    pub label: String,
+    pub commands: Vec<FlowCommand>,
+}
+
+impl HistoryEntry {
+    pub fn redo(&self, graph: &mut FlowGraph) {
+        for command in &self.commands {
+            command.apply(graph);
+        }
+    }
+
+    pub fn undo(&self, graph: &mut FlowGraph) {
+        for command in self.commands.iter().rev() {
+            command.inverse().apply(graph);
+        }
+    }
+
+    /// The commands `undo` applies, in order
+    pub fn inverse_commands(&self) -> Vec<FlowCommand> {
+        self.commands.iter().rev().map(FlowCommand::inverse).collect()
+    }
+}
+
+/// Undo and redo stacks of [`HistoryEntry`]s, bounded to a number of steps
+#[derive(Clone, Debug)]
+pub struct FlowHistory {
+    undo: Vec<HistoryEntry>,
+    redo: Vec<HistoryEntry>,
+    limit: usize,
+}
+
+impl Default for FlowHistory {
+    fn default() -> Self {
+        Self::new(canvas::UNDO_STACK_SIZE)
+    }
+}
+
+impl FlowHistory {
+    /// History keeping at most `limit` undo steps
+    pub fn new(limit: usize) -> Self {
+        Self { undo: Vec::new(), redo: Vec::new(), limit: limit.max(1) }
+    }
+
+    /// Record already applied commands, e.g. from [`FlowGraph::end_recording`],
+    /// as one step. Returns `None`, recording nothing, if there are none.
+    pub fn record_commands(&mut self, label: &str, commands: Vec<FlowCommand>) -> Option<&HistoryEntry> {
+        if commands.is_empty() {
+            return None;
+        }
+        self.push(HistoryEntry { label: label.to_string(), commands });
+        self.undo.last()
+    }
+
+    /// Add a step that has already been applied; clears the redo stack
+    pub fn push(&mut self, entry: HistoryEntry) {
+        self.undo.push(entry);
+        if self.undo.len() > self.limit {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Revert the last step and return it, or `None` if there is nothing to undo
+    pub fn undo(&mut self, graph: &mut FlowGraph) -> Option<&HistoryEntry> {
+        let entry = self.undo.pop()?;
+        entry.undo(graph);
+        self.redo.push(entry);
+        self.redo.last()
+    }
+
+    /// Reapply the last undone step and return it, or `None` if there is nothing to redo
+    pub fn redo(&mut self, graph: &mut FlowGraph) -> Option<&HistoryEntry> {
+        let entry = self.redo.pop()?;
+        entry.redo(graph);
+        self.undo.push(entry);
+        self.undo.last()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Label of the step `undo` would revert
+    pub fn undo_label(&self) -> Option<&str> {
+        self.undo.last().map(|e| e.label.as_str())
+    }
+
+    /// Label of the step `redo` would reapply
+    pub fn redo_label(&self) -> Option<&str> {
+        self.redo.last().map(|e| e.label.as_str())
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: &str, x: f64, y: f64) -> FlowNode {
+        FlowNode::new_dataflow(id, x, y, id, NodeCategory::default(), vec![Port::new("in")], vec![Port::new("out")])
+    }
+
+    fn edge(from: &str, to: &str) -> EdgeConnection {
+        EdgeConnection::new_with_ports(from, "out", to, "in")
+    }
+
+    fn sample() -> FlowGraph {
+        FlowGraph::from_parts(
+            vec![node("a", 0.0, 0.0), node("b", 200.0, 0.0), node("c", 400.0, 0.0)],
+            vec![edge("a", "b"), edge("b", "c")],
+        )
+    }
+
+    fn parts(graph: &FlowGraph) -> (Vec<FlowNode>, Vec<EdgeConnection>) {
+        (graph.nodes().to_vec(), graph.edges().to_vec())
+    }
+
+    /// Record `edit` on a copy of `before`, then undo back to `before` and redo to the result
+    fn assert_round_trip(edit: impl FnOnce(&mut FlowGraph)) {
+        let before = sample();
+        let mut graph = before.clone();
+        graph.begin_recording();
+        edit(&mut graph);
+        let commands = graph.end_recording();
+        assert!(!commands.is_empty());
+        let after = graph.clone();
+
+        // The commands alone reproduce the edit
+        let mut replayed = before.clone();
+        for command in &commands {
+            command.apply(&mut replayed);
+        }
+        assert_eq!(parts(&replayed), parts(&after));
+
+        let mut history = FlowHistory::default();
+        history.record_commands("Edit", commands);
+        history.undo(&mut graph);
+        assert_eq!(parts(&graph), parts(&before));
+        history.redo(&mut graph);
+        assert_eq!(parts(&graph), parts(&after));
+    }
+
+    #[test]
+    fn add_node_round_trips() {
+        assert_round_trip(|graph| {
+            graph.add_node(node("d", 600.0, 0.0));
+        });
+    }
+
+    #[test]
+    fn remove_node_round_trips() {
+        assert_round_trip(|graph| {
+            graph.remove_node("b");
+        });
+    }
+
+    #[test]
+    fn move_node_round_trips() {
+        assert_round_trip(|graph| {
+            graph.move_node("c", 40.0, 80.0);
+        });
+        let mut graph = sample();
+        graph.begin_recording();
+        graph.move_node("c", 40.0, 80.0);
+        assert!(matches!(graph.end_recording().as_slice(), [FlowCommand::MoveNode { .. }]));
+    }
+
+    #[test]
+    fn connect_round_trips() {
+        assert_round_trip(|graph| {
+            graph.connect(edge("a", "c"));
+        });
+    }
+
+    #[test]
+    fn disconnect_round_trips() {
+        assert_round_trip(|graph| {
+            graph.disconnect(0);
+        });
+    }
+
+    #[test]
+    fn mixed_batch_round_trips() {
+        assert_round_trip(|graph| {
+            graph.add_node(node("d", 600.0, 0.0));
+            graph.connect(edge("c", "d"));
+            graph.move_node("a", 10.0, 20.0);
+            graph.disconnect(0);
+            graph.update_node("b", |n| n.title = "Renamed".to_string());
+            graph.remove_node("a");
+            graph.group_nodes("group", "Group", &["c".to_string(), "d".to_string()]);
+        });
+    }
+
+    #[test]
+    fn repeated_moves_record_one_command() {
+        let before = sample();
+        let mut graph = before.clone();
+        graph.begin_recording();
+        for i in 0..10 {
+            graph.move_node("a", i as f64, 0.0);
+        }
+        graph.connect(edge("a", "c"));
+        graph.remove_node("b");
+        let recorded = graph.end_recording();
+        // Repeated moves of one node are merged
+        assert_eq!(recorded.iter().filter(|c| matches!(c, FlowCommand::MoveNode { .. })).count(), 1);
+
+        let mut history = FlowHistory::default();
+        let after = graph.clone();
+        history.record_commands("Edit", recorded);
+        history.undo(&mut graph);
+        assert_eq!(parts(&graph), parts(&before));
+        history.redo(&mut graph);
+        assert_eq!(parts(&graph), parts(&after));
+    }
+
+    #[test]
+    fn cancelled_reconnect_records_nothing() {
+        let mut graph = sample();
+        let mut history = FlowHistory::default();
+        history.record_commands("Connect", vec![FlowCommand::Connect { index: 0, edge: edge("a", "b") }]);
+
+        // Grab the edge, drag while the simulation moves a node, drop it back
+        graph.begin_recording();
+        let taken = graph.disconnect(1).unwrap();
+        graph.move_node("a", 5.0, 5.0);
+        graph.insert_edge(1, taken);
+        let recorded = graph.end_recording();
+        assert!(matches!(recorded.as_slice(), [FlowCommand::MoveNode { .. }]));
+
+        // Without the move nothing is left to record
+        graph.begin_recording();
+        let taken = graph.disconnect(0).unwrap();
+        graph.insert_edge(0, taken);
+        assert!(history.record_commands("Reconnect Edge", graph.end_recording()).is_none());
+        assert_eq!(history.undo_label(), Some("Connect"));
+        history.undo(&mut graph);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn unchanged_graph_records_nothing() {
+        let mut graph = sample();
+        let mut history = FlowHistory::default();
+        graph.begin_recording();
+        graph.move_node("a", 10.0, 10.0);
+        graph.move_node("a", 0.0, 0.0);
+        assert!(history.record_commands("Nothing", graph.end_recording()).is_none());
+        assert!(!history.can_undo());
+    }
+}
@@ -0,0 +1,781 @@
+//! Automatic graph layout
+//!
+//! [`LayeredLayout`] is a Sugiyama-style layered layout for directed graphs:
+//!
+//! 1. cycles are broken by reversing the back edges of a depth-first search
+//! 2. nodes are assigned to layers by longest path from the sources, and edges
+//!    spanning several layers get invisible dummy nodes
+//! 3. nodes are ordered within each layer by barycenter sweeps that take port
+//!    order into account, keeping the order with the fewest crossings
+//! 4. nodes are positioned so connected ports line up as far as the spacing allows
+//!
+//! [`ForceLayout`] is a force-directed layout for graphs without a clear flow
+//! direction (feedback loops, hubs). Nodes repel each other, edges act as
+//! springs, and pinned nodes stay where they are. It can run to completion in
+//! one call or step by step through a [`ForceSimulation`] for a live view.
+//!
+//! Both layouts only read and write node positions, so they work on any
+//! [`FlowGraph`] whether or not it is shown on a canvas.
+
+use makepad_widgets::*;
+use std::collections::{HashMap, HashSet};
+use crate::graph::*;
+
+/// Direction in which edges flow from layer to layer
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum LayoutDirection {
+    #[default]
+    LeftToRight,
+    RightToLeft,
+    TopToBottom,
+    BottomToTop,
+}
+
+impl LayoutDirection {
+    fn is_horizontal(&self) -> bool {
+        matches!(self, LayoutDirection::LeftToRight | LayoutDirection::RightToLeft)
+    }
+}
+
+/// Options for the layered layout
+#[derive(Clone, Debug)]
+pub struct LayeredLayout {
+    pub direction: LayoutDirection,
+    /// Gap between neighbouring layers
+    pub layer_spacing: f64,
+    /// Gap between neighbouring nodes in the same layer
+    pub node_spacing: f64,
+    /// Number of down-and-up barycenter sweeps used to reduce crossings
+    pub sweeps: usize,
+    /// Top-left corner of the laid out graph
+    pub origin: DVec2,
+}
+
+impl Default for LayeredLayout {
+    fn default() -> Self {
+        Self {
+            direction: LayoutDirection::LeftToRight,
+            layer_spacing: 120.0,
+            node_spacing: 20.0,
+            sweeps: 8,
+            origin: DVec2 { x: 50.0, y: 50.0 },
+        }
+    }
+}
+
+// A node or dummy node in the layered graph
+struct Item {
+    node: Option<usize>, // graph node index; None for dummies
+    along: f64,          // size along the layer (perpendicular to edge flow)
+    across: f64,         // size across the layer (in the direction of edge flow)
+    layer: usize,
+}
+
+// Edge between consecutive layers. Offsets are port positions measured from
+// the start of the node along the layer.
+#[derive(Clone, Copy)]
+struct LayerEdge {
+    from: usize,
+    to: usize,
+    from_offset: f64,
+    to_offset: f64,
+}
+
+impl LayeredLayout {
+    /// Move every node of `graph` to its layered position
+    pub fn apply(&self, graph: &mut FlowGraph) {
+        if graph.is_empty() {
+            return;
+        }
+        let horizontal = self.direction.is_horizontal();
+
+        // Group frames are not placed themselves; they are refitted around their children
+        let mut item_of = vec![None; graph.node_count()];
+        let mut items: Vec<Item> = Vec::new();
+        for (i, node) in graph.nodes().iter().enumerate().filter(|(_, n)| !n.is_frame()) {
+            item_of[i] = Some(items.len());
+            items.push(Item {
+                node: Some(i),
+                along: if horizontal { node.height } else { node.width },
+                across: if horizontal { node.width } else { node.height },
+                layer: 0,
+            });
+        }
+        if items.is_empty() {
+            return;
+        }
+
+        let mut edges = Vec::new();
+        for edge in graph.edges() {
+            let (Some(from), Some(to)) = (graph.node_index(&edge.from_node), graph.node_index(&edge.to_node)) else {
+                continue;
+            };
+            let (Some(from_item), Some(to_item)) = (item_of[from], item_of[to]) else {
+                continue;
+            };
+            if from == to {
+                continue;
+            }
+            let nodes = graph.nodes();
+            edges.push(LayerEdge {
+                from: from_item,
+                to: to_item,
+                from_offset: port_offset(&nodes[from], &edge.from_port, true, horizontal),
+                to_offset: port_offset(&nodes[to], &edge.to_port, false, horizontal),
+            });
+        }
+
+        // 1. Break cycles by reversing back edges
+        let reversed = back_edges(items.len(), &edges);
+        for (edge, reversed) in edges.iter_mut().zip(reversed) {
+            if reversed {
+                *edge = LayerEdge { from: edge.to, to: edge.from, from_offset: edge.to_offset, to_offset: edge.from_offset };
+            }
+        }
+
+        // 2. Longest-path layering, then split long edges with dummies
+        for (i, layer) in longest_path_layers(items.len(), &edges).into_iter().enumerate() {
+            items[i].layer = layer;
+        }
+        let mut layer_edges = Vec::with_capacity(edges.len());
+        for edge in &edges {
+            let (from_layer, to_layer) = (items[edge.from].layer, items[edge.to].layer);
+            let mut prev = edge.from;
+            let mut prev_offset = edge.from_offset;
+            for layer in from_layer + 1..to_layer {
+                let dummy = items.len();
+                items.push(Item { node: None, along: 0.0, across: 0.0, layer });
+                layer_edges.push(LayerEdge { from: prev, to: dummy, from_offset: prev_offset, to_offset: 0.0 });
+                prev = dummy;
+                prev_offset = 0.0;
+            }
+            layer_edges.push(LayerEdge { from: prev, to: edge.to, from_offset: prev_offset, to_offset: edge.to_offset });
+        }
+
+        let layer_count = items.iter().map(|it| it.layer).max().unwrap_or(0) + 1;
+        let mut layers: Vec<Vec<usize>> = vec![Vec::new(); layer_count];
+        for (i, item) in items.iter().enumerate() {
+            layers[item.layer].push(i);
+        }
+
+        // 3. Crossing reduction
+        self.order_layers(&items, &layer_edges, &mut layers);
+
+        // 4. Coordinates
+        let centers = self.place_along(&items, &layer_edges, &layers);
+        let mut layer_pos = Vec::with_capacity(layer_count);
+        let mut pos = 0.0;
+        for layer in &layers {
+            layer_pos.push(pos);
+            let depth = layer.iter().map(|&i| items[i].across).fold(0.0, f64::max);
+            pos += depth + self.layer_spacing;
+        }
+        let extent = pos - self.layer_spacing;
+
+        let mut placed = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            let Some(node_index) = item.node else { continue };
+            let start_along = centers[i] - item.along / 2.0;
+            let start_across = match self.direction {
+                LayoutDirection::LeftToRight | LayoutDirection::TopToBottom => layer_pos[item.layer],
+                LayoutDirection::RightToLeft | LayoutDirection::BottomToTop => extent - layer_pos[item.layer] - item.across,
+            };
+            let (x, y) = if horizontal { (start_across, start_along) } else { (start_along, start_across) };
+            placed.push((node_index, x, y));
+        }
+
+        // Shift everything so the top-left corner lands on `origin`
+        let min_x = placed.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+        let min_y = placed.iter().map(|p| p.2).fold(f64::INFINITY, f64::min);
+        let ids: Vec<String> = graph.nodes().iter().map(|n| n.id.clone()).collect();
+        for (node_index, x, y) in placed {
+            graph.move_node(&ids[node_index], x - min_x + self.origin.x, y - min_y + self.origin.y);
+        }
+        graph.fit_groups();
+    }
+
+    /// Barycenter sweeps, keeping the ordering with the fewest crossings
+    fn order_layers(&self, items: &[Item], edges: &[LayerEdge], layers: &mut [Vec<usize>]) {
+        let fraction = |item: usize, offset: f64| {
+            let along = items[item].along;
+            if along > 0.0 { offset / along } else { 0.5 }
+        };
+
+        let mut position = vec![0.0; items.len()];
+        let update_positions = |layers: &[Vec<usize>], position: &mut Vec<f64>| {
+            for layer in layers {
+                for (k, &i) in layer.iter().enumerate() {
+                    position[i] = k as f64;
+                }
+            }
+        };
+        update_positions(layers, &mut position);
+
+        let mut best = layers.to_vec();
+        let mut best_crossings = count_crossings(edges, &position, items, &fraction);
+        for _ in 0..self.sweeps {
+            if best_crossings == 0 {
+                break;
+            }
+            // Down: order each layer by where its incoming edges start
+            for layer in layers.iter_mut().skip(1) {
+                let keys = barycenters(layer, edges, &position, |e, v| {
+                    (e.to == v).then(|| position[e.from] + fraction(e.from, e.from_offset))
+                });
+                sort_by_keys(layer, &keys);
+                for (k, &i) in layer.iter().enumerate() {
+                    position[i] = k as f64;
+                }
+            }
+            // Up: order each layer by where its outgoing edges end
+            for l in (0..layers.len().saturating_sub(1)).rev() {
+                let keys = barycenters(&layers[l], edges, &position, |e, v| {
+                    (e.from == v).then(|| position[e.to] + fraction(e.to, e.to_offset))
+                });
+                sort_by_keys(&mut layers[l], &keys);
+                for (k, &i) in layers[l].iter().enumerate() {
+                    position[i] = k as f64;
+                }
+            }
+            let crossings = count_crossings(edges, &position, items, &fraction);
+            if crossings < best_crossings {
+                best_crossings = crossings;
+                best = layers.to_vec();
+            }
+        }
+        layers.clone_from_slice(&best);
+    }
+
+    /// Centre of every item along its layer, pulling connected ports into line
+    fn place_along(&self, items: &[Item], edges: &[LayerEdge], layers: &[Vec<usize>]) -> Vec<f64> {
+        let mut center = vec![0.0; items.len()];
+        for layer in layers {
+            let desired = vec![0.0; layer.len()];
+            let placed = self.pack(items, layer, &desired);
+            for (&i, c) in layer.iter().zip(placed) {
+                center[i] = c;
+            }
+        }
+
+        // Alternate passes aligning each layer with the one before it, then the one after it
+        for pass in 0..4 {
+            let downward = pass % 2 == 0;
+            let order: Vec<usize> = if downward { (1..layers.len()).collect() } else { (0..layers.len().saturating_sub(1)).rev().collect() };
+            for l in order {
+                let desired: Vec<f64> = layers[l].iter().map(|&v| {
+                    let mut sum = 0.0;
+                    let mut count = 0;
+                    for e in edges {
+                        // Where v's centre must be for this edge to run straight
+                        let target = if downward && e.to == v {
+                            center[e.from] - items[e.from].along / 2.0 + e.from_offset - (e.to_offset - items[v].along / 2.0)
+                        } else if !downward && e.from == v {
+                            center[e.to] - items[e.to].along / 2.0 + e.to_offset - (e.from_offset - items[v].along / 2.0)
+                        } else {
+                            continue;
+                        };
+                        sum += target;
+                        count += 1;
+                    }
+                    if count > 0 { sum / count as f64 } else { center[v] }
+                }).collect();
+                for (&i, c) in layers[l].iter().zip(self.pack(items, &layers[l], &desired)) {
+                    center[i] = c;
+                }
+            }
+        }
+        center
+    }
+
+    /// Closest centres to `desired` (least squares) that keep the layer's order
+    /// and spacing. Isotonic regression by pool-adjacent-violators.
+    fn pack(&self, items: &[Item], layer: &[usize], desired: &[f64]) -> Vec<f64> {
+        // Minimum distance from the first centre to each centre
+        let mut offsets = Vec::with_capacity(layer.len());
+        let mut acc = 0.0;
+        for (k, &i) in layer.iter().enumerate() {
+            if k > 0 {
+                let prev = &items[layer[k - 1]];
+                let gap = if prev.node.is_none() && items[i].node.is_none() { self.node_spacing / 2.0 } else { self.node_spacing };
+                acc += (prev.along + items[i].along) / 2.0 + gap;
+            }
+            offsets.push(acc);
+        }
+
+        // Blocks of (sum, count) whose means are non-decreasing
+        let mut blocks: Vec<(f64, usize)> = Vec::new();
+        for (d, o) in desired.iter().zip(&offsets) {
+            blocks.push((d - o, 1));
+            while blocks.len() >= 2 {
+                let (s2, c2) = blocks[blocks.len() - 1];
+                let (s1, c1) = blocks[blocks.len() - 2];
+                if s1 / c1 as f64 <= s2 / c2 as f64 {
+                    break;
+                }
+                blocks.pop();
+                *blocks.last_mut().unwrap() = (s1 + s2, c1 + c2);
+            }
+        }
+        let mut out = Vec::with_capacity(layer.len());
+        for (sum, count) in blocks {
+            for _ in 0..count {
+                out.push(sum / count as f64 + offsets[out.len()]);
+            }
+        }
+        out
+    }
+}
+
+/// Options for the force-directed layout
+#[derive(Clone, Debug)]
+pub struct ForceLayout {
+    /// Strength of the push between every pair of nodes
+    pub repulsion: f64,
+    /// Preferred gap between the borders of connected nodes
+    pub spring_length: f64,
+    /// How strongly edges pull their nodes towards `spring_length`
+    pub spring_strength: f64,
+    /// Pull towards the centre of the graph, keeps unconnected parts together
+    pub gravity: f64,
+    /// Push edge targets to the right of their sources, matching the port
+    /// sides. 0.0 disables it.
+    pub flow_strength: f64,
+    /// Fraction of the velocity kept from one step to the next
+    pub damping: f64,
+    /// Largest distance a node moves in one step, before cooling
+    pub max_step: f64,
+    /// Steps until the simulation has cooled down
+    pub iterations: usize,
+}
+
+impl Default for ForceLayout {
+    fn default() -> Self {
+        Self {
+            repulsion: 60000.0,
+            spring_length: 80.0,
+            spring_strength: 0.05,
+            gravity: 0.01,
+            flow_strength: 0.1,
+            damping: 0.6,
+            max_step: 40.0,
+            iterations: 300,
+        }
+    }
+}
+
+impl ForceLayout {
+    /// Run the simulation until it settles. Pinned nodes are not moved.
+    pub fn apply(&self, graph: &mut FlowGraph) {
+        let mut simulation = ForceSimulation::new(self.clone());
+        while simulation.step(graph, &HashSet::new()) {}
+    }
+}
+
+// Temperature below which the simulation counts as settled
+const MIN_TEMPERATURE: f64 = 0.01;
+// A step in which no node moves further than this settles the simulation
+const SETTLE_DISTANCE: f64 = 0.1;
+// Closest two node borders are treated as being when computing repulsion
+const MIN_GAP: f64 = 10.0;
+
+/// Incremental force-directed layout.
+///
+/// Call [`ForceSimulation::step`] once per frame to animate the layout. The
+/// simulation cools down over `options.iterations` steps; moving nodes by hand
+/// while it runs is fine, and [`ForceSimulation::reheat`] lets it react again
+/// after it settled.
+#[derive(Clone, Debug)]
+pub struct ForceSimulation {
+    pub options: ForceLayout,
+    velocity: HashMap<String, DVec2>,
+    temperature: f64,
+}
+
+impl ForceSimulation {
+    pub fn new(options: ForceLayout) -> Self {
+        Self { options, velocity: HashMap::new(), temperature: 1.0 }
+    }
+
+    pub fn is_settled(&self) -> bool {
+        self.temperature < MIN_TEMPERATURE
+    }
+
+    /// Raise the temperature to at least `temperature` (0.0..=1.0)
+    pub fn reheat(&mut self, temperature: f64) {
+        self.temperature = self.temperature.max(temperature.min(1.0));
+    }
+
+    /// Advance the simulation by one step. Pinned nodes and nodes in `held`
+    /// (e.g. the ones being dragged) act on the others but do not move.
+    /// Returns false once the simulation has settled.
+    pub fn step(&mut self, graph: &mut FlowGraph, held: &HashSet<String>) -> bool {
+        if self.is_settled() {
+            return false;
+        }
+        let options = &self.options;
+        let nodes = graph.nodes();
+        let n = nodes.len();
+        let centers: Vec<DVec2> = nodes.iter()
+            .map(|node| DVec2 { x: node.x + node.width / 2.0, y: node.y + node.height / 2.0 })
+            .collect();
+        let mut force = vec![DVec2::default(); n];
+
+        // Group frames take no part; they are refitted around their children
+        let frame: Vec<bool> = nodes.iter().map(|node| node.is_frame()).collect();
+
+        // Every pair of nodes repels, growing sharply as their borders meet
+        for i in (0..n).filter(|&i| !frame[i]) {
+            for j in (i + 1..n).filter(|&j| !frame[j]) {
+                let (dir, _) = direction(centers[i], centers[j], i + j);
+                let gap = border_gap(&nodes[i], &nodes[j], centers[i], centers[j]).max(MIN_GAP);
+                let f = options.repulsion / (gap * gap);
+                force[i].x -= dir.x * f;
+                force[i].y -= dir.y * f;
+                force[j].x += dir.x * f;
+                force[j].y += dir.y * f;
+            }
+        }
+
+        // Connected nodes are pulled to `spring_length` apart, once per pair
+        // however many edges join them, and targets are pushed to the right
+        let mut pairs = HashSet::new();
+        for edge in graph.edges() {
+            let (Some(u), Some(v)) = (graph.node_index(&edge.from_node), graph.node_index(&edge.to_node)) else {
+                continue;
+            };
+            if u == v || !pairs.insert((u.min(v), u.max(v))) {
+                continue;
+            }
+            let (dir, _) = direction(centers[u], centers[v], u + v);
+            let gap = border_gap(&nodes[u], &nodes[v], centers[u], centers[v]);
+            let f = options.spring_strength * (gap - options.spring_length);
+            force[u].x += dir.x * f;
+            force[u].y += dir.y * f;
+            force[v].x -= dir.x * f;
+            force[v].y -= dir.y * f;
+
+            let min_x = centers[u].x + (nodes[u].width + nodes[v].width) / 2.0 + options.spring_length;
+            if centers[v].x < min_x {
+                let f = options.flow_strength * (min_x - centers[v].x);
+                force[u].x -= f;
+                force[v].x += f;
+            }
+        }
+
+        // Gravity towards the centroid
+        let placed = frame.iter().filter(|f| !**f).count();
+        let centroid = centers.iter().zip(&frame)
+            .filter(|(_, f)| !**f)
+            .fold(DVec2::default(), |acc, (c, _)| DVec2 { x: acc.x + c.x, y: acc.y + c.y });
+        let centroid = DVec2 { x: centroid.x / placed.max(1) as f64, y: centroid.y / placed.max(1) as f64 };
+        for (f, c) in force.iter_mut().zip(&centers) {
+            f.x += (centroid.x - c.x) * options.gravity;
+            f.y += (centroid.y - c.y) * options.gravity;
+        }
+
+        let limit = options.max_step * self.temperature;
+        let mut moves = Vec::new();
+        let mut max_moved: f64 = 0.0;
+        for (node, f) in nodes.iter().zip(&force) {
+            if node.pinned || node.is_frame() || held.contains(&node.id) {
+                self.velocity.remove(&node.id);
+                continue;
+            }
+            let v = self.velocity.entry(node.id.clone()).or_default();
+            v.x = (v.x + f.x) * options.damping;
+            v.y = (v.y + f.y) * options.damping;
+            let speed = v.x.hypot(v.y);
+            if speed > limit {
+                v.x *= limit / speed;
+                v.y *= limit / speed;
+            }
+            max_moved = max_moved.max(speed.min(limit));
+            moves.push((node.id.clone(), node.x + v.x, node.y + v.y));
+        }
+        for (id, x, y) in moves {
+            graph.move_node(&id, x, y);
+        }
+        graph.fit_groups();
+
+        self.temperature *= MIN_TEMPERATURE.powf(1.0 / self.options.iterations.max(1) as f64);
+        if max_moved < SETTLE_DISTANCE {
+            self.temperature = 0.0;
+        }
+        !self.is_settled()
+    }
+}
+
+/// Distance between the borders of two nodes, 0.0 when they overlap
+fn border_gap(a: &FlowNode, b: &FlowNode, center_a: DVec2, center_b: DVec2) -> f64 {
+    let gx = ((center_b.x - center_a.x).abs() - (a.width + b.width) / 2.0).max(0.0);
+    let gy = ((center_b.y - center_a.y).abs() - (a.height + b.height) / 2.0).max(0.0);
+    gx.hypot(gy)
+}
+
+/// Unit vector from `a` to `b` and the distance between them. Coincident
+/// points get a direction derived from `seed` so they separate.
+fn direction(a: DVec2, b: DVec2, seed: usize) -> (DVec2, f64) {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let dist = dx.hypot(dy);
+    if dist > 0.01 {
+        (DVec2 { x: dx / dist, y: dy / dist }, dist)
+    } else {
+        let angle = seed as f64 * 2.399; // golden angle
+        (DVec2 { x: angle.cos(), y: angle.sin() }, 0.0)
+    }
+}
+
+/// Offset of a port from the start of its node along the layer
+fn port_offset(node: &FlowNode, port_id: &str, output: bool, horizontal: bool) -> f64 {
+    if !horizontal {
+        // Ports sit on the left and right sides, so in vertical layouts use the centre
+        return node.width / 2.0;
+    }
+    let anchor = if output { node.output_anchor(port_id) } else { node.input_anchor(port_id) };
+    anchor.y - node.y
+}
+
+/// Edges that point back up a depth-first search tree; reversing them makes the graph acyclic
+fn back_edges(node_count: usize, edges: &[LayerEdge]) -> Vec<bool> {
+    let mut outgoing = vec![Vec::new(); node_count];
+    let mut has_incoming = vec![false; node_count];
+    for (i, e) in edges.iter().enumerate() {
+        outgoing[e.from].push(i);
+        has_incoming[e.to] = true;
+    }
+
+    // 0 = unvisited, 1 = on the DFS stack, 2 = finished
+    let mut state = vec![0u8; node_count];
+    let mut reversed = vec![false; edges.len()];
+    // Start from sources so natural flow direction is kept
+    let starts = (0..node_count).filter(|&n| !has_incoming[n]).chain(0..node_count);
+    for start in starts {
+        if state[start] != 0 {
+            continue;
+        }
+        let mut stack = vec![(start, 0usize)];
+        state[start] = 1;
+        while let Some(&mut (node, ref mut next)) = stack.last_mut() {
+            if let Some(&edge) = outgoing[node].get(*next) {
+                *next += 1;
+                let to = edges[edge].to;
+                match state[to] {
+                    0 => {
+                        state[to] = 1;
+                        stack.push((to, 0));
+                    }
+                    1 => reversed[edge] = true,
+                    _ => {}
+                }
+            } else {
+                state[node] = 2;
+                stack.pop();
+            }
+        }
+    }
+    reversed
+}
+
+/// Layer of each node: the length of the longest path reaching it (graph must be acyclic)
+fn longest_path_layers(node_count: usize, edges: &[LayerEdge]) -> Vec<usize> {
+    let mut outgoing = vec![Vec::new(); node_count];
+    let mut in_degree = vec![0usize; node_count];
+    for e in edges {
+        outgoing[e.from].push(e.to);
+        in_degree[e.to] += 1;
+    }
+    let mut layer = vec![0usize; node_count];
+    let mut ready: Vec<usize> = (0..node_count).filter(|&n| in_degree[n] == 0).collect();
+    while let Some(n) = ready.pop() {
+        for &m in &outgoing[n] {
+            layer[m] = layer[m].max(layer[n] + 1);
+            in_degree[m] -= 1;
+            if in_degree[m] == 0 {
+                ready.push(m);
+            }
+        }
+    }
+    layer
+}
+
+/// Mean of `value(edge, node)` over the edges it is defined for; nodes without
+/// such edges keep their current position
+fn barycenters(layer: &[usize], edges: &[LayerEdge], position: &[f64], value: impl Fn(&LayerEdge, usize) -> Option<f64>) -> Vec<f64> {
+    layer.iter().map(|&v| {
+        let (sum, count) = edges.iter()
+            .filter_map(|e| value(e, v))
+            .fold((0.0, 0usize), |(s, c), x| (s + x, c + 1));
+        if count > 0 { sum / count as f64 } else { position[v] }
+    }).collect()
+}
+
+fn sort_by_keys(layer: &mut Vec<usize>, keys: &[f64]) {
+    let mut keyed: Vec<(f64, usize)> = keys.iter().copied().zip(layer.iter().copied()).collect();
+    keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+    *layer = keyed.into_iter().map(|(_, i)| i).collect();
+}
+
+/// Number of pairs of edges between the same two layers that cross
+fn count_crossings(edges: &[LayerEdge], position: &[f64], items: &[Item], fraction: &impl Fn(usize, f64) -> f64) -> usize {
+    let mut by_layer: Vec<Vec<(f64, f64)>> = Vec::new();
+    for e in edges {
+        let layer = items[e.from].layer;
+        if by_layer.len() <= layer {
+            by_layer.resize(layer + 1, Vec::new());
+        }
+        by_layer[layer].push((
+            position[e.from] + fraction(e.from, e.from_offset),
+            position[e.to] + fraction(e.to, e.to_offset),
+        ));
+    }
+    let mut crossings = 0;
+    for segs in &by_layer {
+        for (k, a) in segs.iter().enumerate() {
+            for b in &segs[k + 1..] {
+                if (a.0 - b.0) * (a.1 - b.1) < 0.0 {
+                    crossings += 1;
+                }
+            }
+        }
+    }
+    crossings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: &str, x: f64, y: f64) -> FlowNode {
+        FlowNode::new_dataflow(id, x, y, id, NodeCategory::default(), vec![Port::new("in")], vec![Port::new("out")])
+    }
+
+    fn edge(from: &str, to: &str) -> EdgeConnection {
+        EdgeConnection::new_with_ports(from, "out", to, "in")
+    }
+
+    fn layer_edge(from: usize, to: usize) -> LayerEdge {
+        LayerEdge { from, to, from_offset: 20.0, to_offset: 20.0 }
+    }
+
+    fn item(layer: usize) -> Item {
+        Item { node: Some(0), along: 40.0, across: 100.0, layer }
+    }
+
+    fn frame(id: &str) -> FlowNode {
+        let mut frame = node(id, 0.0, 0.0);
+        frame.group = Some(GroupState::default());
+        frame
+    }
+
+    #[test]
+    fn back_edges_make_a_cycle_acyclic() {
+        // 0 -> 1 -> 2 -> 0
+        let edges = vec![layer_edge(0, 1), layer_edge(1, 2), layer_edge(2, 0)];
+        assert_eq!(back_edges(3, &edges), vec![false, false, true]);
+
+        let forward: Vec<LayerEdge> = edges[..2].to_vec();
+        assert_eq!(longest_path_layers(3, &forward), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn longest_path_puts_nodes_after_their_furthest_source() {
+        // 0 -> 1 -> 2 and a shortcut 0 -> 2
+        let edges = vec![layer_edge(0, 1), layer_edge(1, 2), layer_edge(0, 2)];
+        assert_eq!(longest_path_layers(3, &edges), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn cyclic_graph_is_laid_out_in_layers() {
+        let mut graph = FlowGraph::from_parts(
+            vec![node("a", 0.0, 0.0), node("b", 0.0, 0.0), node("c", 0.0, 0.0)],
+            vec![edge("a", "b"), edge("b", "c"), edge("c", "a")],
+        );
+        LayeredLayout::default().apply(&mut graph);
+
+        let x = |id: &str| graph.node(id).unwrap().x;
+        assert!(x("a") < x("b") && x("b") < x("c"));
+        // Each layer starts a node width plus the layer spacing after the previous one
+        let step = graph.node("a").unwrap().width + LayeredLayout::default().layer_spacing;
+        assert_eq!(x("b") - x("a"), step);
+        assert_eq!(x("c") - x("b"), step);
+    }
+
+    #[test]
+    fn ordering_removes_a_crossing() {
+        // Layer 0 holds 0 and 1, layer 1 holds 2 and 3; 0 -> 3 and 1 -> 2 cross
+        let items = vec![item(0), item(0), item(1), item(1)];
+        let edges = vec![layer_edge(0, 3), layer_edge(1, 2)];
+        let mut layers = vec![vec![0, 1], vec![2, 3]];
+        let fraction = |_: usize, offset: f64| offset / 40.0;
+        let positions = |layers: &[Vec<usize>]| {
+            let mut position = vec![0.0; items.len()];
+            for layer in layers {
+                for (k, &i) in layer.iter().enumerate() {
+                    position[i] = k as f64;
+                }
+            }
+            position
+        };
+        assert_eq!(count_crossings(&edges, &positions(&layers), &items, &fraction), 1);
+
+        LayeredLayout::default().order_layers(&items, &edges, &mut layers);
+        assert_eq!(count_crossings(&edges, &positions(&layers), &items, &fraction), 0);
+    }
+
+    #[test]
+    fn pack_keeps_order_and_spacing() {
+        let layout = LayeredLayout::default();
+        let items = vec![item(0), item(0), item(0)];
+        let layer = [0, 1, 2];
+        // Centres must be at least half of both sizes plus the spacing apart
+        let gap = 40.0 + layout.node_spacing;
+
+        // Everything wants the same spot: spread evenly around it
+        let packed = layout.pack(&items, &layer, &[0.0, 0.0, 0.0]);
+        assert_eq!(packed, vec![-gap, 0.0, gap]);
+
+        // Wanted out of order: order wins, as close to the wishes as it allows
+        let packed = layout.pack(&items, &layer, &[100.0, 0.0, 200.0]);
+        assert_eq!(packed[1] - packed[0], gap);
+        assert!(packed[2] - packed[1] >= gap);
+        assert_eq!(packed[2], 200.0);
+
+        // Far enough apart already: nothing moves
+        let packed = layout.pack(&items, &layer, &[0.0, 100.0, 200.0]);
+        assert_eq!(packed, vec![0.0, 100.0, 200.0]);
+    }
+
+    #[test]
+    fn force_layout_keeps_pinned_and_held_nodes() {
+        let mut pinned = node("pinned", 0.0, 0.0);
+        pinned.pinned = true;
+        let mut graph = FlowGraph::from_parts(
+            vec![pinned, node("held", 10.0, 10.0), node("free", 20.0, 20.0)],
+            vec![edge("pinned", "held"), edge("held", "free")],
+        );
+        let held: HashSet<String> = ["held".to_string()].into_iter().collect();
+        let mut simulation = ForceSimulation::new(ForceLayout::default());
+        let mut steps = 0;
+        while simulation.step(&mut graph, &held) {
+            steps += 1;
+            assert!(steps <= ForceLayout::default().iterations);
+        }
+
+        let pos = |id: &str| graph.node(id).map(|n| (n.x, n.y)).unwrap();
+        assert_eq!(pos("pinned"), (0.0, 0.0));
+        assert_eq!(pos("held"), (10.0, 10.0));
+        assert_ne!(pos("free"), (20.0, 20.0));
+    }
+
+    #[test]
+    fn force_layout_terminates_without_movable_nodes() {
+        let mut empty = FlowGraph::new();
+        ForceLayout::default().apply(&mut empty);
+        assert!(empty.is_empty());
+
+        let mut frames = FlowGraph::from_parts(vec![frame("g1"), frame("g2")], Vec::new());
+        let before = frames.nodes().to_vec();
+        ForceLayout::default().apply(&mut frames);
+        assert_eq!(frames.nodes(), before.as_slice());
+    }
+}
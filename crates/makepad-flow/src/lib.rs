@@ -1,5 +1,20 @@
 pub mod constants;
+pub mod graph;
+pub mod category;
+pub mod template;
+pub mod theme;
+pub mod edge_path;
+pub mod router;
+pub mod spatial;
+pub mod layout;
+pub mod document;
+pub mod connection;
+pub mod history;
 pub mod flow_canvas;
+pub mod minimap;
+pub mod palette;
+#[cfg(feature = "dora")]
+pub mod dora;
 
 pub use makepad_widgets;
 pub use makepad_widgets::makepad_draw;
@@ -7,9 +22,24 @@ pub use makepad_widgets::makepad_draw;
 use makepad_widgets::*;
 
 pub use constants::*;
+pub use graph::*;
+pub use category::{CategoryError, CategoryRegistry, CategoryStyle};
+pub use theme::FlowTheme;
+pub use template::{NodeTemplate, NodeTemplateRegistry};
+pub use edge_path::BezierCurve;
+pub use router::EdgeRouter;
+pub use spatial::SpatialIndex;
+pub use layout::{ForceLayout, ForceSimulation, LayeredLayout, LayoutDirection};
+pub use document::*;
+pub use connection::*;
+pub use history::{FlowCommand, FlowHistory, HistoryEntry};
 pub use flow_canvas::*;
+pub use minimap::*;
+pub use palette::*;
 
 /// Register all live designs for this crate
 pub fn live_design(cx: &mut Cx) {
     crate::flow_canvas::live_design(cx);
+    crate::minimap::live_design(cx);
+    crate::palette::live_design(cx);
 }
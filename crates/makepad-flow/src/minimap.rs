@@ -0,0 +1,202 @@
+//! Minimap overview for a [`FlowCanvas`](crate::flow_canvas::FlowCanvas)
+//!
+//! `FlowMinimap` draws every node of a linked canvas as a small rectangle in
+//! its category colour, plus the part of the graph the canvas currently shows.
+//! Clicking or dragging in the minimap pans the canvas.
+//!
+//! ```ignore
+//! self.ui.flow_minimap(ids!(minimap)).set_canvas(cx, &self.ui.flow_canvas(ids!(canvas)));
+//! ```
+
+use makepad_widgets::*;
+use crate::flow_canvas::FlowCanvasRef;
+use crate::theme::FlowTheme;
+
+live_design! {
+    use link::theme::*;
+    use link::shaders::*;
+    use link::widgets::*;
+
+    pub FlowMinimap = {{FlowMinimap}} {
+        width: 200, height: 150
+        show_bg: true
+        draw_bg: { color: #fafafa }
+
+        draw_viewport: { color: #4A90D922 }
+        viewport_border_color: #4A90D9
+        inset: 8.0
+        min_node_size: 2.0
+    }
+}
+
+#[derive(Live, LiveHook, Widget)]
+pub struct FlowMinimap {
+    #[deref] view: View,
+    #[live] draw_node: DrawColor,
+    #[live] draw_viewport: DrawColor,
+    #[live] pub viewport_border_color: Vec4,
+    #[live(8.0)] pub inset: f64,       // Space between the overview and the widget border
+    #[live(2.0)] pub min_node_size: f64, // Nodes never shrink below this many pixels
+
+    #[rust] canvas: Option<FlowCanvasRef>,
+    #[rust] canvas_revision: u64, // Canvas draw revision the minimap last drew
+    #[rust] scale: f64,           // Minimap pixels per canvas unit
+    #[rust] world_origin: DVec2,  // Canvas point drawn at `screen_origin`
+    #[rust] screen_origin: DVec2,
+    #[rust] viewport: Option<Rect>, // Visible canvas area in minimap coordinates
+    #[rust] drag_offset: Option<DVec2>, // Pointer offset from the viewport centre while dragging
+}
+
+impl Widget for FlowMinimap {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, _scope: &mut Scope) {
+        // Redraw whenever the canvas has drawn something new
+        if let Some(revision) = self.canvas.as_ref().and_then(|c| c.borrow().map(|c| c.draw_revision())) {
+            if revision != self.canvas_revision {
+                self.canvas_revision = revision;
+                self.view.redraw(cx);
+            }
+        }
+
+        match event.hits(cx, self.view.area()) {
+            Hit::FingerDown(fe) => {
+                // Grabbing the viewport keeps it under the pointer; clicking elsewhere centres on the click
+                let offset = match self.viewport {
+                    Some(vp) if vp.contains(fe.abs) => DVec2 {
+                        x: fe.abs.x - (vp.pos.x + vp.size.x / 2.0),
+                        y: fe.abs.y - (vp.pos.y + vp.size.y / 2.0),
+                    },
+                    _ => DVec2::default(),
+                };
+                self.drag_offset = Some(offset);
+                self.pan_canvas(cx, fe.abs);
+            }
+            Hit::FingerMove(fe) => {
+                if self.drag_offset.is_some() {
+                    self.pan_canvas(cx, fe.abs);
+                }
+            }
+            Hit::FingerUp(_) => {
+                self.drag_offset = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        let _ = self.view.draw_walk(cx, scope, walk);
+        let rect = self.view.area().rect(cx);
+        self.viewport = None;
+
+        let Some(canvas) = self.canvas.clone() else { return DrawStep::done(); };
+        let Some(inner) = canvas.borrow() else { return DrawStep::done(); };
+        let visible = inner.visible_rect(cx);
+        let Some(bounds) = inner.graph().bounds() else { return DrawStep::done(); };
+
+        // Fit the nodes and the visible area into the widget, keeping the aspect ratio
+        let world = union(bounds, visible);
+        let avail = DVec2 {
+            x: (rect.size.x - self.inset * 2.0).max(1.0),
+            y: (rect.size.y - self.inset * 2.0).max(1.0),
+        };
+        self.scale = (avail.x / world.size.x.max(1.0)).min(avail.y / world.size.y.max(1.0));
+        self.world_origin = world.pos;
+        self.screen_origin = DVec2 {
+            x: rect.pos.x + (rect.size.x - world.size.x * self.scale) / 2.0,
+            y: rect.pos.y + (rect.size.y - world.size.y * self.scale) / 2.0,
+        };
+
+        for node in inner.graph().nodes().iter().filter(|n| !n.is_frame()) {
+            let pos = self.to_minimap(DVec2 { x: node.x, y: node.y });
+            self.draw_node.color = inner.categories().style(&node.category).color;
+            self.draw_node.draw_abs(cx, Rect {
+                pos,
+                size: DVec2 {
+                    x: (node.width * self.scale).max(self.min_node_size),
+                    y: (node.height * self.scale).max(self.min_node_size),
+                },
+            });
+        }
+
+        // Visible area: tinted fill with a 1px border
+        let vp = Rect {
+            pos: self.to_minimap(visible.pos),
+            size: DVec2 { x: visible.size.x * self.scale, y: visible.size.y * self.scale },
+        };
+        self.draw_viewport.draw_abs(cx, vp);
+        self.draw_node.color = self.viewport_border_color;
+        for line in [
+            Rect { pos: vp.pos, size: DVec2 { x: vp.size.x, y: 1.0 } },
+            Rect { pos: DVec2 { x: vp.pos.x, y: vp.pos.y + vp.size.y - 1.0 }, size: DVec2 { x: vp.size.x, y: 1.0 } },
+            Rect { pos: vp.pos, size: DVec2 { x: 1.0, y: vp.size.y } },
+            Rect { pos: DVec2 { x: vp.pos.x + vp.size.x - 1.0, y: vp.pos.y }, size: DVec2 { x: 1.0, y: vp.size.y } },
+        ] {
+            self.draw_node.draw_abs(cx, line);
+        }
+        self.viewport = Some(vp);
+
+        DrawStep::done()
+    }
+}
+
+impl FlowMinimap {
+    fn to_minimap(&self, p: DVec2) -> DVec2 {
+        DVec2 {
+            x: self.screen_origin.x + (p.x - self.world_origin.x) * self.scale,
+            y: self.screen_origin.y + (p.y - self.world_origin.y) * self.scale,
+        }
+    }
+
+    fn to_canvas(&self, p: DVec2) -> DVec2 {
+        DVec2 {
+            x: self.world_origin.x + (p.x - self.screen_origin.x) / self.scale,
+            y: self.world_origin.y + (p.y - self.screen_origin.y) / self.scale,
+        }
+    }
+
+    /// Centre the canvas on the point under the pointer (minus the grab offset)
+    fn pan_canvas(&mut self, cx: &mut Cx, abs: DVec2) {
+        if self.scale <= 0.0 {
+            return;
+        }
+        let Some(canvas) = &self.canvas else { return; };
+        let offset = self.drag_offset.unwrap_or_default();
+        let target = self.to_canvas(DVec2 { x: abs.x - offset.x, y: abs.y - offset.y });
+        if let Some(mut inner) = canvas.borrow_mut() {
+            inner.center_on(cx, target);
+        }
+        self.view.redraw(cx);
+    }
+}
+
+fn union(a: Rect, b: Rect) -> Rect {
+    if b.size.x <= 0.0 || b.size.y <= 0.0 {
+        return a;
+    }
+    let min_x = a.pos.x.min(b.pos.x);
+    let min_y = a.pos.y.min(b.pos.y);
+    let max_x = (a.pos.x + a.size.x).max(b.pos.x + b.size.x);
+    let max_y = (a.pos.y + a.size.y).max(b.pos.y + b.size.y);
+    Rect { pos: DVec2 { x: min_x, y: min_y }, size: DVec2 { x: max_x - min_x, y: max_y - min_y } }
+}
+
+impl FlowMinimapRef {
+    /// Show `canvas` in this minimap and pan it on click/drag
+    pub fn set_canvas(&self, cx: &mut Cx, canvas: &FlowCanvasRef) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.canvas = Some(canvas.clone());
+            inner.view.redraw(cx);
+        }
+    }
+
+    /// Use the minimap colors of `theme`
+    pub fn set_theme(&self, cx: &mut Cx, theme: &FlowTheme) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.viewport_border_color = theme.selection;
+            inner.draw_viewport.color = theme.minimap_viewport;
+            inner.view.apply_over(cx, live! {
+                draw_bg: { color: (theme.minimap_background) }
+            });
+            inner.view.redraw(cx);
+        }
+    }
+}
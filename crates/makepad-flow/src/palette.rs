@@ -0,0 +1,238 @@
+//! Node palette for [`FlowCanvas`](crate::flow_canvas::FlowCanvas)
+//!
+//! `FlowPalette` lists the node templates of a linked canvas grouped by
+//! category, with a search box above the list. Dragging an entry sends
+//! [`FlowPaletteAction`]s; a `FlowCanvas` under the pointer previews the node
+//! and creates it where the entry is dropped.
+//!
+//! ```ignore
+//! self.ui.flow_palette(ids!(palette)).set_canvas(cx, &self.ui.flow_canvas(ids!(canvas)));
+//! ```
+
+use makepad_widgets::*;
+use crate::constants::palette;
+use crate::flow_canvas::FlowCanvasRef;
+use crate::theme::FlowTheme;
+
+live_design! {
+    use link::theme::*;
+    use link::shaders::*;
+    use link::widgets::*;
+
+    pub FlowPalette = {{FlowPalette}} {
+        width: 200, height: Fill
+        flow: Down
+        show_bg: true
+        draw_bg: { color: #f8f8f8 }
+
+        search_input = <TextInput> {
+            width: Fill, height: 28
+            margin: 8
+            empty_text: "Search nodes..."
+            draw_bg: {
+                color: #f5f5f5
+                border_radius: 4.0
+            }
+            draw_text: {
+                color: #333333
+                text_style: { font_size: 10.0 }
+            }
+        }
+        // Entries are drawn into this area
+        list = <View> { width: Fill, height: Fill }
+
+        draw_text: { text_style: { font_size: 10.0 } }
+        header_color: #666666
+        text_color: #333333
+        hover_color: #e8f4fd
+        row_height: 24.0
+        indent: 24.0
+    }
+}
+
+/// Sent while an entry is dragged out of a palette
+#[derive(Clone, Debug, DefaultNone)]
+pub enum FlowPaletteAction {
+    None,
+    /// Pointer moved while dragging the template `template`
+    Dragging { template: String, abs: DVec2 },
+    /// The entry was released; a canvas under `abs` creates the node there
+    Dropped { template: String, abs: DVec2 },
+}
+
+// Press on an entry that may turn into a drag
+struct PaletteDrag {
+    template: String,
+    start: DVec2,
+    active: bool,
+}
+
+#[derive(Live, LiveHook, Widget)]
+pub struct FlowPalette {
+    #[deref] view: View,
+    #[live] draw_row: DrawColor,
+    #[live] draw_text: DrawText,
+    #[live] pub header_color: Vec4,
+    #[live] pub text_color: Vec4,
+    #[live] pub hover_color: Vec4,
+    #[live(24.0)] pub row_height: f64,
+    #[live(24.0)] pub indent: f64, // Entry text offset; category swatches sit in this gap
+
+    #[rust] canvas: Option<FlowCanvasRef>,
+    #[rust] query: String,
+    #[rust] entries: Vec<(Rect, String)>, // Entry rows drawn last, with their template ids
+    #[rust] hovered: Option<String>,
+    #[rust] drag: Option<PaletteDrag>,
+}
+
+impl Widget for FlowPalette {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        self.view.handle_event(cx, event, scope);
+        self.widget_match_event(cx, event, scope);
+
+        let uid = self.widget_uid();
+        match event.hits(cx, self.view.widget(ids!(list)).area()) {
+            Hit::FingerHoverIn(fh) | Hit::FingerHoverOver(fh) => {
+                let hovered = self.entry_at(fh.abs);
+                if hovered.is_some() {
+                    cx.set_cursor(MouseCursor::Hand);
+                }
+                if hovered != self.hovered {
+                    self.hovered = hovered;
+                    self.view.redraw(cx);
+                }
+            }
+            Hit::FingerHoverOut(_) => {
+                if self.hovered.take().is_some() {
+                    self.view.redraw(cx);
+                }
+            }
+            Hit::FingerDown(fe) => {
+                self.drag = self.entry_at(fe.abs).map(|template| PaletteDrag {
+                    template,
+                    start: fe.abs,
+                    active: false,
+                });
+            }
+            Hit::FingerMove(fe) => {
+                if let Some(drag) = &mut self.drag {
+                    drag.active |= (fe.abs - drag.start).length() > palette::DRAG_THRESHOLD;
+                    if drag.active {
+                        cx.set_cursor(MouseCursor::Grab);
+                        cx.widget_action(uid, &scope.path, FlowPaletteAction::Dragging {
+                            template: drag.template.clone(),
+                            abs: fe.abs,
+                        });
+                    }
+                }
+            }
+            Hit::FingerUp(fe) => {
+                if let Some(drag) = self.drag.take() {
+                    if drag.active {
+                        cx.set_cursor(MouseCursor::Arrow);
+                        cx.widget_action(uid, &scope.path, FlowPaletteAction::Dropped {
+                            template: drag.template,
+                            abs: fe.abs,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        let _ = self.view.draw_walk(cx, scope, walk);
+        self.entries.clear();
+
+        let Some(canvas) = self.canvas.clone() else { return DrawStep::done(); };
+        let Some(inner) = canvas.borrow() else { return DrawStep::done(); };
+        let rect = self.view.widget(ids!(list)).area().rect(cx);
+        let bottom = rect.pos.y + rect.size.y;
+        let text_y = (self.row_height - self.draw_text.text_style.font_size as f64) / 2.0;
+        let mut y = rect.pos.y;
+
+        // Rows that do not fit are left out
+        'groups: for (category, templates) in inner.templates().grouped(&self.query) {
+            if y + self.row_height > bottom {
+                break;
+            }
+            let style = inner.categories().style(category);
+            let swatch = self.indent / 2.0 - 4.0;
+            self.draw_row.color = style.color;
+            self.draw_row.draw_abs(cx, Rect {
+                pos: DVec2 { x: rect.pos.x + swatch, y: y + self.row_height / 2.0 - 4.0 },
+                size: DVec2 { x: 8.0, y: 8.0 },
+            });
+            let label = if style.label.is_empty() { category.name() } else { style.label.as_str() };
+            self.draw_text.color = self.header_color;
+            self.draw_text.draw_abs(cx, DVec2 { x: rect.pos.x + self.indent, y: y + text_y }, label);
+            y += self.row_height;
+
+            for template in templates {
+                if y + self.row_height > bottom {
+                    break 'groups;
+                }
+                let row = Rect {
+                    pos: DVec2 { x: rect.pos.x, y },
+                    size: DVec2 { x: rect.size.x, y: self.row_height },
+                };
+                if self.hovered.as_deref() == Some(template.id.as_str()) {
+                    self.draw_row.color = self.hover_color;
+                    self.draw_row.draw_abs(cx, row);
+                }
+                self.draw_text.color = self.text_color;
+                self.draw_text.draw_abs(cx, DVec2 { x: rect.pos.x + self.indent, y: y + text_y }, &template.title);
+                self.entries.push((row, template.id.clone()));
+                y += self.row_height;
+            }
+        }
+
+        DrawStep::done()
+    }
+}
+
+impl WidgetMatchEvent for FlowPalette {
+    fn handle_actions(&mut self, cx: &mut Cx, actions: &Actions, _scope: &mut Scope) {
+        if let Some(text) = self.view.text_input(ids!(search_input)).changed(actions) {
+            self.query = text;
+            self.view.redraw(cx);
+        }
+    }
+}
+
+impl FlowPalette {
+    /// Template id of the entry under `abs`
+    fn entry_at(&self, abs: DVec2) -> Option<String> {
+        self.entries.iter()
+            .find(|(row, _)| row.contains(abs))
+            .map(|(_, id)| id.clone())
+    }
+}
+
+impl FlowPaletteRef {
+    /// List the templates registered on `canvas`, colored by its categories
+    pub fn set_canvas(&self, cx: &mut Cx, canvas: &FlowCanvasRef) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.canvas = Some(canvas.clone());
+            inner.view.redraw(cx);
+        }
+    }
+
+    /// Use the panel colors of `theme`
+    pub fn set_theme(&self, cx: &mut Cx, theme: &FlowTheme) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.header_color = theme.text_muted;
+            inner.text_color = theme.text;
+            inner.hover_color = theme.accent_soft;
+            inner.view.apply_over(cx, live! {
+                draw_bg: { color: (theme.panel_background) }
+            });
+            inner.view.text_input(ids!(search_input)).apply_over(cx, live! {
+                draw_bg: { color: (theme.input_background) }
+                draw_text: { color: (theme.text) }
+            });
+            inner.view.redraw(cx);
+        }
+    }
+}
@@ -0,0 +1,394 @@
+//! Obstacle-avoiding orthogonal edge routing
+//!
+//! [`EdgeRouter`] finds orthogonal paths that go around node rectangles instead
+//! of through them, then nudges overlapping parallel segments apart so wires
+//! sharing a corridor stay distinguishable. Routes are cached per edge and only
+//! recomputed when an endpoint moves or a node is moved onto or off the route,
+//! so dragging one node re-routes just the edges around it.
+
+use makepad_widgets::*;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use crate::constants::routing;
+use crate::graph::*;
+
+// Identifies an edge across graph edits, independent of its index
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct EdgeKey {
+    from_node: String,
+    from_port: String,
+    to_node: String,
+    to_port: String,
+}
+
+impl EdgeKey {
+    fn of(edge: &EdgeConnection) -> Self {
+        Self {
+            from_node: edge.from_node.clone(),
+            from_port: edge.from_port.clone(),
+            to_node: edge.to_node.clone(),
+            to_port: edge.to_port.clone(),
+        }
+    }
+}
+
+// Un-nudged route plus the anchors it was computed for
+#[derive(Clone, Debug)]
+struct CachedRoute {
+    from: DVec2,
+    to: DVec2,
+    points: Vec<DVec2>,
+}
+
+/// Caches orthogonal routes for the edges of a [`FlowGraph`]
+#[derive(Clone, Debug, Default)]
+pub struct EdgeRouter {
+    node_rects: HashMap<String, Rect>,
+    cache: HashMap<EdgeKey, CachedRoute>,
+    keys: Vec<EdgeKey>,
+    routes: Vec<Vec<DVec2>>, // nudged, same order as the graph's edges
+}
+
+impl EdgeRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Route of `edge`, which must be the edge at `index` in the graph passed to
+    /// the last [`update`](Self::update). Returns `None` for stale or unroutable edges.
+    pub fn route(&self, index: usize, edge: &EdgeConnection) -> Option<&[DVec2]> {
+        let key = self.keys.get(index)?;
+        let matches = key.from_node == edge.from_node && key.from_port == edge.from_port &&
+            key.to_node == edge.to_node && key.to_port == edge.to_port;
+        let route = self.routes.get(index)?;
+        (matches && !route.is_empty()).then_some(route.as_slice())
+    }
+
+    /// Drop all cached routes so the next update recomputes everything
+    pub fn invalidate(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Bring the routes up to date with `graph`. Returns true if any route changed.
+    pub fn update(&mut self, graph: &FlowGraph) -> bool {
+        // Group frames contain their children, so edges may cross them
+        let rects: HashMap<String, Rect> = graph.nodes().iter()
+            .filter(|n| !n.is_frame())
+            .map(|n| (n.id.clone(), node_rect(n)))
+            .collect();
+
+        // Areas where nodes appeared, disappeared or moved; routes crossing them are stale
+        let mut dirty_areas = Vec::new();
+        for (id, rect) in &rects {
+            match self.node_rects.get(id) {
+                Some(old) if old == rect => {}
+                Some(old) => {
+                    dirty_areas.push(inflate(*old, routing::MARGIN));
+                    dirty_areas.push(inflate(*rect, routing::MARGIN));
+                }
+                None => dirty_areas.push(inflate(*rect, routing::MARGIN)),
+            }
+        }
+        for (id, old) in &self.node_rects {
+            if !rects.contains_key(id) {
+                dirty_areas.push(inflate(*old, routing::MARGIN));
+            }
+        }
+
+        let keys: Vec<EdgeKey> = graph.edges().iter().map(EdgeKey::of).collect();
+        if dirty_areas.is_empty() && keys == self.keys {
+            return false;
+        }
+
+        let obstacles: Vec<Rect> = rects.values().map(|r| inflate(*r, routing::MARGIN)).collect();
+        let mut cache = HashMap::with_capacity(keys.len());
+        let mut raw_routes = Vec::with_capacity(keys.len());
+        for (edge, key) in graph.edges().iter().zip(&keys) {
+            let (Some(from_node), Some(to_node)) = (graph.node(&edge.from_node), graph.node(&edge.to_node)) else {
+                raw_routes.push(Vec::new());
+                continue;
+            };
+            let from = from_node.output_anchor(&edge.from_port);
+            let to = to_node.input_anchor(&edge.to_port);
+
+            let reusable = self.cache.get(key).filter(|cached| {
+                cached.from == from && cached.to == to &&
+                !dirty_areas.iter().any(|area| path_crosses_rect(&cached.points, area))
+            });
+            let route = match reusable {
+                Some(cached) => cached.clone(),
+                None => CachedRoute { from, to, points: route_edge(from, to, &obstacles) },
+            };
+            raw_routes.push(route.points.clone());
+            cache.insert(key.clone(), route);
+        }
+
+        nudge(&mut raw_routes, true);
+        nudge(&mut raw_routes, false);
+
+        self.node_rects = rects;
+        self.cache = cache;
+        self.keys = keys;
+        self.routes = raw_routes;
+        true
+    }
+}
+
+/// Orthogonal route from an output anchor to an input anchor around `obstacles`.
+/// Falls back to a plain step path when no route exists.
+pub fn route_edge(from: DVec2, to: DVec2, obstacles: &[Rect]) -> Vec<DVec2> {
+    // Leave and enter the ports horizontally, clear of the node borders
+    let start = DVec2 { x: from.x + routing::MARGIN, y: from.y };
+    let end = DVec2 { x: to.x - routing::MARGIN, y: to.y };
+
+    // Only obstacles near the endpoints matter; this keeps the search grid small
+    let pad = routing::SEARCH_PADDING;
+    let bounds = Rect {
+        pos: DVec2 { x: start.x.min(end.x) - pad, y: start.y.min(end.y) - pad },
+        size: DVec2 { x: (start.x - end.x).abs() + pad * 2.0, y: (start.y - end.y).abs() + pad * 2.0 },
+    };
+    let nearby: Vec<Rect> = obstacles.iter().copied().filter(|r| rects_overlap(r, &bounds)).collect();
+
+    // Grid lines: obstacle borders, endpoint rows/columns, the midlines between
+    // the endpoints and the search bounds
+    let mut xs = vec![start.x, end.x, (start.x + end.x) / 2.0, bounds.pos.x, bounds.pos.x + bounds.size.x];
+    let mut ys = vec![start.y, end.y, (start.y + end.y) / 2.0, bounds.pos.y, bounds.pos.y + bounds.size.y];
+    for r in &nearby {
+        xs.push(r.pos.x);
+        xs.push(r.pos.x + r.size.x);
+        ys.push(r.pos.y);
+        ys.push(r.pos.y + r.size.y);
+    }
+    let clamp_x = |x: f64| x.clamp(bounds.pos.x, bounds.pos.x + bounds.size.x);
+    let clamp_y = |y: f64| y.clamp(bounds.pos.y, bounds.pos.y + bounds.size.y);
+    let xs = sorted_unique(xs.into_iter().map(clamp_x).collect());
+    let ys = sorted_unique(ys.into_iter().map(clamp_y).collect());
+
+    let (Some(si), Some(sj), Some(ei), Some(ej)) = (
+        position_of(&xs, start.x), position_of(&ys, start.y),
+        position_of(&xs, end.x), position_of(&ys, end.y),
+    ) else {
+        return EdgePathType::Step.points(from, to);
+    };
+
+    match grid_search(&xs, &ys, (si, sj), (ei, ej), &nearby) {
+        Some(mut points) => {
+            points.insert(0, from);
+            points.push(to);
+            simplify(points)
+        }
+        None => EdgePathType::Step.points(from, to),
+    }
+}
+
+// Direction of travel on the grid: +x, -x, +y, -y
+const DIRS: [(isize, isize); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const DIR_RIGHT: usize = 0;
+
+#[derive(PartialEq)]
+struct Candidate {
+    estimate: f64,
+    cost: f64,
+    state: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the BinaryHeap pops the cheapest candidate first
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A* over the grid intersections with a penalty per bend.
+/// States are (cell, arrival direction) so bends can be costed.
+fn grid_search(xs: &[f64], ys: &[f64], start: (usize, usize), end: (usize, usize), obstacles: &[Rect]) -> Option<Vec<DVec2>> {
+    let nx = xs.len();
+    let ny = ys.len();
+    let state_of = |i: usize, j: usize, dir: usize| (j * nx + i) * 4 + dir;
+    let point = |i: usize, j: usize| DVec2 { x: xs[i], y: ys[j] };
+    let heuristic = |i: usize, j: usize| (xs[i] - xs[end.0]).abs() + (ys[j] - ys[end.1]).abs();
+
+    let mut best = vec![f64::INFINITY; nx * ny * 4];
+    let mut came_from = vec![usize::MAX; nx * ny * 4];
+    let mut open = BinaryHeap::new();
+
+    // The route starts heading right, out of the output port
+    let first = state_of(start.0, start.1, DIR_RIGHT);
+    best[first] = 0.0;
+    open.push(Candidate { estimate: heuristic(start.0, start.1), cost: 0.0, state: first });
+
+    while let Some(Candidate { cost, state, .. }) = open.pop() {
+        if cost > best[state] {
+            continue;
+        }
+        let dir = state % 4;
+        let cell = state / 4;
+        let (i, j) = (cell % nx, cell / nx);
+        if (i, j) == end {
+            let mut points = vec![point(i, j)];
+            let mut s = state;
+            while came_from[s] != usize::MAX {
+                s = came_from[s];
+                let c = s / 4;
+                points.push(point(c % nx, c / nx));
+            }
+            points.reverse();
+            return Some(points);
+        }
+
+        for (next_dir, (di, dj)) in DIRS.iter().enumerate() {
+            // Never double back on the previous segment
+            if (next_dir ^ 1) == dir {
+                continue;
+            }
+            let (Some(ni), Some(nj)) = (i.checked_add_signed(*di), j.checked_add_signed(*dj)) else {
+                continue;
+            };
+            if ni >= nx || nj >= ny {
+                continue;
+            }
+            let a = point(i, j);
+            let b = point(ni, nj);
+            let mid = DVec2 { x: (a.x + b.x) / 2.0, y: (a.y + b.y) / 2.0 };
+            if obstacles.iter().any(|r| strictly_inside(r, mid)) {
+                continue;
+            }
+            let mut step = (b.x - a.x).abs() + (b.y - a.y).abs();
+            if next_dir != dir {
+                step += routing::BEND_PENALTY;
+            }
+            // The route must leave `end` heading right, into the input port
+            if (ni, nj) == end && next_dir != DIR_RIGHT {
+                step += routing::BEND_PENALTY;
+            }
+            let next = state_of(ni, nj, next_dir);
+            let next_cost = cost + step;
+            if next_cost < best[next] {
+                best[next] = next_cost;
+                came_from[next] = state;
+                open.push(Candidate { estimate: next_cost + heuristic(ni, nj), cost: next_cost, state: next });
+            }
+        }
+    }
+    None
+}
+
+/// Spread overlapping parallel segments that share a line.
+/// `horizontal` selects which segments are handled; the first and last segment
+/// of each route stay put because they attach to the ports.
+fn nudge(routes: &mut [Vec<DVec2>], horizontal: bool) {
+    // (line key, span start, span end, route, segment start index)
+    let mut segments = Vec::new();
+    for (r, points) in routes.iter().enumerate() {
+        if points.len() < 4 {
+            continue;
+        }
+        for i in 1..points.len() - 2 {
+            let (a, b) = (points[i], points[i + 1]);
+            let (along_a, along_b, line) = if horizontal { (a.x, b.x, a.y) } else { (a.y, b.y, a.x) };
+            let on_line = if horizontal { (a.y - b.y).abs() < 0.01 } else { (a.x - b.x).abs() < 0.01 };
+            if on_line && (along_a - along_b).abs() > 0.01 {
+                segments.push(((line * 100.0).round() as i64, along_a.min(along_b), along_a.max(along_b), r, i));
+            }
+        }
+    }
+    segments.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+    let max_offset = routing::MARGIN - 2.0;
+    let mut start = 0;
+    while start < segments.len() {
+        // Cluster segments on the same line whose spans overlap
+        let mut end = start + 1;
+        let mut reach = segments[start].2;
+        while end < segments.len() && segments[end].0 == segments[start].0 && segments[end].1 < reach {
+            reach = reach.max(segments[end].2);
+            end += 1;
+        }
+        let cluster = &segments[start..end];
+        if cluster.len() > 1 {
+            let center = (cluster.len() - 1) as f64 / 2.0;
+            for (k, &(_, _, _, r, i)) in cluster.iter().enumerate() {
+                let offset = ((k as f64 - center) * routing::NUDGE_SPACING).clamp(-max_offset, max_offset);
+                for p in [i, i + 1] {
+                    if horizontal {
+                        routes[r][p].y += offset;
+                    } else {
+                        routes[r][p].x += offset;
+                    }
+                }
+            }
+        }
+        start = end;
+    }
+}
+
+/// Drop repeated points and interior points on a straight line
+fn simplify(points: Vec<DVec2>) -> Vec<DVec2> {
+    let mut out: Vec<DVec2> = Vec::with_capacity(points.len());
+    for p in points {
+        if let Some(last) = out.last() {
+            if (last.x - p.x).abs() < 0.01 && (last.y - p.y).abs() < 0.01 {
+                continue;
+            }
+        }
+        if out.len() >= 2 {
+            let a = out[out.len() - 2];
+            let b = out[out.len() - 1];
+            let collinear = ((a.x - b.x).abs() < 0.01 && (b.x - p.x).abs() < 0.01) ||
+                ((a.y - b.y).abs() < 0.01 && (b.y - p.y).abs() < 0.01);
+            if collinear {
+                out.pop();
+            }
+        }
+        out.push(p);
+    }
+    out
+}
+
+fn node_rect(node: &FlowNode) -> Rect {
+    Rect { pos: DVec2 { x: node.x, y: node.y }, size: DVec2 { x: node.width, y: node.height } }
+}
+
+fn inflate(r: Rect, by: f64) -> Rect {
+    Rect {
+        pos: DVec2 { x: r.pos.x - by, y: r.pos.y - by },
+        size: DVec2 { x: r.size.x + by * 2.0, y: r.size.y + by * 2.0 },
+    }
+}
+
+fn strictly_inside(r: &Rect, p: DVec2) -> bool {
+    p.x > r.pos.x + 0.01 && p.x < r.pos.x + r.size.x - 0.01 &&
+    p.y > r.pos.y + 0.01 && p.y < r.pos.y + r.size.y - 0.01
+}
+
+fn rects_overlap(a: &Rect, b: &Rect) -> bool {
+    a.pos.x < b.pos.x + b.size.x && b.pos.x < a.pos.x + a.size.x &&
+    a.pos.y < b.pos.y + b.size.y && b.pos.y < a.pos.y + a.size.y
+}
+
+// Routes are orthogonal, so a segment's bounding box is the segment itself
+fn path_crosses_rect(points: &[DVec2], r: &Rect) -> bool {
+    points.windows(2).any(|w| {
+        let (a, b) = (w[0], w[1]);
+        a.x.min(b.x) <= r.pos.x + r.size.x && a.x.max(b.x) >= r.pos.x &&
+        a.y.min(b.y) <= r.pos.y + r.size.y && a.y.max(b.y) >= r.pos.y
+    })
+}
+
+fn sorted_unique(mut values: Vec<f64>) -> Vec<f64> {
+    values.sort_by(f64::total_cmp);
+    values.dedup_by(|a, b| (*a - *b).abs() < 0.01);
+    values
+}
+
+fn position_of(values: &[f64], v: f64) -> Option<usize> {
+    values.iter().position(|x| (x - v).abs() < 0.01)
+}
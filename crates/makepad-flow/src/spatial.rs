@@ -0,0 +1,288 @@
+//! Spatial index over a graph's nodes and edges
+//!
+//! [`SpatialIndex`] buckets node and edge bounding boxes into a uniform grid,
+//! so hit tests, selection boxes and viewport culling only look at the items
+//! near the area they ask about instead of every node and edge. Queries return
+//! candidates whose boxes overlap the area; callers still do their exact test
+//! (port hit rect, distance to the edge path) on those.
+//!
+//! The index remembers the [`FlowGraph::revision`] it was built from, so the
+//! owner can bring it up to date lazily, once per change, before querying.
+//! When only nodes were moved or resized since, [`SpatialIndex::update`]
+//! re-indexes just those nodes and the edges touching them:
+//!
+//! ```ignore
+//! index.update(&graph, false, |_, edge| SpatialIndex::edge_bounds(&graph, edge));
+//! let under_cursor = index.nodes_at(pos);
+//! ```
+
+use makepad_widgets::*;
+use std::collections::{HashMap, HashSet};
+use crate::constants::{edge, spatial};
+use crate::graph::*;
+
+type Cell = (i64, i64);
+
+// Items in a grid: small boxes are bucketed per cell, boxes spanning many cells
+// (long edges, big group frames) are kept aside and checked on every query
+#[derive(Clone, Debug, Default)]
+struct Grid {
+    cells: HashMap<Cell, Vec<usize>>,
+    large: Vec<usize>,
+}
+
+/// Uniform grid of node and edge bounds, addressed by index into
+/// `FlowGraph::nodes()` / `FlowGraph::edges()`
+#[derive(Clone, Debug)]
+pub struct SpatialIndex {
+    cell_size: f64,
+    node_bounds: Vec<Rect>,
+    edge_bounds: Vec<Option<Rect>>,
+    node_grid: Grid,
+    edge_grid: Grid,
+    revision: Option<u64>,
+    structure_revision: Option<u64>,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self::new(spatial::CELL_SIZE)
+    }
+}
+
+impl SpatialIndex {
+    /// Empty index with square cells of `cell_size` canvas units
+    pub fn new(cell_size: f64) -> Self {
+        Self {
+            cell_size: cell_size.max(1.0),
+            node_bounds: Vec::new(),
+            edge_bounds: Vec::new(),
+            node_grid: Grid::default(),
+            edge_grid: Grid::default(),
+            revision: None,
+            structure_revision: None,
+        }
+    }
+
+    /// Whether the index was built from `graph` as it is now
+    pub fn is_current(&self, graph: &FlowGraph) -> bool {
+        self.revision == Some(graph.revision())
+    }
+
+    /// Force the next `is_current` to fail, e.g. when edge shapes change
+    /// without the graph changing
+    pub fn invalidate(&mut self) {
+        self.revision = None;
+        self.structure_revision = None;
+    }
+
+    /// Bring the index up to date with `graph`. If nodes were only moved or
+    /// resized since the last build, just those nodes and the edges touching
+    /// them are re-indexed; any other change rebuilds everything.
+    /// `edges_changed` re-checks every edge box as well, for edge shapes that
+    /// change with more than their endpoints, e.g. routed edges.
+    pub fn update(&mut self, graph: &FlowGraph, edges_changed: bool, mut edge_bounds: impl FnMut(usize, &EdgeConnection) -> Option<Rect>) {
+        let same_structure = self.structure_revision == Some(graph.structure_revision())
+            && self.node_bounds.len() == graph.node_count()
+            && self.edge_bounds.len() == graph.edge_count();
+        if !same_structure {
+            self.rebuild(graph, edge_bounds);
+            return;
+        }
+        if self.is_current(graph) && !edges_changed {
+            return;
+        }
+
+        let mut moved = HashSet::new();
+        for (i, node) in graph.nodes().iter().enumerate() {
+            let bounds = inflate(node.rect(), spatial::NODE_MARGIN);
+            if bounds != self.node_bounds[i] {
+                self.node_grid.remove(self.cell_size, i, Some(self.node_bounds[i]));
+                self.node_grid.insert(self.cell_size, i, Some(bounds));
+                self.node_bounds[i] = bounds;
+                moved.insert(node.id.as_str());
+            }
+        }
+        for (i, edge) in graph.edges().iter().enumerate() {
+            if !edges_changed && !moved.contains(edge.from_node.as_str()) && !moved.contains(edge.to_node.as_str()) {
+                continue;
+            }
+            let bounds = edge_bounds(i, edge).map(|rect| inflate(rect, edge::HIT_DISTANCE));
+            if bounds != self.edge_bounds[i] {
+                self.edge_grid.remove(self.cell_size, i, self.edge_bounds[i]);
+                self.edge_grid.insert(self.cell_size, i, bounds);
+                self.edge_bounds[i] = bounds;
+            }
+        }
+        self.revision = Some(graph.revision());
+    }
+
+    /// Index every node of `graph` and every edge `edge_bounds` gives a box for.
+    /// Node boxes include the port hit areas; edge boxes are widened by the
+    /// edge hit distance.
+    pub fn rebuild(&mut self, graph: &FlowGraph, mut edge_bounds: impl FnMut(usize, &EdgeConnection) -> Option<Rect>) {
+        self.node_bounds = graph.nodes().iter()
+            .map(|node| inflate(node.rect(), spatial::NODE_MARGIN))
+            .collect();
+        self.edge_bounds = graph.edges().iter().enumerate()
+            .map(|(i, edge)| edge_bounds(i, edge).map(|rect| inflate(rect, edge::HIT_DISTANCE)))
+            .collect();
+        self.node_grid = Grid::build(self.cell_size, self.node_bounds.iter().copied().map(Some));
+        self.edge_grid = Grid::build(self.cell_size, self.edge_bounds.iter().copied());
+        self.revision = Some(graph.revision());
+        self.structure_revision = Some(graph.structure_revision());
+    }
+
+    /// Box of an edge drawn with its own path type between its ports.
+    /// `None` if an endpoint is missing.
+    pub fn edge_bounds(graph: &FlowGraph, edge: &EdgeConnection) -> Option<Rect> {
+        let from = graph.node(&edge.from_node)?.output_anchor(&edge.from_port);
+        let to = graph.node(&edge.to_node)?.input_anchor(&edge.to_port);
+        Some(edge.path_type.bounds(from, to))
+    }
+
+    /// Nodes whose box overlaps `rect`, in draw order
+    pub fn nodes_in(&self, rect: Rect) -> Vec<usize> {
+        self.node_grid.query(self.cell_size, rect, self.node_bounds.len(), |i| Some(self.node_bounds[i]))
+    }
+
+    /// Nodes whose box contains `pos`, in draw order (topmost last)
+    pub fn nodes_at(&self, pos: DVec2) -> Vec<usize> {
+        self.nodes_in(Rect { pos, size: DVec2::default() })
+    }
+
+    /// Edges whose box overlaps `rect`, in draw order
+    pub fn edges_in(&self, rect: Rect) -> Vec<usize> {
+        self.edge_grid.query(self.cell_size, rect, self.edge_bounds.len(), |i| self.edge_bounds[i])
+    }
+
+    /// Edges whose box contains `pos`, in draw order
+    pub fn edges_at(&self, pos: DVec2) -> Vec<usize> {
+        self.edges_in(Rect { pos, size: DVec2::default() })
+    }
+}
+
+impl Grid {
+    fn build(cell_size: f64, bounds: impl Iterator<Item = Option<Rect>>) -> Self {
+        let mut grid = Grid::default();
+        for (i, rect) in bounds.enumerate() {
+            grid.insert(cell_size, i, rect);
+        }
+        grid
+    }
+
+    fn insert(&mut self, cell_size: f64, item: usize, rect: Option<Rect>) {
+        let Some(rect) = rect else { return };
+        if cell_count(cell_size, rect) > spatial::MAX_ITEM_CELLS {
+            self.large.push(item);
+            return;
+        }
+        for cell in cells(cell_size, rect) {
+            self.cells.entry(cell).or_default().push(item);
+        }
+    }
+
+    // Take out an item inserted with `rect`
+    fn remove(&mut self, cell_size: f64, item: usize, rect: Option<Rect>) {
+        let Some(rect) = rect else { return };
+        if cell_count(cell_size, rect) > spatial::MAX_ITEM_CELLS {
+            self.large.retain(|&i| i != item);
+            return;
+        }
+        for cell in cells(cell_size, rect) {
+            if let Some(items) = self.cells.get_mut(&cell) {
+                items.retain(|&i| i != item);
+                if items.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    fn query(&self, cell_size: f64, rect: Rect, count: usize, bounds: impl Fn(usize) -> Option<Rect>) -> Vec<usize> {
+        let hit = |&i: &usize| bounds(i).is_some_and(|b| overlaps(b, rect));
+        // A query covering more cells than there are items, e.g. a zoomed out
+        // view, is cheaper as a plain scan
+        if cell_count(cell_size, rect) > count as u64 {
+            return (0..count).filter(hit).collect();
+        }
+        let mut found: Vec<usize> = cells(cell_size, rect)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .chain(&self.large)
+            .copied()
+            .filter(hit)
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+}
+
+fn cell_count(cell_size: f64, rect: Rect) -> u64 {
+    let (min, max) = cell_range(cell_size, rect);
+    let span = |lo: i64, hi: i64| hi.saturating_sub(lo).saturating_add(1).max(0) as u64;
+    span(min.0, max.0).saturating_mul(span(min.1, max.1))
+}
+
+fn cell_range(cell_size: f64, rect: Rect) -> (Cell, Cell) {
+    let cell = |v: f64| (v / cell_size).floor() as i64;
+    (
+        (cell(rect.pos.x), cell(rect.pos.y)),
+        (cell(rect.pos.x + rect.size.x), cell(rect.pos.y + rect.size.y)),
+    )
+}
+
+fn cells(cell_size: f64, rect: Rect) -> impl Iterator<Item = Cell> {
+    let (min, max) = cell_range(cell_size, rect);
+    (min.0..=max.0).flat_map(move |x| (min.1..=max.1).map(move |y| (x, y)))
+}
+
+fn overlaps(a: Rect, b: Rect) -> bool {
+    a.pos.x <= b.pos.x + b.size.x && b.pos.x <= a.pos.x + a.size.x &&
+    a.pos.y <= b.pos.y + b.size.y && b.pos.y <= a.pos.y + a.size.y
+}
+
+fn inflate(rect: Rect, margin: f64) -> Rect {
+    Rect {
+        pos: DVec2 { x: rect.pos.x - margin, y: rect.pos.y - margin },
+        size: DVec2 { x: rect.size.x + margin * 2.0, y: rect.size.y + margin * 2.0 },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_after_moves_matches_rebuild() {
+        let mut graph = FlowGraph::new();
+        for i in 0..20 {
+            let (x, y) = ((i % 5) as f64 * 300.0, (i / 5) as f64 * 300.0);
+            graph.add_node(FlowNode::new_dataflow(&format!("n{}", i), x, y, "n", NodeCategory::default(), vec![Port::new("in")], vec![Port::new("out")]));
+        }
+        for i in 0..19 {
+            graph.connect(EdgeConnection::new_with_ports(&format!("n{}", i), "out", &format!("n{}", i + 1), "in"));
+        }
+        let mut index = SpatialIndex::default();
+        index.update(&graph, false, |_, edge| SpatialIndex::edge_bounds(&graph, edge));
+
+        let structure = graph.structure_revision();
+        for step in 0..40 {
+            graph.move_node(&format!("n{}", step % 20), (step * 370 % 1500) as f64, (step * 910 % 1200) as f64);
+            assert_eq!(graph.structure_revision(), structure);
+            index.update(&graph, false, |_, edge| SpatialIndex::edge_bounds(&graph, edge));
+            assert!(index.is_current(&graph));
+
+            let mut rebuilt = SpatialIndex::default();
+            rebuilt.rebuild(&graph, |_, edge| SpatialIndex::edge_bounds(&graph, edge));
+            for x in (0..1800).step_by(150) {
+                for y in (0..1500).step_by(150) {
+                    let rect = Rect { pos: DVec2 { x: x as f64, y: y as f64 }, size: DVec2 { x: 120.0, y: 90.0 } };
+                    assert_eq!(index.nodes_in(rect), rebuilt.nodes_in(rect));
+                    assert_eq!(index.edges_in(rect), rebuilt.edges_in(rect));
+                }
+            }
+        }
+    }
+}
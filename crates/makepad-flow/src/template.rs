@@ -0,0 +1,187 @@
+//! Node templates
+//!
+//! A [`NodeTemplate`] describes a kind of node an application offers: its
+//! title, default ports, shape, category and size. Templates live in a
+//! [`NodeTemplateRegistry`] keyed by id, and nodes are created from them with
+//! [`NodeTemplate::instantiate`]:
+//!
+//! ```ignore
+//! let mut templates = NodeTemplateRegistry::new();
+//! templates.register(
+//!     NodeTemplate::new("llm", "LLM")
+//!         .with_inputs(vec![Port::new("prompt")])
+//!         .with_outputs(vec![Port::new("text"), Port::new("tokens")])
+//!         .with_category("MaaS"),
+//! );
+//! let node = templates.get("llm").unwrap().instantiate("llm_1", 100.0, 80.0);
+//! ```
+//!
+//! Nothing about a node depends on the template afterwards; which ports it has
+//! is decided by its own port lists.
+
+use crate::constants::node;
+use crate::graph::{FlowNode, Metadata, NodeCategory, NodeShape, Port};
+
+/// A kind of node that can be created on the canvas
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeTemplate {
+    /// Key in the registry; copied to [`FlowNode::template`]
+    pub id: String,
+    pub title: String,
+    pub inputs: Vec<Port>,
+    pub outputs: Vec<Port>,
+    pub shape: NodeShape,
+    pub category: NodeCategory,
+    pub width: f64,
+    /// `None` fits the header and every port row
+    pub height: Option<f64>,
+}
+
+impl NodeTemplate {
+    pub fn new(id: &str, title: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            title: title.to_string(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            shape: NodeShape::RoundedRect,
+            category: NodeCategory::default(),
+            width: node::WIDTH,
+            height: None,
+        }
+    }
+
+    pub fn with_inputs(mut self, inputs: Vec<Port>) -> Self {
+        self.inputs = inputs;
+        self
+    }
+
+    pub fn with_outputs(mut self, outputs: Vec<Port>) -> Self {
+        self.outputs = outputs;
+        self
+    }
+
+    pub fn with_shape(mut self, shape: NodeShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    pub fn with_category(mut self, category: impl Into<NodeCategory>) -> Self {
+        self.category = category.into();
+        self
+    }
+
+    pub fn with_size(mut self, width: f64, height: f64) -> Self {
+        self.width = width;
+        self.height = Some(height);
+        self
+    }
+
+    /// Size of nodes created from this template
+    pub fn size(&self) -> (f64, f64) {
+        let height = self.height.unwrap_or_else(|| {
+            FlowNode::height_for_ports(self.inputs.len().max(self.outputs.len()))
+        });
+        (self.width, height)
+    }
+
+    /// New node of this kind with its top-left corner at `x`, `y`
+    pub fn instantiate(&self, id: &str, x: f64, y: f64) -> FlowNode {
+        let (width, height) = self.size();
+        FlowNode {
+            id: id.to_string(),
+            x, y,
+            width,
+            height,
+            template: Some(self.id.clone()),
+            title: self.title.clone(),
+            shape: self.shape,
+            border_width: node::BORDER_WIDTH,
+            category: self.category.clone(),
+            input_ports: self.inputs.clone(),
+            output_ports: self.outputs.clone(),
+            pinned: false,
+            parent: None,
+            group: None,
+            metadata: Metadata::new(),
+        }
+    }
+}
+
+/// Node templates by id, in registration order
+#[derive(Clone, Debug, Default)]
+pub struct NodeTemplateRegistry {
+    templates: Vec<NodeTemplate>,
+}
+
+impl NodeTemplateRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The camera pipeline kinds the demo graph is built from
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register(
+            NodeTemplate::new("camera", "Camera")
+                .with_outputs(vec![Port::new("image")])
+                .with_size(node::WIDTH, node::CAMERA_HEIGHT),
+        );
+        for (id, title) in [("detector", "Object Detector"), ("tracker", "Tracker"), ("processor", "Processor")] {
+            registry.register(
+                NodeTemplate::new(id, title)
+                    .with_inputs(vec![Port::new("input")])
+                    .with_outputs(vec![Port::new("output")])
+                    .with_size(node::WIDTH, node::DEFAULT_HEIGHT),
+            );
+        }
+        registry.register(
+            NodeTemplate::new("visualizer", "Visualizer")
+                .with_inputs(vec![Port::new("input")])
+                .with_size(node::WIDTH, node::DEFAULT_HEIGHT),
+        );
+        registry
+    }
+
+    /// Add a template, replacing any template already registered under its id
+    pub fn register(&mut self, template: NodeTemplate) {
+        match self.templates.iter_mut().find(|t| t.id == template.id) {
+            Some(existing) => *existing = template,
+            None => self.templates.push(template),
+        }
+    }
+
+    pub fn unregister(&mut self, id: &str) {
+        self.templates.retain(|t| t.id != id);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&NodeTemplate> {
+        self.templates.iter().find(|t| t.id == id)
+    }
+
+    /// Registered templates, in registration order
+    pub fn templates(&self) -> impl Iterator<Item = &NodeTemplate> {
+        self.templates.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+
+    /// Templates whose title or id contains `query`, ignoring case, grouped by
+    /// category. Groups are in the order their first template was registered.
+    pub fn grouped(&self, query: &str) -> Vec<(&NodeCategory, Vec<&NodeTemplate>)> {
+        let query = query.to_lowercase();
+        let mut groups: Vec<(&NodeCategory, Vec<&NodeTemplate>)> = Vec::new();
+        let matching = self.templates.iter().filter(|t| {
+            query.is_empty() || t.title.to_lowercase().contains(&query) || t.id.to_lowercase().contains(&query)
+        });
+        for template in matching {
+            match groups.iter_mut().find(|(c, _)| *c == &template.category) {
+                Some((_, members)) => members.push(template),
+                None => groups.push((&template.category, vec![template])),
+            }
+        }
+        groups
+    }
+}
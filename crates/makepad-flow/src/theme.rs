@@ -0,0 +1,259 @@
+//! Color themes
+//!
+//! [`FlowTheme`] holds every color the canvas, the minimap and the panels
+//! around them draw with. [`FlowTheme::light`] matches the widgets' DSL
+//! defaults; [`FlowTheme::dark`] is its dark counterpart. Themes can be
+//! switched at any time without touching the graph:
+//!
+//! ```ignore
+//! let theme = FlowTheme::dark();
+//! self.ui.flow_canvas(ids!(canvas)).set_theme(cx, &theme);
+//! self.ui.flow_minimap(ids!(minimap)).set_theme(cx, &theme);
+//! ```
+//!
+//! Node colors of registered categories come from the
+//! [`CategoryRegistry`](crate::category::CategoryRegistry), not the theme.
+
+use makepad_widgets::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlowTheme {
+    pub is_dark: bool,
+
+    // Canvas
+    pub background: Vec4,
+    pub grid: Vec4,
+    pub selection: Vec4,
+    pub selection_box_fill: Vec4,
+    pub selection_box_border: Vec4,
+
+    // Edges
+    pub edge: Vec4,
+    pub edge_selected: Vec4,
+    pub edge_valid: Vec4,
+    pub edge_invalid: Vec4,
+    pub edge_label: Vec4,
+    pub edge_label_selected: Vec4,
+    pub edge_label_background: Vec4,
+
+    // Nodes without a registered category
+    pub node_body: Vec4,
+    pub node_header: Vec4,
+    pub node_body_selected: Vec4,
+    pub node_header_selected: Vec4,
+    /// Border of rounded rectangle nodes
+    pub node_border: Vec4,
+    /// Border of rectangle, round and diamond nodes
+    pub node_outline: Vec4,
+    pub node_title: Vec4,
+    pub port_label: Vec4,
+    pub input_port: Vec4,
+    pub output_port: Vec4,
+    /// Zoomed out nodes, see `DetailLevel`
+    pub block: Vec4,
+    pub block_title: Vec4,
+    pub group_title: Vec4,
+    /// Inside of resize handles, which are outlined in `selection`
+    pub handle: Vec4,
+
+    // Context menus
+    pub menu_background: Vec4,
+    pub menu_border: Vec4,
+    pub menu_heading: Vec4,
+    pub menu_text: Vec4,
+
+    // Minimap
+    pub minimap_background: Vec4,
+    pub minimap_viewport: Vec4,
+
+    // Panels next to the canvas: toolbars, trees, lists
+    pub panel_background: Vec4,
+    pub panel_surface: Vec4,
+    pub panel_divider: Vec4,
+    pub text: Vec4,
+    pub text_muted: Vec4,
+    pub text_hint: Vec4,
+    pub input_background: Vec4,
+    pub button_background: Vec4,
+    pub button_text: Vec4,
+    pub accent: Vec4,
+    pub accent_soft: Vec4,
+    pub accent_strong: Vec4,
+    pub on_accent: Vec4,
+    pub positive: Vec4,
+    pub positive_soft: Vec4,
+    pub warning: Vec4,
+    pub warning_soft: Vec4,
+    pub negative: Vec4,
+
+    // Log panel
+    pub log_background: Vec4,
+    pub log_control: Vec4,
+    pub log_control_active: Vec4,
+    pub log_text: Vec4,
+    pub log_muted: Vec4,
+    pub log_node: Vec4,
+    pub log_debug: Vec4,
+    pub log_info: Vec4,
+    pub log_warn: Vec4,
+    pub log_error: Vec4,
+    pub log_danger_background: Vec4,
+}
+
+impl Default for FlowTheme {
+    fn default() -> Self {
+        Self::light()
+    }
+}
+
+impl FlowTheme {
+    /// White nodes with colored headers on a light gray canvas
+    pub fn light() -> Self {
+        Self {
+            is_dark: false,
+
+            background: vec4(0.94, 0.94, 0.94, 1.0),             // #f0f0f0
+            grid: vec4(0.816, 0.816, 0.816, 1.0),                // #d0d0d0
+            selection: vec4(0.29, 0.565, 0.851, 1.0),            // #4A90D9
+            selection_box_fill: vec4(0.3, 0.5, 0.8, 0.15),
+            selection_box_border: vec4(0.4, 0.6, 1.0, 0.6),
+
+            edge: vec4(0.549, 0.749, 1.0, 1.0),                  // #8CBFFF
+            edge_selected: vec4(1.0, 0.851, 0.4, 1.0),           // #FFD966
+            edge_valid: vec4(0.298, 0.686, 0.314, 1.0),          // #4CAF50
+            edge_invalid: vec4(0.898, 0.224, 0.208, 1.0),        // #E53935
+            edge_label: vec4(0.4, 0.4, 0.45, 1.0),
+            edge_label_selected: vec4(0.2, 0.4, 0.7, 1.0),
+            edge_label_background: vec4(1.0, 1.0, 1.0, 0.95),
+
+            node_body: vec4(1.0, 1.0, 1.0, 1.0),
+            node_header: vec4(0.97, 0.97, 0.97, 1.0),
+            node_body_selected: vec4(0.93, 0.96, 1.0, 1.0),
+            node_header_selected: vec4(0.90, 0.93, 0.98, 1.0),
+            node_border: vec4(0.88, 0.88, 0.88, 1.0),            // #e0e0e0
+            node_outline: vec4(0.4, 0.4, 0.5, 0.6),
+            node_title: vec4(0.88, 0.88, 0.88, 1.0),
+            port_label: vec4(0.7, 0.7, 0.8, 1.0),
+            input_port: vec4(0.23, 0.51, 0.96, 1.0),
+            output_port: vec4(0.13, 0.77, 0.37, 1.0),
+            block: vec4(0.85, 0.85, 0.87, 1.0),
+            block_title: vec4(0.2, 0.2, 0.25, 1.0),
+            group_title: vec4(0.35, 0.35, 0.4, 1.0),
+            handle: vec4(1.0, 1.0, 1.0, 1.0),
+
+            menu_background: vec4(1.0, 1.0, 1.0, 0.98),
+            menu_border: vec4(0.88, 0.88, 0.88, 1.0),            // #e0e0e0
+            menu_heading: vec4(0.6, 0.6, 0.6, 1.0),              // #999999
+            menu_text: vec4(0.2, 0.2, 0.2, 1.0),                 // #333333
+
+            minimap_background: vec4(0.98, 0.98, 0.98, 1.0),     // #fafafa
+            minimap_viewport: vec4(0.29, 0.565, 0.851, 0.133),   // #4A90D922
+
+            panel_background: vec4(0.973, 0.973, 0.973, 1.0),   // #f8f8f8
+            panel_surface: vec4(1.0, 1.0, 1.0, 1.0),
+            panel_divider: vec4(0.88, 0.88, 0.88, 1.0),          // #e0e0e0
+            text: vec4(0.2, 0.2, 0.2, 1.0),                      // #333333
+            text_muted: vec4(0.4, 0.4, 0.4, 1.0),                // #666666
+            text_hint: vec4(0.533, 0.533, 0.533, 1.0),           // #888888
+            input_background: vec4(0.961, 0.961, 0.961, 1.0),   // #f5f5f5
+            button_background: vec4(0.91, 0.91, 0.91, 1.0),     // #e8e8e8
+            button_text: vec4(0.333, 0.333, 0.333, 1.0),         // #555555
+            accent: vec4(0.29, 0.565, 0.851, 1.0),               // #4A90D9
+            accent_soft: vec4(0.91, 0.957, 0.992, 1.0),          // #e8f4fd
+            accent_strong: vec4(0.816, 0.91, 1.0, 1.0),          // #d0e8ff
+            on_accent: vec4(1.0, 1.0, 1.0, 1.0),
+            positive: vec4(0.133, 0.773, 0.369, 1.0),            // #22c55e
+            positive_soft: vec4(0.902, 0.969, 0.929, 1.0),       // #e6f7ed
+            warning: vec4(0.961, 0.62, 0.043, 1.0),              // #f59e0b
+            warning_soft: vec4(0.996, 0.953, 0.886, 1.0),        // #fef3e2
+            negative: vec4(0.937, 0.267, 0.267, 1.0),            // #ef4444
+
+            log_background: vec4(0.102, 0.102, 0.18, 1.0),       // #1a1a2e
+            log_control: vec4(0.239, 0.239, 0.361, 1.0),         // #3d3d5c
+            log_control_active: vec4(0.29, 0.29, 0.416, 1.0),    // #4a4a6a
+            log_text: vec4(0.8, 0.8, 0.8, 1.0),                  // #cccccc
+            log_muted: vec4(0.4, 0.4, 0.502, 1.0),               // #666680
+            log_node: vec4(0.533, 0.533, 0.667, 1.0),            // #8888aa
+            log_debug: vec4(0.533, 0.533, 0.533, 1.0),           // #888888
+            log_info: vec4(0.533, 0.8, 0.533, 1.0),              // #88cc88
+            log_warn: vec4(0.8, 0.8, 0.533, 1.0),                // #cccc88
+            log_error: vec4(0.8, 0.533, 0.533, 1.0),             // #cc8888
+            log_danger_background: vec4(0.353, 0.239, 0.239, 1.0), // #5a3d3d
+        }
+    }
+
+    /// Dark gray nodes on a near-black canvas
+    pub fn dark() -> Self {
+        Self {
+            is_dark: true,
+
+            background: vec4(0.11, 0.11, 0.14, 1.0),
+            grid: vec4(0.22, 0.22, 0.27, 1.0),
+            selection: vec4(0.36, 0.62, 0.92, 1.0),
+            selection_box_fill: vec4(0.3, 0.5, 0.8, 0.2),
+            selection_box_border: vec4(0.45, 0.65, 1.0, 0.7),
+
+            edge: vec4(0.45, 0.62, 0.85, 1.0),
+            edge_selected: vec4(1.0, 0.82, 0.35, 1.0),
+            edge_valid: vec4(0.4, 0.75, 0.42, 1.0),
+            edge_invalid: vec4(0.94, 0.33, 0.31, 1.0),
+            edge_label: vec4(0.75, 0.75, 0.8, 1.0),
+            edge_label_selected: vec4(0.55, 0.75, 1.0, 1.0),
+            edge_label_background: vec4(0.16, 0.16, 0.2, 0.95),
+
+            node_body: vec4(0.17, 0.17, 0.21, 1.0),
+            node_header: vec4(0.22, 0.22, 0.27, 1.0),
+            node_body_selected: vec4(0.18, 0.21, 0.28, 1.0),
+            node_header_selected: vec4(0.22, 0.27, 0.36, 1.0),
+            node_border: vec4(0.3, 0.3, 0.36, 1.0),
+            node_outline: vec4(0.5, 0.5, 0.6, 0.7),
+            node_title: vec4(0.9, 0.9, 0.92, 1.0),
+            port_label: vec4(0.65, 0.65, 0.75, 1.0),
+            input_port: vec4(0.33, 0.58, 0.98, 1.0),
+            output_port: vec4(0.2, 0.8, 0.45, 1.0),
+            block: vec4(0.3, 0.3, 0.36, 1.0),
+            block_title: vec4(0.9, 0.9, 0.92, 1.0),
+            group_title: vec4(0.7, 0.7, 0.76, 1.0),
+            handle: vec4(0.17, 0.17, 0.21, 1.0),
+
+            menu_background: vec4(0.16, 0.16, 0.2, 0.98),
+            menu_border: vec4(0.3, 0.3, 0.36, 1.0),
+            menu_heading: vec4(0.55, 0.55, 0.6, 1.0),
+            menu_text: vec4(0.88, 0.88, 0.9, 1.0),
+
+            minimap_background: vec4(0.13, 0.13, 0.17, 1.0),
+            minimap_viewport: vec4(0.36, 0.62, 0.92, 0.15),
+
+            panel_background: vec4(0.14, 0.14, 0.18, 1.0),
+            panel_surface: vec4(0.12, 0.12, 0.15, 1.0),
+            panel_divider: vec4(0.24, 0.24, 0.29, 1.0),
+            text: vec4(0.88, 0.88, 0.9, 1.0),
+            text_muted: vec4(0.62, 0.62, 0.68, 1.0),
+            text_hint: vec4(0.5, 0.5, 0.56, 1.0),
+            input_background: vec4(0.2, 0.2, 0.25, 1.0),
+            button_background: vec4(0.22, 0.22, 0.27, 1.0),
+            button_text: vec4(0.8, 0.8, 0.84, 1.0),
+            accent: vec4(0.36, 0.62, 0.92, 1.0),
+            accent_soft: vec4(0.18, 0.24, 0.33, 1.0),
+            accent_strong: vec4(0.22, 0.32, 0.46, 1.0),
+            on_accent: vec4(1.0, 1.0, 1.0, 1.0),
+            positive: vec4(0.2, 0.7, 0.4, 1.0),
+            positive_soft: vec4(0.15, 0.27, 0.2, 1.0),
+            warning: vec4(0.96, 0.68, 0.2, 1.0),
+            warning_soft: vec4(0.3, 0.24, 0.14, 1.0),
+            negative: vec4(0.86, 0.3, 0.3, 1.0),
+
+            log_background: vec4(0.08, 0.08, 0.11, 1.0),
+            log_control: vec4(0.2, 0.2, 0.27, 1.0),
+            log_control_active: vec4(0.27, 0.27, 0.36, 1.0),
+            log_text: vec4(0.82, 0.82, 0.84, 1.0),
+            log_muted: vec4(0.45, 0.45, 0.55, 1.0),
+            log_node: vec4(0.55, 0.55, 0.7, 1.0),
+            log_debug: vec4(0.55, 0.55, 0.55, 1.0),
+            log_info: vec4(0.53, 0.8, 0.53, 1.0),
+            log_warn: vec4(0.85, 0.82, 0.5, 1.0),
+            log_error: vec4(0.88, 0.55, 0.55, 1.0),
+            log_danger_background: vec4(0.33, 0.2, 0.2, 1.0),
+        }
+    }
+}
@@ -1,10 +1,11 @@
 use makepad_flow::*;
+use makepad_flow::dora::{self, DoraDataflow};
 use makepad_widgets::*;
 use std::collections::HashMap;
-use serde::Deserialize;
 
 use crate::dataflow_tree::{DataflowTreeWidgetRefExt, DataflowTreeHeaderWidgetRefExt, DataflowTreeFooterWidgetRefExt, DataflowTreeAction, TreeNode, TreePort};
 use crate::log_panel::{LogPanelWidgetRefExt, LogEntry, LogLevel};
+use crate::theme::{style_background, style_button, style_label};
 
 live_design! {
     use link::theme::*;
@@ -47,7 +48,7 @@ live_design! {
                     padding: { left: 16, right: 16 }, spacing: 12, align: { y: 0.5 }
                     show_bg: true, draw_bg: { color: #ffffff }
 
-                    <Label> {
+                    title_label = <Label> {
                         draw_text: { text_style: <FONT_MANROPE> { font_size: 16.0 }, color: #333333 }
                         text: "DORA Viewer"
                     }
@@ -65,7 +66,8 @@ live_design! {
                         draw_bg: { color: #4A90D9, border_radius: 6.0 }
                         draw_text: {
                             text_style: <FONT_MANROPE> { font_size: 13.0 }
-                            fn get_color(self) -> vec4 { return #ffffff; }
+                            color: #ffffff
+                            fn get_color(self) -> vec4 { return self.color; }
                         }
                         text: "Reload"
                     }
@@ -75,10 +77,55 @@ live_design! {
                         draw_bg: { color: #4A90D9, border_radius: 6.0 }
                         draw_text: {
                             text_style: <FONT_MANROPE> { font_size: 13.0 }
-                            fn get_color(self) -> vec4 { return #ffffff; }
+                            color: #ffffff
+                            fn get_color(self) -> vec4 { return self.color; }
                         }
                         text: "Fit View"
                     }
+
+                    relax_btn = <Button> {
+                        width: Fit, height: 32, padding: { left: 16, right: 16 }
+                        draw_bg: { color: #4A90D9, border_radius: 6.0 }
+                        draw_text: {
+                            text_style: <FONT_MANROPE> { font_size: 13.0 }
+                            color: #ffffff
+                            fn get_color(self) -> vec4 { return self.color; }
+                        }
+                        text: "Relax"
+                    }
+
+                    fold_btn = <Button> {
+                        width: Fit, height: 32, padding: { left: 16, right: 16 }
+                        draw_bg: { color: #4A90D9, border_radius: 6.0 }
+                        draw_text: {
+                            text_style: <FONT_MANROPE> { font_size: 13.0 }
+                            color: #ffffff
+                            fn get_color(self) -> vec4 { return self.color; }
+                        }
+                        text: "Fold"
+                    }
+
+                    export_btn = <Button> {
+                        width: Fit, height: 32, padding: { left: 16, right: 16 }
+                        draw_bg: { color: #4A90D9, border_radius: 6.0 }
+                        draw_text: {
+                            text_style: <FONT_MANROPE> { font_size: 13.0 }
+                            color: #ffffff
+                            fn get_color(self) -> vec4 { return self.color; }
+                        }
+                        text: "Export"
+                    }
+
+                    theme_btn = <Button> {
+                        width: Fit, height: 32, padding: { left: 16, right: 16 }
+                        draw_bg: { color: #4A90D9, border_radius: 6.0 }
+                        draw_text: {
+                            text_style: <FONT_MANROPE> { font_size: 13.0 }
+                            color: #ffffff
+                            fn get_color(self) -> vec4 { return self.color; }
+                        }
+                        text: "Dark"
+                    }
                 }
 
                 // Main area with panels
@@ -91,12 +138,12 @@ live_design! {
                         show_bg: true, draw_bg: { color: #ffffff }
 
                         // Panel header
-                        <View> {
+                        tree_title_bar = <View> {
                             width: Fill, height: 40
                             padding: { left: 16, right: 16 }, align: { y: 0.5 }
                             show_bg: true, draw_bg: { color: #f8f8f8 }
 
-                            <Label> {
+                            tree_title = <Label> {
                                 draw_text: { text_style: <FONT_MANROPE> { font_size: 13.0 }, color: #333333 }
                                 text: "Dataflow Tree"
                             }
@@ -110,6 +157,11 @@ live_design! {
 
                         // Footer with batch actions
                         tree_footer = <DataflowTreeFooter> {}
+
+                        // Overview of the whole flow; click or drag to pan the canvas
+                        minimap = <FlowMinimap> {
+                            width: Fill, height: 160
+                        }
                     }
 
                     // Left splitter
@@ -118,6 +170,8 @@ live_design! {
                     // ========== CENTER PANEL: FlowCanvas ==========
                     canvas = <FlowCanvas> {
                         width: Fill, height: Fill
+                        // Dense dataflows: keep wires out of unrelated nodes
+                        route_edges: true
                     }
                 }
 
@@ -131,27 +185,27 @@ live_design! {
                     <View> { flow: Right, spacing: 12, align: { y: 0.5 }
                         <View> { flow: Right, spacing: 4, align: { y: 0.5 }
                             <RoundedView> { width: 10, height: 10, draw_bg: { color: #4a90d9, border_radius: 2.0 } }
-                            <Label> { draw_text: { text_style: <FONT_MANROPE> { font_size: 9.0 }, color: #666666 }, text: "MaaS" }
+                            legend_maas = <Label> { draw_text: { text_style: <FONT_MANROPE> { font_size: 9.0 }, color: #666666 }, text: "MaaS" }
                         }
                         <View> { flow: Right, spacing: 4, align: { y: 0.5 }
                             <RoundedView> { width: 10, height: 10, draw_bg: { color: #22c55e, border_radius: 2.0 } }
-                            <Label> { draw_text: { text_style: <FONT_MANROPE> { font_size: 9.0 }, color: #666666 }, text: "TTS" }
+                            legend_tts = <Label> { draw_text: { text_style: <FONT_MANROPE> { font_size: 9.0 }, color: #666666 }, text: "TTS" }
                         }
                         <View> { flow: Right, spacing: 4, align: { y: 0.5 }
                             <RoundedView> { width: 10, height: 10, draw_bg: { color: #f59e0b, border_radius: 2.0 } }
-                            <Label> { draw_text: { text_style: <FONT_MANROPE> { font_size: 9.0 }, color: #666666 }, text: "Bridge" }
+                            legend_bridge = <Label> { draw_text: { text_style: <FONT_MANROPE> { font_size: 9.0 }, color: #666666 }, text: "Bridge" }
                         }
                         <View> { flow: Right, spacing: 4, align: { y: 0.5 }
                             <RoundedView> { width: 10, height: 10, draw_bg: { color: #ef4444, border_radius: 2.0 } }
-                            <Label> { draw_text: { text_style: <FONT_MANROPE> { font_size: 9.0 }, color: #666666 }, text: "Controller" }
+                            legend_controller = <Label> { draw_text: { text_style: <FONT_MANROPE> { font_size: 9.0 }, color: #666666 }, text: "Controller" }
                         }
                         <View> { flow: Right, spacing: 4, align: { y: 0.5 }
                             <RoundedView> { width: 10, height: 10, draw_bg: { color: #8b5cf6, border_radius: 2.0 } }
-                            <Label> { draw_text: { text_style: <FONT_MANROPE> { font_size: 9.0 }, color: #666666 }, text: "MoFA" }
+                            legend_mofa = <Label> { draw_text: { text_style: <FONT_MANROPE> { font_size: 9.0 }, color: #666666 }, text: "MoFA" }
                         }
                         <View> { flow: Right, spacing: 4, align: { y: 0.5 }
                             <RoundedView> { width: 10, height: 10, draw_bg: { color: #06b6d4, border_radius: 2.0 } }
-                            <Label> { draw_text: { text_style: <FONT_MANROPE> { font_size: 9.0 }, color: #666666 }, text: "Segmenter" }
+                            legend_segmenter = <Label> { draw_text: { text_style: <FONT_MANROPE> { font_size: 9.0 }, color: #666666 }, text: "Segmenter" }
                         }
                     }
 
@@ -169,288 +223,70 @@ live_design! {
 
 // ============ YAML Dataflow Parsing ============
 
-#[derive(Debug, Deserialize)]
-struct DataflowYaml {
-    nodes: Vec<DataflowNodeYaml>,
-}
-
-#[derive(Debug, Deserialize)]
-struct DataflowNodeYaml {
-    id: String,
-    #[serde(default)]
-    path: Option<String>,
-    #[serde(default)]
-    inputs: Option<serde_yaml::Value>,
-    #[serde(default)]
-    outputs: Option<Vec<String>>,
-}
-
-fn parse_dataflow_yaml(yaml_content: &str) -> Result<(Vec<FlowNode>, Vec<EdgeConnection>), String> {
-    let dataflow: DataflowYaml = serde_yaml::from_str(yaml_content)
-        .map_err(|e| format!("YAML parse error: {}", e))?;
-
-    let mut nodes_data: Vec<(FlowNode, Vec<(String, String, String)>)> = Vec::new();
-    let mut edges = Vec::new();
-    let mut node_id_to_index: HashMap<String, usize> = HashMap::new();
-
-    // First pass: create all nodes with temporary positions
-    for (i, node_yaml) in dataflow.nodes.iter().enumerate() {
-        let mut input_ports = Vec::new();
-        let mut input_sources: Vec<(String, String, String)> = Vec::new();
-
-        if let Some(inputs) = &node_yaml.inputs {
-            match inputs {
-                serde_yaml::Value::Mapping(map) => {
-                    for (key, value) in map {
-                        if let Some(port_name) = key.as_str() {
-                            input_ports.push(Port::new(port_name));
-
-                            let source = match value {
-                                serde_yaml::Value::String(s) => Some(s.clone()),
-                                serde_yaml::Value::Mapping(m) => {
-                                    m.get(&serde_yaml::Value::String("source".to_string()))
-                                        .and_then(|v| v.as_str())
-                                        .map(|s| s.to_string())
-                                }
-                                _ => None,
-                            };
-
-                            if let Some(src) = source {
-                                if let Some((src_node, src_port)) = src.split_once('/') {
-                                    input_sources.push((
-                                        port_name.to_string(),
-                                        src_node.to_string(),
-                                        src_port.to_string(),
-                                    ));
-                                }
-                            }
-                        }
-                    }
-                }
-                _ => {}
-            }
-        }
-
-        let output_ports: Vec<Port> = node_yaml.outputs
-            .as_ref()
-            .map(|outputs| outputs.iter().map(|s| Port::new(s)).collect())
-            .unwrap_or_default();
-
-        let category = categorize_node(&node_yaml.id, node_yaml.path.as_deref());
-
-        // Temporary position (will be updated by layout algorithm)
-        let node = FlowNode::new_dataflow(
-            &node_yaml.id,
-            0.0, 0.0,
-            &node_yaml.id,
-            category,
-            input_ports,
-            output_ports,
-        );
-
-        node_id_to_index.insert(node_yaml.id.clone(), i);
-        nodes_data.push((node, input_sources));
-    }
-
-    // Second pass: create edges
-    for (to_node_idx, (_, input_sources)) in nodes_data.iter().enumerate() {
-        for (to_port, from_node_id, from_port) in input_sources {
-            if let Some(&from_node_idx) = node_id_to_index.get(from_node_id) {
-                edges.push(EdgeConnection::new_with_ports(
-                    from_node_idx,
-                    from_port,
-                    to_node_idx,
-                    to_port,
-                ));
-            }
-        }
-    }
-
-    // Category-based layout: group nodes by category and arrange in columns
-    // Define column order for categories (data flow direction: left to right)
-    fn category_to_column(cat: NodeCategory) -> usize {
-        match cat {
-            NodeCategory::MaaS => 0,        // Source: LLM clients
-            NodeCategory::Bridge => 1,      // Bridge/Switch
-            NodeCategory::Segmenter => 2,   // Text processing
-            NodeCategory::TTS => 3,         // TTS conversion
-            NodeCategory::Controller => 2,  // Controller (middle)
-            NodeCategory::MoFA => 4,        // MoFA nodes (output)
-            NodeCategory::Default => 2,     // Default in middle
-        }
-    }
-
-    // Build connection info for barycenter calculation
-    let mut incoming: Vec<Vec<usize>> = vec![Vec::new(); nodes_data.len()];
-    let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); nodes_data.len()];
-    for edge in &edges {
-        incoming[edge.to_node].push(edge.from_node);
-        outgoing[edge.from_node].push(edge.to_node);
-    }
-
-    // Group nodes by their column
-    let max_col = 4;
-    let mut columns: Vec<Vec<usize>> = vec![Vec::new(); max_col + 1];
-
-    for (idx, (node, _)) in nodes_data.iter().enumerate() {
-        let col = category_to_column(node.category);
-        columns[col].push(idx);
-    }
-
-    // Initial sort by name
-    for col in &mut columns {
-        col.sort_by(|&a, &b| nodes_data[a].0.id.cmp(&nodes_data[b].0.id));
-    }
-
-    // Assign initial positions for barycenter calculation
-    let mut positions: Vec<f64> = vec![0.0; nodes_data.len()];
-    for (col_idx, nodes_in_col) in columns.iter().enumerate() {
-        for (row_idx, &node_idx) in nodes_in_col.iter().enumerate() {
-            positions[node_idx] = row_idx as f64;
-        }
-    }
-
-    // Barycenter optimization: multiple passes to minimize crossings
-    for _pass in 0..4 {
-        // Forward pass (left to right)
-        for col_idx in 1..=max_col {
-            if columns[col_idx].is_empty() {
-                continue;
-            }
-
-            // Calculate barycenter for each node based on connected nodes in previous columns
-            let mut barycenters: Vec<(usize, f64)> = columns[col_idx].iter()
-                .map(|&node| {
-                    let connected_positions: Vec<f64> = incoming[node].iter()
-                        .filter(|&&src| category_to_column(nodes_data[src].0.category) < col_idx)
-                        .map(|&src| positions[src])
-                        .collect();
-
-                    let barycenter = if connected_positions.is_empty() {
-                        positions[node] // Keep current position
-                    } else {
-                        connected_positions.iter().sum::<f64>() / connected_positions.len() as f64
-                    };
-                    (node, barycenter)
-                })
-                .collect();
-
-            barycenters.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
-            columns[col_idx] = barycenters.iter().map(|(node, _)| *node).collect();
-
-            // Update positions
-            for (row_idx, &node_idx) in columns[col_idx].iter().enumerate() {
-                positions[node_idx] = row_idx as f64;
-            }
-        }
-
-        // Backward pass (right to left)
-        for col_idx in (0..max_col).rev() {
-            if columns[col_idx].is_empty() {
-                continue;
-            }
-
-            let mut barycenters: Vec<(usize, f64)> = columns[col_idx].iter()
-                .map(|&node| {
-                    let connected_positions: Vec<f64> = outgoing[node].iter()
-                        .filter(|&&dst| category_to_column(nodes_data[dst].0.category) > col_idx)
-                        .map(|&dst| positions[dst])
-                        .collect();
-
-                    let barycenter = if connected_positions.is_empty() {
-                        positions[node]
-                    } else {
-                        connected_positions.iter().sum::<f64>() / connected_positions.len() as f64
-                    };
-                    (node, barycenter)
-                })
-                .collect();
-
-            barycenters.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
-            columns[col_idx] = barycenters.iter().map(|(node, _)| *node).collect();
-
-            for (row_idx, &node_idx) in columns[col_idx].iter().enumerate() {
-                positions[node_idx] = row_idx as f64;
-            }
-        }
-    }
+/// Import a dataflow, colour nodes by category and lay it out in layers
+fn parse_dataflow_yaml(yaml_content: &str) -> Result<DoraDataflow, String> {
+    let mut dataflow = dora::import_dataflow(yaml_content)
+        .map_err(|e| e.to_string())?;
 
-    // Calculate positions with dynamic row height based on node port count
-    let col_spacing = 300.0;   // Horizontal spacing between columns
-    let base_row_spacing = 120.0;   // Base vertical spacing
-    let start_x = 50.0;
-    let start_y = 50.0;
-
-    // Calculate actual height needed for each node
-    fn node_height(node: &FlowNode) -> f64 {
-        let port_count = node.input_ports.len().max(node.output_ports.len());
-        let base_height = 60.0;
-        let port_height = 20.0;
-        base_height + (port_count as f64 * port_height)
+    let ids: Vec<String> = dataflow.graph.nodes().iter().map(|n| n.id.clone()).collect();
+    for id in &ids {
+        dataflow.graph.update_node(id, |node| {
+            let path = node.metadata.get("path").and_then(|v| v.as_str());
+            node.category = categorize_node(id, path);
+        });
     }
 
-    // Calculate total height for each column
-    let col_heights: Vec<f64> = columns.iter()
-        .map(|col| {
-            col.iter()
-                .map(|&idx| node_height(&nodes_data[idx].0) + 20.0) // 20px gap
-                .sum()
-        })
-        .collect();
-
-    let max_height = col_heights.iter().cloned().fold(0.0, f64::max);
-
-    for (col_idx, nodes_in_col) in columns.iter().enumerate() {
-        if nodes_in_col.is_empty() {
-            continue;
-        }
+    // Layers follow the data flow, so sources end up on the left
+    LayeredLayout::default().apply(&mut dataflow.graph);
 
-        // Center this column vertically
-        let col_height = col_heights[col_idx];
-        let y_offset = (max_height - col_height) / 2.0;
+    Ok(dataflow)
+}
 
-        let mut current_y = start_y + y_offset;
-        for &node_idx in nodes_in_col.iter() {
-            let x = start_x + col_idx as f64 * col_spacing;
-            nodes_data[node_idx].0.x = x;
-            nodes_data[node_idx].0.y = current_y;
-            current_y += node_height(&nodes_data[node_idx].0) + 20.0;
+/// Group every LLM participant with the nodes named after it
+/// (`bridge-to-student1`, `primespeech-student1`, ...) and collapse each group
+fn fold_pipelines(cx: &mut Cx, canvas: &FlowCanvasRef) {
+    let graph = canvas.graph();
+    for participant in graph.nodes().iter().filter(|n| n.category.name() == "MaaS") {
+        let suffix = format!("-{}", participant.id);
+        let members: Vec<String> = graph.nodes().iter()
+            .filter(|n| n.id == participant.id || n.id.ends_with(&suffix))
+            .map(|n| n.id.clone())
+            .collect();
+        if let Some(group_id) = canvas.group_nodes(cx, &participant.id, &members) {
+            canvas.set_group_collapsed(cx, &group_id, true);
         }
     }
-
-    let nodes: Vec<FlowNode> = nodes_data.into_iter().map(|(n, _)| n).collect();
-    Ok((nodes, edges))
 }
 
 fn categorize_node(id: &str, path: Option<&str>) -> NodeCategory {
     let id_lower = id.to_lowercase();
 
     if id_lower.starts_with("mofa-") {
-        return NodeCategory::MoFA;
+        return NodeCategory::new("MoFA");
     }
     if id_lower.starts_with("bridge-") {
-        return NodeCategory::Bridge;
+        return NodeCategory::new("Bridge");
     }
     if id_lower.contains("controller") {
-        return NodeCategory::Controller;
+        return NodeCategory::new("Controller");
     }
     if id_lower.contains("primespeech") || id_lower.contains("tts") {
-        return NodeCategory::TTS;
+        return NodeCategory::new("TTS");
     }
     if id_lower.contains("segmenter") {
-        return NodeCategory::Segmenter;
+        return NodeCategory::new("Segmenter");
     }
     if id_lower.contains("student") || id_lower.contains("tutor") {
-        return NodeCategory::MaaS;
+        return NodeCategory::new("MaaS");
     }
 
     if let Some(p) = path {
         if p == "dynamic" {
-            return NodeCategory::MoFA;
+            return NodeCategory::new("MoFA");
         }
     }
 
-    NodeCategory::Default
+    NodeCategory::default()
 }
 
 // ============ App State ============
@@ -460,7 +296,10 @@ pub struct App {
     #[live] ui: WidgetRef,
     #[rust] loaded_nodes: Vec<FlowNode>,
     #[rust] loaded_edges: Vec<EdgeConnection>,
+    /// Top-level dataflow keys other than `nodes`, kept for export
+    #[rust] loaded_extra: Metadata,
     #[rust] node_enabled: HashMap<String, bool>,
+    #[rust] dark_theme: bool,
     // Splitter state
     #[rust] left_panel_width: f64,
     #[rust] left_dragging: bool,
@@ -469,6 +308,9 @@ pub struct App {
 impl App {
     const MIN_LEFT_WIDTH: f64 = 200.0;
     const DEFAULT_LEFT_WIDTH: f64 = 300.0;
+    const EXPORT_FILE: &'static str = "dataflow-export.yml";
+    /// Colors of the node categories assigned by `categorize_node`
+    const CATEGORY_THEME: &'static str = include_str!("../resources/categories.json");
 }
 
 impl LiveRegister for App {
@@ -485,6 +327,12 @@ impl MatchEvent for App {
         // Initialize panel width
         self.left_panel_width = Self::DEFAULT_LEFT_WIDTH;
 
+        let canvas = self.ui.flow_canvas(ids!(canvas));
+        self.ui.flow_minimap(ids!(minimap)).set_canvas(cx, &canvas);
+        if let Err(e) = canvas.load_categories(cx, Self::CATEGORY_THEME) {
+            log!("Invalid category theme: {}", e);
+        }
+
         // Adjust toolbar padding for macOS window controls (traffic lights)
         if let OsType::Macos = cx.os_type() {
             self.ui.view(id!(toolbar)).apply_over(cx, live! {
@@ -501,7 +349,8 @@ impl MatchEvent for App {
         for path in &yaml_paths {
             if let Ok(yaml_content) = std::fs::read_to_string(path) {
                 match parse_dataflow_yaml(&yaml_content) {
-                    Ok((nodes, edges)) => {
+                    Ok(dataflow) => {
+                        let (nodes, edges) = dataflow.graph.into_parts();
                         log!("Loaded dataflow from {}: {} nodes, {} edges", path, nodes.len(), edges.len());
 
                         // Initialize node enabled state
@@ -511,6 +360,7 @@ impl MatchEvent for App {
 
                         self.loaded_nodes = nodes;
                         self.loaded_edges = edges;
+                        self.loaded_extra = dataflow.extra;
 
                         // Update UI
                         self.ui.label(ids!(file_label)).set_text(cx, path);
@@ -522,6 +372,7 @@ impl MatchEvent for App {
                             nodes: self.loaded_nodes.clone(),
                             edges: self.loaded_edges.clone(),
                         });
+                        self.ui.button(ids!(fold_btn)).set_text(cx, "Fold");
 
                         // Populate the DataflowTree
                         self.populate_dataflow_tree(cx);
@@ -554,6 +405,54 @@ impl MatchEvent for App {
             self.handle_startup(cx);
         }
 
+        // Theme button: switch between the light and dark presets
+        if self.ui.button(ids!(theme_btn)).clicked(actions) {
+            self.dark_theme = !self.dark_theme;
+            self.apply_theme(cx);
+        }
+
+        // Relax button: toggle the live force-directed layout
+        if self.ui.button(ids!(relax_btn)).clicked(actions) {
+            let canvas = self.ui.flow_canvas(ids!(canvas));
+            if canvas.is_simulating() {
+                canvas.stop_force_simulation(cx);
+                self.ui.button(ids!(relax_btn)).set_text(cx, "Relax");
+            } else {
+                canvas.start_force_simulation(cx, ForceLayout::default());
+                self.ui.button(ids!(relax_btn)).set_text(cx, "Stop");
+            }
+        }
+
+        // Fold button: collapse each participant's pipeline into one box, or dissolve the boxes
+        if self.ui.button(ids!(fold_btn)).clicked(actions) {
+            let canvas = self.ui.flow_canvas(ids!(canvas));
+            let groups: Vec<String> = canvas.graph().nodes().iter()
+                .filter(|n| n.is_group())
+                .map(|n| n.id.clone())
+                .collect();
+            if groups.is_empty() {
+                fold_pipelines(cx, &canvas);
+                self.ui.button(ids!(fold_btn)).set_text(cx, "Unfold");
+            } else {
+                for id in &groups {
+                    canvas.ungroup(cx, id);
+                }
+                self.ui.button(ids!(fold_btn)).set_text(cx, "Fold");
+            }
+        }
+
+        // Export button: write the graph on the canvas back out as dora YAML
+        if self.ui.button(ids!(export_btn)).clicked(actions) {
+            let graph = self.ui.flow_canvas(ids!(canvas)).graph();
+            match dora::export_dataflow(&graph, &self.loaded_extra) {
+                Ok(yaml) => match std::fs::write(Self::EXPORT_FILE, yaml) {
+                    Ok(()) => log!("Exported dataflow to {}", Self::EXPORT_FILE),
+                    Err(e) => log!("Failed to write {}: {}", Self::EXPORT_FILE, e),
+                },
+                Err(e) => log!("Failed to export dataflow: {}", e),
+            }
+        }
+
         // LogPanel disabled for now
         // let log_panel = self.ui.log_panel(ids!(log_panel));
         // if log_panel.clear_clicked(actions) {
@@ -636,6 +535,20 @@ impl MatchEvent for App {
 
         // Canvas status updates
         for action in actions {
+            if matches!(action.cast(), FlowCanvasAction::LayoutSettled) {
+                self.ui.button(ids!(relax_btn)).set_text(cx, "Relax");
+            }
+            // Keep positions from drags and layouts when the enabled filter reloads the canvas
+            if let FlowCanvasAction::GraphChanged { commands, .. } = action.cast() {
+                for command in &commands {
+                    if let FlowCommand::MoveNode { id, to, .. } = command {
+                        if let Some(node) = self.loaded_nodes.iter_mut().find(|n| &n.id == id) {
+                            node.x = to.x;
+                            node.y = to.y;
+                        }
+                    }
+                }
+            }
             if let FlowCanvasAction::StatusUpdate { nodes, edges } = action.cast() {
                 let enabled_count = self.node_enabled.values().filter(|&&e| e).count();
                 let text = format!("Nodes: {} | Edges: {} | Enabled: {}", nodes, edges, enabled_count);
@@ -651,6 +564,46 @@ impl App {
         tree_footer.set_node_count(cx, self.loaded_nodes.len());
     }
 
+    /// Recolor the canvas and every panel; the loaded graph stays as it is
+    fn apply_theme(&mut self, cx: &mut Cx) {
+        let theme = if self.dark_theme { FlowTheme::dark() } else { FlowTheme::light() };
+        let ui = &self.ui;
+
+        ui.flow_canvas(ids!(canvas)).set_theme(cx, &theme);
+        ui.flow_minimap(ids!(minimap)).set_theme(cx, &theme);
+        ui.dataflow_tree_header(ids!(tree_header)).set_theme(cx, &theme);
+        ui.dataflow_tree(ids!(dataflow_tree)).set_theme(cx, &theme);
+        ui.dataflow_tree_footer(ids!(tree_footer)).set_theme(cx, &theme);
+        ui.log_panel(ids!(log_panel)).set_theme(cx, &theme);
+
+        ui.apply_over(cx, live! { draw_bg: { color: (theme.background) } });
+        style_background(cx, &ui.view(ids!(toolbar)), theme.panel_surface);
+        style_background(cx, &ui.view(ids!(status_bar)), theme.panel_surface);
+        style_background(cx, &ui.view(ids!(left_panel)), theme.panel_surface);
+        style_background(cx, &ui.view(ids!(tree_title_bar)), theme.panel_background);
+        style_background(cx, &ui.view(ids!(left_splitter)), theme.panel_divider);
+
+        style_label(cx, &ui.label(ids!(title_label)), theme.text);
+        style_label(cx, &ui.label(ids!(tree_title)), theme.text);
+        style_label(cx, &ui.label(ids!(file_label)), theme.text_muted);
+        style_label(cx, &ui.label(ids!(count_label)), theme.text_muted);
+        style_label(cx, &ui.label(ids!(legend_maas)), theme.text_muted);
+        style_label(cx, &ui.label(ids!(legend_tts)), theme.text_muted);
+        style_label(cx, &ui.label(ids!(legend_bridge)), theme.text_muted);
+        style_label(cx, &ui.label(ids!(legend_controller)), theme.text_muted);
+        style_label(cx, &ui.label(ids!(legend_mofa)), theme.text_muted);
+        style_label(cx, &ui.label(ids!(legend_segmenter)), theme.text_muted);
+
+        style_button(cx, &ui.button(ids!(reload_btn)), theme.accent, theme.on_accent);
+        style_button(cx, &ui.button(ids!(fit_view_btn)), theme.accent, theme.on_accent);
+        style_button(cx, &ui.button(ids!(relax_btn)), theme.accent, theme.on_accent);
+        style_button(cx, &ui.button(ids!(fold_btn)), theme.accent, theme.on_accent);
+        style_button(cx, &ui.button(ids!(export_btn)), theme.accent, theme.on_accent);
+        style_button(cx, &ui.button(ids!(theme_btn)), theme.accent, theme.on_accent);
+        ui.button(ids!(theme_btn)).set_text(cx, if self.dark_theme { "Light" } else { "Dark" });
+        ui.redraw(cx);
+    }
+
     fn update_status_bar(&mut self, cx: &mut Cx) {
         let enabled_count = self.node_enabled.values().filter(|&&e| e).count();
         let text = format!(
@@ -668,83 +621,42 @@ impl App {
         let node_states = dataflow_tree.get_node_enabled_states();
         let port_states = dataflow_tree.get_port_enabled_states();
 
-        // Step 1: Find all edges that have both ports enabled
+        // Step 1: Find all edges that have both nodes and both ports enabled
         // We need to do this first to determine which nodes have connections
-        let mut valid_edges: Vec<(usize, usize, EdgeConnection)> = Vec::new();
-
-        for edge in &self.loaded_edges {
-            let from_node = &self.loaded_nodes[edge.from_node];
-            let to_node = &self.loaded_nodes[edge.to_node];
-
-            // Check if both nodes are enabled
-            let from_node_enabled = node_states.get(&from_node.id).copied().unwrap_or(true);
-            let to_node_enabled = node_states.get(&to_node.id).copied().unwrap_or(true);
-
-            if !from_node_enabled || !to_node_enabled {
-                continue;
-            }
-
-            // Check if the source port (output) is enabled
-            let from_port_enabled = port_states
-                .get(&(from_node.id.clone(), edge.from_port.clone(), false))
-                .copied()
-                .unwrap_or(true);
-
-            // Check if the target port (input) is enabled
-            let to_port_enabled = port_states
-                .get(&(to_node.id.clone(), edge.to_port.clone(), true))
-                .copied()
-                .unwrap_or(true);
-
-            // Only include edge if both ports are enabled
-            if from_port_enabled && to_port_enabled {
-                valid_edges.push((edge.from_node, edge.to_node, edge.clone()));
-            }
-        }
+        let enabled_edges: Vec<EdgeConnection> = self.loaded_edges.iter()
+            .filter(|edge| {
+                // Check if both nodes are enabled
+                let from_node_enabled = node_states.get(&edge.from_node).copied().unwrap_or(true);
+                let to_node_enabled = node_states.get(&edge.to_node).copied().unwrap_or(true);
+
+                // Check if the source port (output) and target port (input) are enabled
+                let from_port_enabled = port_states
+                    .get(&(edge.from_node.clone(), edge.from_port.clone(), false))
+                    .copied()
+                    .unwrap_or(true);
+                let to_port_enabled = port_states
+                    .get(&(edge.to_node.clone(), edge.to_port.clone(), true))
+                    .copied()
+                    .unwrap_or(true);
+
+                from_node_enabled && to_node_enabled && from_port_enabled && to_port_enabled
+            })
+            .cloned()
+            .collect();
 
         // Step 2: Find nodes that have at least one valid connection
-        let mut connected_node_indices: std::collections::HashSet<usize> = std::collections::HashSet::new();
-        for (from_idx, to_idx, _) in &valid_edges {
-            connected_node_indices.insert(*from_idx);
-            connected_node_indices.insert(*to_idx);
-        }
-
-        // Step 3: Build list of nodes to show (only those with connections)
-        let mut enabled_indices: Vec<usize> = Vec::new();
-        let mut old_to_new_idx: HashMap<usize, usize> = HashMap::new();
-
-        for (old_idx, node) in self.loaded_nodes.iter().enumerate() {
-            // Node must be enabled AND have at least one connection
-            let node_enabled = node_states.get(&node.id).copied().unwrap_or(true);
-            let has_connections = connected_node_indices.contains(&old_idx);
-
-            if node_enabled && has_connections {
-                let new_idx = enabled_indices.len();
-                old_to_new_idx.insert(old_idx, new_idx);
-                enabled_indices.push(old_idx);
-            }
-        }
-
-        // Step 4: Collect enabled nodes
-        let enabled_nodes: Vec<FlowNode> = enabled_indices.iter()
-            .map(|&idx| self.loaded_nodes[idx].clone())
+        let connected_node_ids: std::collections::HashSet<&str> = enabled_edges.iter()
+            .flat_map(|edge| [edge.from_node.as_str(), edge.to_node.as_str()])
             .collect();
 
-        // Step 5: Remap edges to new indices
-        let enabled_edges: Vec<EdgeConnection> = valid_edges.iter()
-            .filter_map(|(from_idx, to_idx, edge)| {
-                if let (Some(&new_from), Some(&new_to)) = (
-                    old_to_new_idx.get(from_idx),
-                    old_to_new_idx.get(to_idx)
-                ) {
-                    let mut new_edge = edge.clone();
-                    new_edge.from_node = new_from;
-                    new_edge.to_node = new_to;
-                    Some(new_edge)
-                } else {
-                    None
-                }
+        // Step 3: Keep nodes that are enabled AND have at least one connection.
+        // Edges reference nodes by id, so they need no remapping.
+        let enabled_nodes: Vec<FlowNode> = self.loaded_nodes.iter()
+            .filter(|node| {
+                let node_enabled = node_states.get(&node.id).copied().unwrap_or(true);
+                node_enabled && connected_node_ids.contains(node.id.as_str())
             })
+            .cloned()
             .collect();
 
         log!("Reloading flow: {} enabled nodes, {} enabled edges",
@@ -755,12 +667,14 @@ impl App {
             nodes: enabled_nodes,
             edges: enabled_edges,
         });
+        self.ui.button(ids!(fold_btn)).set_text(cx, "Fold");
 
         self.update_status_bar(cx);
     }
 
     fn populate_dataflow_tree(&mut self, cx: &mut Cx) {
         // Convert FlowNodes to TreeNodes for the DataflowTree widget
+        let categories = self.ui.flow_canvas(ids!(canvas)).categories();
         let tree_nodes: Vec<TreeNode> = self.loaded_nodes.iter().map(|flow_node| {
             // Get enabled state
             let enabled = self.node_enabled.get(&flow_node.id).copied().unwrap_or(true);
@@ -789,7 +703,10 @@ impl App {
             TreeNode {
                 id: flow_node.id.clone(),
                 label: flow_node.title.clone(),
-                category: flow_node.category,
+                category: flow_node.category.clone(),
+                category_label: categories.get(&flow_node.category)
+                    .map(|style| style.label.clone())
+                    .unwrap_or_default(),
                 enabled,
                 ports,
             }
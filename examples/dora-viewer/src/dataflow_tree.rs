@@ -2,8 +2,9 @@
 
 use makepad_widgets::*;
 use makepad_widgets::file_tree::{FileTree, FileTreeAction};
-use makepad_flow::NodeCategory;
+use makepad_flow::{FlowTheme, NodeCategory};
 use std::collections::HashMap;
+use crate::theme::{style_button, style_input, style_label};
 
 pub fn register_live_design(cx: &mut Cx) {
     self::live_design(cx);
@@ -51,7 +52,8 @@ live_design! {
                     text: "Expand"
                     draw_text: {
                         text_style: { font_size: 9.0 }
-                        fn get_color(self) -> vec4 { return #555555; }
+                        color: #555555
+                        fn get_color(self) -> vec4 { return self.color; }
                     }
                 }
 
@@ -62,7 +64,8 @@ live_design! {
                     text: "Collapse"
                     draw_text: {
                         text_style: { font_size: 9.0 }
-                        fn get_color(self) -> vec4 { return #555555; }
+                        color: #555555
+                        fn get_color(self) -> vec4 { return self.color; }
                     }
                 }
 
@@ -75,9 +78,8 @@ live_design! {
                     text: "Toggle Match"
                     draw_text: {
                         text_style: { font_size: 9.0 }
-                        fn get_color(self) -> vec4 {
-                            return #4A90D9;
-                        }
+                        color: #4A90D9
+                        fn get_color(self) -> vec4 { return self.color; }
                     }
                 }
             }
@@ -89,7 +91,7 @@ live_design! {
             padding: { left: 8, right: 8, bottom: 4 }
             flow: Right, spacing: 4
 
-            <Label> {
+            filter_label = <Label> {
                 width: Fit, height: Fit
                 draw_text: { color: #888888, text_style: { font_size: 9.0 } }
                 text: "Filter:"
@@ -102,7 +104,8 @@ live_design! {
                 text: "All"
                 draw_text: {
                     text_style: { font_size: 9.0 }
-                    fn get_color(self) -> vec4 { return #ffffff; }
+                    color: #ffffff
+                    fn get_color(self) -> vec4 { return self.color; }
                 }
             }
 
@@ -113,7 +116,8 @@ live_design! {
                 text: "MaaS"
                 draw_text: {
                     text_style: { font_size: 9.0 }
-                    fn get_color(self) -> vec4 { return #4a90d9; }
+                    color: #4a90d9
+                    fn get_color(self) -> vec4 { return self.color; }
                 }
             }
 
@@ -124,7 +128,8 @@ live_design! {
                 text: "TTS"
                 draw_text: {
                     text_style: { font_size: 9.0 }
-                    fn get_color(self) -> vec4 { return #22c55e; }
+                    color: #22c55e
+                    fn get_color(self) -> vec4 { return self.color; }
                 }
             }
 
@@ -135,7 +140,8 @@ live_design! {
                 text: "Bridge"
                 draw_text: {
                     text_style: { font_size: 9.0 }
-                    fn get_color(self) -> vec4 { return #f59e0b; }
+                    color: #f59e0b
+                    fn get_color(self) -> vec4 { return self.color; }
                 }
             }
         }
@@ -157,7 +163,8 @@ live_design! {
             text: "Enable All"
             draw_text: {
                 text_style: { font_size: 9.0 }
-                fn get_color(self) -> vec4 { return #ffffff; }
+                color: #ffffff
+                fn get_color(self) -> vec4 { return self.color; }
             }
         }
 
@@ -168,7 +175,8 @@ live_design! {
             text: "Disable All"
             draw_text: {
                 text_style: { font_size: 9.0 }
-                fn get_color(self) -> vec4 { return #ffffff; }
+                color: #ffffff
+                fn get_color(self) -> vec4 { return self.color; }
             }
         }
 
@@ -179,7 +187,8 @@ live_design! {
             text: "Toggle Match"
             draw_text: {
                 text_style: { font_size: 9.0 }
-                fn get_color(self) -> vec4 { return #ffffff; }
+                color: #ffffff
+                fn get_color(self) -> vec4 { return self.color; }
             }
         }
 
@@ -210,12 +219,15 @@ live_design! {
                 indent_width: 14.0
 
                 draw_bg: {
+                    instance bg_color: #ffffff
+                    instance hover_color: #e8f4fd
+                    instance active_color: #d0e8ff
                     fn pixel(self) -> vec4 {
                         let sdf = Sdf2d::viewport(self.pos * self.rect_size);
                         sdf.rect(0., 0., self.rect_size.x, self.rect_size.y);
                         sdf.fill(mix(
-                            mix(#ffffff, #e8f4fd, self.hover),
-                            #d0e8ff,
+                            mix(self.bg_color, self.hover_color, self.hover),
+                            self.active_color,
                             self.active
                         ));
                         return sdf.result;
@@ -224,14 +236,18 @@ live_design! {
 
                 draw_text: {
                     text_style: { font_size: 10.0 }
+                    color: #555555
+                    instance active_color: #333333
                     fn get_color(self) -> vec4 {
-                        return mix(#555555, #333333, self.active);
+                        return mix(self.color, self.active_color, self.active);
                     }
                 }
 
                 draw_icon: {
+                    instance icon_color: #888888
+                    instance hover_color: #666666
                     fn get_color(self) -> vec4 {
-                        return mix(#888888, #666666, self.hover);
+                        return mix(self.icon_color, self.hover_color, self.hover);
                     }
                 }
             }
@@ -241,12 +257,15 @@ live_design! {
                 indent_width: 14.0
 
                 draw_bg: {
+                    instance bg_color: #ffffff
+                    instance hover_color: #e8f4fd
+                    instance active_color: #d0e8ff
                     fn pixel(self) -> vec4 {
                         let sdf = Sdf2d::viewport(self.pos * self.rect_size);
                         sdf.rect(0., 0., self.rect_size.x, self.rect_size.y);
                         sdf.fill(mix(
-                            mix(#ffffff, #e8f4fd, self.hover),
-                            #d0e8ff,
+                            mix(self.bg_color, self.hover_color, self.hover),
+                            self.active_color,
                             self.active
                         ));
                         return sdf.result;
@@ -255,21 +274,26 @@ live_design! {
 
                 draw_text: {
                     text_style: { font_size: 10.0 }
+                    color: #333333
+                    instance active_color: #222222
                     fn get_color(self) -> vec4 {
-                        return mix(#333333, #222222, self.active);
+                        return mix(self.color, self.active_color, self.active);
                     }
                 }
 
                 draw_icon: {
+                    instance icon_color: #4a90d9
+                    instance hover_color: #3080c9
                     fn get_color(self) -> vec4 {
-                        return mix(#4a90d9, #3080c9, self.hover);
+                        return mix(self.icon_color, self.hover_color, self.hover);
                     }
                 }
             }
 
             filler: {
+                instance bg_color: #ffffff
                 fn pixel(self) -> vec4 {
-                    return #ffffff;
+                    return self.bg_color;
                 }
             }
         }
@@ -282,6 +306,8 @@ pub struct TreeNode {
     pub id: String,
     pub label: String,
     pub category: NodeCategory,
+    /// Shown before the label; empty for uncategorized nodes
+    pub category_label: String,
     pub enabled: bool,
     pub ports: Vec<TreePort>,
 }
@@ -366,15 +392,15 @@ impl WidgetMatchEvent for DataflowTreeHeader {
         }
         if self.view.button(ids!(filter_maas)).clicked(actions) {
             cx.widget_action(self.widget_uid(), &scope.path,
-                DataflowTreeAction::FilterCategory { category: Some(NodeCategory::MaaS) });
+                DataflowTreeAction::FilterCategory { category: Some(NodeCategory::new("MaaS")) });
         }
         if self.view.button(ids!(filter_tts)).clicked(actions) {
             cx.widget_action(self.widget_uid(), &scope.path,
-                DataflowTreeAction::FilterCategory { category: Some(NodeCategory::TTS) });
+                DataflowTreeAction::FilterCategory { category: Some(NodeCategory::new("TTS")) });
         }
         if self.view.button(ids!(filter_bridge)).clicked(actions) {
             cx.widget_action(self.widget_uid(), &scope.path,
-                DataflowTreeAction::FilterCategory { category: Some(NodeCategory::Bridge) });
+                DataflowTreeAction::FilterCategory { category: Some(NodeCategory::new("Bridge")) });
         }
 
         if self.view.button(ids!(expand_all)).clicked(actions) {
@@ -430,6 +456,17 @@ impl DataflowTreeFooterRef {
             inner.view.label(ids!(node_count)).set_text(cx, &format!("{} nodes", count));
         }
     }
+
+    pub fn set_theme(&self, cx: &mut Cx, theme: &FlowTheme) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.view.apply_over(cx, live! { draw_bg: { color: (theme.panel_background) } });
+            style_button(cx, &inner.view.button(ids!(enable_selected)), theme.positive, theme.on_accent);
+            style_button(cx, &inner.view.button(ids!(disable_selected)), theme.negative, theme.on_accent);
+            style_button(cx, &inner.view.button(ids!(toggle_matching)), theme.accent, theme.on_accent);
+            style_label(cx, &inner.view.label(ids!(node_count)), theme.text_muted);
+            inner.view.redraw(cx);
+        }
+    }
 }
 
 // ============================================================================
@@ -984,14 +1021,10 @@ impl DataflowTree {
                 let any_enabled = node.ports.iter().any(|p| p.enabled);
                 if all_enabled { "●" } else if any_enabled { "◐" } else { "○" }
             };
-            let category_prefix = match node.category {
-                NodeCategory::MaaS => "[MaaS]",
-                NodeCategory::TTS => "[TTS]",
-                NodeCategory::Bridge => "[Bridge]",
-                NodeCategory::Controller => "[Ctrl]",
-                NodeCategory::MoFA => "[MoFA]",
-                NodeCategory::Segmenter => "[Seg]",
-                NodeCategory::Default => "",
+            let category_prefix = if node.category_label.is_empty() {
+                String::new()
+            } else {
+                format!("[{}]", node.category_label)
             };
 
             let node_name = if category_prefix.is_empty() {
@@ -1059,8 +1092,8 @@ impl DataflowTree {
         }
 
         // Category filter
-        if let Some(cat) = self.category_filter {
-            if node.category != cat {
+        if let Some(cat) = &self.category_filter {
+            if node.category != *cat {
                 return false;
             }
         }
@@ -1141,6 +1174,34 @@ impl DataflowTree {
 
 // Widget reference extension for easier access from App
 impl DataflowTreeRef {
+    /// Recolor rows, icons and the empty area below them
+    pub fn set_theme(&self, cx: &mut Cx, theme: &FlowTheme) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.file_tree.apply_over(cx, live! {
+                file_node: {
+                    draw_bg: {
+                        bg_color: (theme.panel_surface)
+                        hover_color: (theme.accent_soft)
+                        active_color: (theme.accent_strong)
+                    }
+                    draw_text: { color: (theme.button_text), active_color: (theme.text) }
+                    draw_icon: { icon_color: (theme.text_hint), hover_color: (theme.text_muted) }
+                }
+                folder_node: {
+                    draw_bg: {
+                        bg_color: (theme.panel_surface)
+                        hover_color: (theme.accent_soft)
+                        active_color: (theme.accent_strong)
+                    }
+                    draw_text: { color: (theme.text), active_color: (theme.text) }
+                    draw_icon: { icon_color: (theme.accent), hover_color: (theme.accent) }
+                }
+                filler: { bg_color: (theme.panel_surface) }
+            });
+            inner.file_tree.redraw(cx);
+        }
+    }
+
     pub fn set_nodes(&self, cx: &mut Cx, nodes: Vec<TreeNode>) {
         if let Some(mut inner) = self.borrow_mut() {
             inner.set_nodes(cx, nodes);
@@ -1292,6 +1353,23 @@ impl DataflowTreeRef {
 
 // Action helper for the combined panel
 impl DataflowTreeHeaderRef {
+    pub fn set_theme(&self, cx: &mut Cx, theme: &FlowTheme) {
+        if let Some(mut inner) = self.borrow_mut() {
+            let view = &inner.view;
+            style_input(cx, &view.text_input(ids!(search_input)), theme.input_background, theme.text);
+            style_button(cx, &view.button(ids!(expand_all)), theme.button_background, theme.button_text);
+            style_button(cx, &view.button(ids!(collapse_all)), theme.button_background, theme.button_text);
+            style_button(cx, &view.button(ids!(toggle_match)), theme.accent_soft, theme.accent);
+            style_label(cx, &view.label(ids!(filter_label)), theme.text_hint);
+            style_button(cx, &view.button(ids!(filter_all)), theme.accent, theme.on_accent);
+            style_button(cx, &view.button(ids!(filter_maas)), theme.accent_soft, theme.accent);
+            style_button(cx, &view.button(ids!(filter_tts)), theme.positive_soft, theme.positive);
+            style_button(cx, &view.button(ids!(filter_bridge)), theme.warning_soft, theme.warning);
+            inner.view.apply_over(cx, live! { draw_bg: { color: (theme.panel_background) } });
+            inner.view.redraw(cx);
+        }
+    }
+
     pub fn search_changed(&self, actions: &Actions) -> Option<String> {
         if let Some(item) = actions.find_widget_action(self.widget_uid()) {
             if let DataflowTreeAction::SearchChanged { text } = item.cast() {
@@ -1,6 +1,8 @@
 #![allow(dead_code)]
 
 use makepad_widgets::*;
+use makepad_flow::FlowTheme;
+use crate::theme::{style_button, style_input, style_label};
 
 pub fn register_live_design(cx: &mut Cx) {
     self::live_design(cx);
@@ -113,7 +115,7 @@ live_design! {
                 width: Fill, height: Fit
                 flow: Right, spacing: 4
 
-                <Label> {
+                level_label = <Label> {
                     width: Fit, height: Fit
                     draw_text: {
                         color: #888888
@@ -374,6 +376,23 @@ impl LogPanel {
 
 // Widget reference extension for easier access from App
 impl LogPanelRef {
+    pub fn set_theme(&self, cx: &mut Cx, theme: &FlowTheme) {
+        if let Some(mut inner) = self.borrow_mut() {
+            let view = &inner.view;
+            style_input(cx, &view.text_input(ids!(log_search)), theme.log_control, theme.on_accent);
+            style_label(cx, &view.label(ids!(level_label)), theme.log_debug);
+            style_button(cx, &view.button(ids!(filter_all_levels)), theme.log_control_active, theme.on_accent);
+            style_button(cx, &view.button(ids!(filter_debug)), theme.log_control, theme.log_debug);
+            style_button(cx, &view.button(ids!(filter_info)), theme.log_control, theme.log_info);
+            style_button(cx, &view.button(ids!(filter_warn)), theme.log_control, theme.log_warn);
+            style_button(cx, &view.button(ids!(filter_error)), theme.log_control, theme.log_error);
+            style_label(cx, &view.label(ids!(entry_count)), theme.log_muted);
+            style_button(cx, &view.button(ids!(clear_btn)), theme.log_danger_background, theme.log_error);
+            inner.view.apply_over(cx, live! { draw_bg: { color: (theme.log_background) } });
+            inner.view.redraw(cx);
+        }
+    }
+
     pub fn set_entries(&self, cx: &mut Cx, entries: Vec<LogEntry>) {
         if let Some(mut inner) = self.borrow_mut() {
             inner.set_entries(cx, entries);
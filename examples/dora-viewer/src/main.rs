@@ -1,6 +1,7 @@
 mod app;
 mod dataflow_tree;
 mod log_panel;
+mod theme;
 
 fn main() {
     app::app_main()
@@ -0,0 +1,32 @@
+//! Helpers to recolor the viewer's widgets from a `FlowTheme`
+
+use makepad_widgets::*;
+
+/// Background and text color of a button
+pub fn style_button(cx: &mut Cx, button: &ButtonRef, background: Vec4, text: Vec4) {
+    button.apply_over(cx, live! {
+        draw_bg: { color: (background) }
+        draw_text: { color: (text) }
+    });
+}
+
+pub fn style_label(cx: &mut Cx, label: &LabelRef, color: Vec4) {
+    label.apply_over(cx, live! {
+        draw_text: { color: (color) }
+    });
+}
+
+/// Background of a view with `show_bg: true`
+pub fn style_background(cx: &mut Cx, view: &ViewRef, color: Vec4) {
+    view.apply_over(cx, live! {
+        draw_bg: { color: (color) }
+    });
+}
+
+/// Background and text color of a text input
+pub fn style_input(cx: &mut Cx, input: &TextInputRef, background: Vec4, text: Vec4) {
+    input.apply_over(cx, live! {
+        draw_bg: { color: (background) }
+        draw_text: { color: (text) }
+    });
+}
@@ -6,6 +6,7 @@ live_design! {
     use link::shaders::*;
     use link::widgets::*;
     use makepad_flow::flow_canvas::*;
+    use makepad_flow::palette::*;
 
     // Main application
     App = {{App}} {
@@ -42,6 +43,18 @@ live_design! {
                         values: [w1, w2, w3, w4]
                     }
 
+                    <Label> { draw_text: { text_style: { font_size: 10.0 }, color: #a0a0b0 }, text: "Grid:" }
+                    grid_dropdown = <DropDown> {
+                        width: 80, height: 28
+                        draw_bg: { color: #3d3d5c }
+                        labels: ["Dots", "Lines", "None"]
+                        values: [dots, lines, none]
+                    }
+                    snap_check = <CheckBox> {
+                        text: "Snap"
+                        draw_text: { color: #a0a0b0 }
+                    }
+
                     <View> { width: Fill, height: 1 }
                     add_node_btn = <Button> {
                         width: Fit, height: 32, padding: { left: 12, right: 12 }
@@ -63,10 +76,35 @@ live_design! {
                         draw_bg: { color: #3d3d5c }
                         text: "Clear"
                     }
+                    save_btn = <Button> {
+                        width: Fit, height: 32, padding: { left: 12, right: 12 }
+                        draw_bg: { color: #3d3d5c }
+                        text: "Save"
+                    }
+                    load_btn = <Button> {
+                        width: Fit, height: 32, padding: { left: 12, right: 12 }
+                        draw_bg: { color: #3d3d5c }
+                        text: "Load"
+                    }
                 }
 
-                // Canvas area
-                canvas = <FlowCanvas> {}
+                // Node palette and canvas area
+                <View> {
+                    width: Fill, height: Fill, flow: Right
+
+                    palette = <FlowPalette> {
+                        width: 180
+                        draw_bg: { color: #252538 }
+                        search_input = {
+                            draw_bg: { color: #3d3d5c }
+                            draw_text: { color: #e0e0e0 }
+                        }
+                        header_color: #8080a0
+                        text_color: #e0e0e0
+                        hover_color: #3d3d5c
+                    }
+                    canvas = <FlowCanvas> {}
+                }
 
                 // Status bar
                 status_bar = <View> {
@@ -101,11 +139,21 @@ impl LiveRegister for App {
     }
 }
 
+impl App {
+    /// Where the Save/Load buttons persist the flow
+    const FLOW_FILE: &'static str = "flow.json";
+}
+
 impl MatchEvent for App {
+    fn handle_startup(&mut self, cx: &mut Cx) {
+        // Drag node kinds from the palette onto the canvas
+        self.ui.flow_palette(ids!(palette)).set_canvas(cx, &self.ui.flow_canvas(ids!(canvas)));
+    }
+
     fn handle_actions(&mut self, cx: &mut Cx, actions: &Actions) {
         // Handle button clicks - send commands as widget actions
         if self.ui.button(ids!(add_node_btn)).clicked(actions) {
-            cx.action(FlowCanvasCommand::AddNode);
+            cx.action(FlowCanvasCommand::AddNode { template: "processor".to_string() });
         }
 
         if self.ui.button(ids!(delete_btn)).clicked(actions) {
@@ -120,6 +168,30 @@ impl MatchEvent for App {
             cx.action(FlowCanvasCommand::Clear);
         }
 
+        // Persist the flow (graph + viewport) as JSON
+        if self.ui.button(ids!(save_btn)).clicked(actions) {
+            let canvas = self.ui.flow_canvas(ids!(canvas));
+            match canvas.save_to_json().map(|json| std::fs::write(Self::FLOW_FILE, json)) {
+                Ok(Ok(())) => log!("Saved flow to {}", Self::FLOW_FILE),
+                Ok(Err(e)) => log!("Failed to write {}: {}", Self::FLOW_FILE, e),
+                Err(e) => log!("Failed to serialize flow: {}", e),
+            }
+        }
+
+        if self.ui.button(ids!(load_btn)).clicked(actions) {
+            let canvas = self.ui.flow_canvas(ids!(canvas));
+            match std::fs::read_to_string(Self::FLOW_FILE) {
+                Ok(json) => {
+                    if let Err(e) = canvas.load_from_json(cx, &json) {
+                        log!("Failed to load {}: {}", Self::FLOW_FILE, e);
+                    }
+                    let text = format!("Nodes: {} | Edges: {}", canvas.node_count(), canvas.edge_count());
+                    self.ui.label(ids!(count_label)).set_text(cx, &text);
+                }
+                Err(e) => log!("Failed to read {}: {}", Self::FLOW_FILE, e),
+            }
+        }
+
         // Handle line style dropdown
         if let Some(index) = self.ui.drop_down(ids!(line_style_dropdown)).changed(actions) {
             cx.action(FlowCanvasCommand::SetLineStyle(index as f32));
@@ -130,6 +202,20 @@ impl MatchEvent for App {
             cx.action(FlowCanvasCommand::SetLineWidth((index + 1) as f32));
         }
 
+        // Handle grid dropdown and snap toggle
+        if let Some(index) = self.ui.drop_down(ids!(grid_dropdown)).changed(actions) {
+            let style = match index {
+                0 => GridStyle::Dots,
+                1 => GridStyle::Lines,
+                _ => GridStyle::None,
+            };
+            self.ui.flow_canvas(ids!(canvas)).set_grid_style(cx, style);
+        }
+
+        if let Some(snap) = self.ui.check_box(ids!(snap_check)).changed(actions) {
+            self.ui.flow_canvas(ids!(canvas)).set_snap_to_grid(snap);
+        }
+
         // Update status on canvas actions
         for action in actions {
             if let FlowCanvasAction::StatusUpdate { nodes, edges } = action.cast() {